@@ -0,0 +1,116 @@
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Constellation {
+    Gps,
+    Galileo,
+    Beidou,
+    Glonass,
+    /// Including the L1S augmentation (SLAS)
+    Qzss,
+    Sbas,
+}
+
+impl Constellation {
+    pub const ALL: [Constellation; 6] = [
+        Constellation::Gps,
+        Constellation::Galileo,
+        Constellation::Beidou,
+        Constellation::Glonass,
+        Constellation::Qzss,
+        Constellation::Sbas,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Constellation::Gps => "gps",
+            Constellation::Galileo => "galileo",
+            Constellation::Beidou => "beidou",
+            Constellation::Glonass => "glonass",
+            Constellation::Qzss => "qzss",
+            Constellation::Sbas => "sbas",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Constellation> {
+        Constellation::ALL.into_iter().find(|c| c.name() == name)
+    }
+}
+
+/// GNSS whose signals the receiver tracks. Fewer of them draw less power, at the cost of the
+/// availability of a fix.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Constellations {
+    pub gps: bool,
+    pub galileo: bool,
+    pub beidou: bool,
+    pub glonass: bool,
+    pub qzss: bool,
+    pub sbas: bool,
+}
+
+impl Default for Constellations {
+    /// Same as the default of the receiver.
+    fn default() -> Self {
+        Self {
+            gps: true,
+            galileo: true,
+            beidou: true,
+            glonass: false,
+            qzss: true,
+            sbas: true,
+        }
+    }
+}
+
+impl Constellations {
+    pub fn contains(&self, c: Constellation) -> bool {
+        match c {
+            Constellation::Gps => self.gps,
+            Constellation::Galileo => self.galileo,
+            Constellation::Beidou => self.beidou,
+            Constellation::Glonass => self.glonass,
+            Constellation::Qzss => self.qzss,
+            Constellation::Sbas => self.sbas,
+        }
+    }
+
+    pub fn set(&mut self, c: Constellation, enabled: bool) {
+        *match c {
+            Constellation::Gps => &mut self.gps,
+            Constellation::Galileo => &mut self.galileo,
+            Constellation::Beidou => &mut self.beidou,
+            Constellation::Glonass => &mut self.glonass,
+            Constellation::Qzss => &mut self.qzss,
+            Constellation::Sbas => &mut self.sbas,
+        } = enabled;
+    }
+
+    /// At least one of them has to fix the position on its own, QZSS and SBAS only augment GPS.
+    pub fn is_valid(&self) -> bool {
+        self.gps || self.galileo || self.beidou || self.glonass
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::constellations::*;
+
+    #[test]
+    fn set() {
+        let mut c = Constellations::default();
+        for x in Constellation::ALL {
+            c.set(x, false);
+            assert!(!c.contains(x));
+            assert_eq!(Constellation::from_name(x.name()), Some(x));
+        }
+        assert!(!c.is_valid());
+        c.set(Constellation::Qzss, true);
+        c.set(Constellation::Sbas, true);
+        assert!(!c.is_valid());
+        c.set(Constellation::Glonass, true);
+        assert!(c.is_valid());
+        assert_eq!(Constellation::from_name("navic"), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! Header put in front of the values stored on flash, so that they can be identified and older
+//! layouts can be upgraded.
+//!
+//! ```text
+//! magic: u32, version: u16, crc: u32 (CRC-32 of the payload), payload (postcard)
+//! ```
+
+use misc::crc::crc32;
+
+pub const HEADER_LEN: usize = 10;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    BufferTooSmall,
+    InvalidHeader,
+    Crc,
+    UnsupportedVersion(u16),
+    Postcard(postcard::Error),
+}
+
+impl From<postcard::Error> for Error {
+    fn from(e: postcard::Error) -> Self {
+        Self::Postcard(e)
+    }
+}
+
+pub trait Versioned: serde::Serialize + serde::de::DeserializeOwned {
+    /// Identifies the type of the value.
+    const MAGIC: u32;
+    /// Has to be incremented whenever the serialized layout changes.
+    const VERSION: u16;
+
+    /// Upgrades `payload` serialized with an older `version`.
+    fn migrate(version: u16, payload: &[u8]) -> Result<Self, Error> {
+        let _ = payload;
+        Err(Error::UnsupportedVersion(version))
+    }
+}
+
+pub fn encode<'b, T: Versioned>(value: &T, buf: &'b mut [u8]) -> Result<&'b mut [u8], Error> {
+    let (header, payload) = buf
+        .split_at_mut_checked(HEADER_LEN)
+        .ok_or(Error::BufferTooSmall)?;
+    let len = postcard::to_slice(value, payload)?.len();
+    header[0..4].copy_from_slice(&T::MAGIC.to_le_bytes());
+    header[4..6].copy_from_slice(&T::VERSION.to_le_bytes());
+    header[6..10].copy_from_slice(&crc32(&payload[..len]).to_le_bytes());
+    Ok(&mut buf[..HEADER_LEN + len])
+}
+
+pub fn decode<T: Versioned>(bytes: &[u8]) -> Result<T, Error> {
+    let (header, payload) = bytes
+        .split_at_checked(HEADER_LEN)
+        .ok_or(Error::InvalidHeader)?;
+    let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    let crc = u32::from_le_bytes([header[6], header[7], header[8], header[9]]);
+    if magic != T::MAGIC {
+        return Err(Error::InvalidHeader);
+    }
+    if crc != crc32(payload) {
+        return Err(Error::Crc);
+    }
+    match version {
+        v if v == T::VERSION => Ok(postcard::from_bytes(payload)?),
+        v if v < T::VERSION => T::migrate(v, payload),
+        v => Err(Error::UnsupportedVersion(v)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::envelope::*;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct V1 {
+        a: u8,
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct V2 {
+        a: u8,
+        b: u8,
+    }
+
+    impl Versioned for V1 {
+        const MAGIC: u32 = 0x1234_5678;
+        const VERSION: u16 = 1;
+    }
+
+    impl Versioned for V2 {
+        const MAGIC: u32 = 0x1234_5678;
+        const VERSION: u16 = 2;
+
+        fn migrate(version: u16, payload: &[u8]) -> Result<Self, Error> {
+            match version {
+                1 => {
+                    let v1: V1 = postcard::from_bytes(payload)?;
+                    Ok(V2 { a: v1.a, b: 42 })
+                }
+                v => Err(Error::UnsupportedVersion(v)),
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0; 32];
+        let bytes = encode(&V2 { a: 1, b: 2 }, &mut buf).unwrap();
+        assert_eq!(
+            bytes,
+            [0x78, 0x56, 0x34, 0x12, 2, 0, 0x92, 0x42, 0xcc, 0xb6, 1, 2]
+        );
+        assert_eq!(decode::<V2>(bytes), Ok(V2 { a: 1, b: 2 }));
+    }
+
+    #[test]
+    fn migrate() {
+        let mut buf = [0; 32];
+        let bytes = encode(&V1 { a: 1 }, &mut buf).unwrap();
+        assert_eq!(decode::<V2>(bytes), Ok(V2 { a: 1, b: 42 }));
+
+        let bytes = encode(&V2 { a: 1, b: 2 }, &mut buf).unwrap();
+        assert_eq!(decode::<V1>(bytes), Err(Error::UnsupportedVersion(2)));
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut buf = [0; 32];
+        let bytes = encode(&V2 { a: 1, b: 2 }, &mut buf).unwrap();
+        bytes[11] = 3;
+        assert_eq!(decode::<V2>(bytes), Err(Error::Crc));
+        bytes[0] = 0;
+        assert_eq!(decode::<V2>(bytes), Err(Error::InvalidHeader));
+        assert_eq!(decode::<V2>(&bytes[..9]), Err(Error::InvalidHeader));
+        assert_eq!(
+            encode(&V2 { a: 1, b: 2 }, &mut buf[..11]),
+            Err(Error::Postcard(postcard::Error::SerializeBufferFull))
+        );
+    }
+}
@@ -0,0 +1,27 @@
+/// Use of the EXTINT pin of the receiver, which is wired to the board.
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "kebab-case")]
+pub enum ExtInt {
+    /// Output, asserted while the receiver has data for the board to read over I2C.
+    #[default]
+    TxReady,
+    /// Input, each edge of the signal wired to it is timestamped by the receiver (UBX-TIM-TM2).
+    /// The board polls the receiver over I2C instead.
+    Timestamp,
+}
+
+impl ExtInt {
+    pub const ALL: [ExtInt; 2] = [ExtInt::TxReady, ExtInt::Timestamp];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExtInt::TxReady => "tx-ready",
+            ExtInt::Timestamp => "timestamp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ExtInt> {
+        ExtInt::ALL.into_iter().find(|e| e.name() == name)
+    }
+}
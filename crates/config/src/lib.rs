@@ -0,0 +1,361 @@
+#![no_std]
+
+use chrono::{FixedOffset, NaiveTime, Timelike};
+
+mod constellations;
+pub mod envelope;
+mod ext_int;
+pub mod migration;
+mod odometer_source;
+mod power_save;
+mod rate;
+mod receiver_storage;
+mod speed_unit;
+pub mod text;
+mod time_pulse;
+
+pub use constellations::{Constellation, Constellations};
+pub use ext_int::ExtInt;
+pub use odometer_source::OdometerSource;
+pub use power_save::PowerSave;
+pub use rate::Rate;
+pub use receiver_storage::ReceiverStorage;
+pub use speed_unit::SpeedUnit;
+pub use time_pulse::TimePulse;
+
+#[derive(Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    pub time_zone_secs: i32,
+    pub rotation: Rotation,
+    pub auto_rotate: Option<AutoRotate>,
+    pub speed_unit: SpeedUnit,
+    pub odometer_source: OdometerSource,
+    pub constellations: Constellations,
+    pub rate: Rate,
+    pub power_save: PowerSave,
+    pub receiver_storage: ReceiverStorage,
+    pub time_pulse: TimePulse,
+    pub ext_int: ExtInt,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            time_zone_secs: 0,
+            rotation: Rotation::new(&[DisplayMode::Time, DisplayMode::Date, DisplayMode::Velocity])
+                .unwrap(),
+            auto_rotate: None,
+            speed_unit: SpeedUnit::KilometersPerHour,
+            odometer_source: OdometerSource::Positions,
+            constellations: Constellations::default(),
+            rate: Rate::default(),
+            power_save: PowerSave::default(),
+            receiver_storage: ReceiverStorage::default(),
+            time_pulse: TimePulse::default(),
+            ext_int: ExtInt::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn time_zone(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.time_zone_secs).unwrap()
+    }
+
+    /// Returns the mode shown after `mode` when SW3 is pressed.
+    ///
+    /// The modes in `rotation` come first, followed by the menu pages which are always
+    /// reachable regardless of `rotation`.
+    pub fn next_mode(&self, mode: DisplayMode) -> DisplayMode {
+        let views = self.rotation.as_slice();
+        if let Some(i) = views.iter().position(|m| *m == mode) {
+            return views.get(i + 1).copied().unwrap_or(DisplayMode::MENU[0]);
+        }
+        if let Some(i) = DisplayMode::MENU.iter().position(|m| *m == mode) {
+            return DisplayMode::MENU.get(i + 1).copied().unwrap_or(views[0]);
+        }
+        views[0]
+    }
+
+    /// Checks the constraints which the types of the fields do not enforce by themselves.
+    pub fn is_valid(&self) -> bool {
+        // `FixedOffset` excludes ±24 hours
+        self.time_zone_secs.unsigned_abs() < 24 * 60 * 60
+            && self
+                .auto_rotate
+                .is_none_or(|a| AutoRotate::new(a.mode, a.duration_secs, a.period_secs).is_some())
+            && self.constellations.is_valid()
+            && self.rate.is_valid()
+            && self.power_save.is_valid()
+            && self.time_pulse.is_valid()
+    }
+
+    pub fn is_reachable(&self, mode: DisplayMode) -> bool {
+        self.rotation.contains(mode) || DisplayMode::MENU.contains(&mode)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayMode {
+    Time,
+    Date,
+    Velocity,
+    MaxSpeed,
+    AverageSpeed,
+    Trip,
+    Odometer,
+    Heading,
+    ConfigTimeZone,
+    ConfigRotation,
+    ConfigAutoRotate,
+    ConfigSpeedUnit,
+    Info,
+    ConfigConstellations,
+    SubSecond,
+    Timing,
+}
+
+impl DisplayMode {
+    /// Modes which can be put in the SW3 rotation.
+    pub const VIEWS: [DisplayMode; 9] = [
+        DisplayMode::Time,
+        DisplayMode::Date,
+        DisplayMode::Velocity,
+        DisplayMode::MaxSpeed,
+        DisplayMode::AverageSpeed,
+        DisplayMode::Trip,
+        DisplayMode::Odometer,
+        DisplayMode::Heading,
+        DisplayMode::SubSecond,
+    ];
+
+    /// Configuration and information pages, always appended to the SW3 rotation.
+    pub const MENU: [DisplayMode; 7] = [
+        DisplayMode::ConfigTimeZone,
+        DisplayMode::ConfigRotation,
+        DisplayMode::ConfigAutoRotate,
+        DisplayMode::ConfigSpeedUnit,
+        DisplayMode::ConfigConstellations,
+        DisplayMode::Info,
+        DisplayMode::Timing,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DisplayMode::Time => "time",
+            DisplayMode::Date => "date",
+            DisplayMode::Velocity => "velocity",
+            DisplayMode::MaxSpeed => "max-speed",
+            DisplayMode::AverageSpeed => "average-speed",
+            DisplayMode::Trip => "trip",
+            DisplayMode::Odometer => "odometer",
+            DisplayMode::Heading => "heading",
+            DisplayMode::ConfigTimeZone => "config-time-zone",
+            DisplayMode::ConfigRotation => "config-rotation",
+            DisplayMode::ConfigAutoRotate => "config-auto-rotate",
+            DisplayMode::ConfigSpeedUnit => "config-speed-unit",
+            DisplayMode::Info => "info",
+            DisplayMode::ConfigConstellations => "config-constellations",
+            DisplayMode::SubSecond => "sub-second",
+            DisplayMode::Timing => "timing",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DisplayMode> {
+        DisplayMode::VIEWS
+            .into_iter()
+            .chain(DisplayMode::MENU)
+            .find(|m| m.name() == name)
+    }
+
+    pub fn is_view(&self) -> bool {
+        DisplayMode::VIEWS.contains(self)
+    }
+}
+
+pub const MAX_ROTATION_LEN: usize = DisplayMode::VIEWS.len();
+
+/// Ordered set of the modes cycled by SW3.
+///
+/// Serialized as the list of the modes in human-readable formats, and as the fixed-size array on
+/// the flash.
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rotation {
+    modes: [DisplayMode; MAX_ROTATION_LEN],
+    len: u8,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Rotation")]
+struct RotationLayout {
+    modes: [DisplayMode; MAX_ROTATION_LEN],
+    len: u8,
+}
+
+impl serde::Serialize for Rotation {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_seq(self.as_slice())
+        } else {
+            RotationLayout {
+                modes: self.modes,
+                len: self.len,
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Rotation {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            let RotationLayout { modes, len } = RotationLayout::deserialize(deserializer)?;
+            return Rotation::new(&modes[..usize::from(len).min(MAX_ROTATION_LEN)])
+                .ok_or_else(|| serde::de::Error::custom("invalid rotation"));
+        }
+
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Rotation;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("a list of distinct modes")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Rotation, A::Error> {
+                let mut modes = [DisplayMode::Time; MAX_ROTATION_LEN];
+                let mut len = 0;
+                while let Some(mode) = seq.next_element()? {
+                    if len == MAX_ROTATION_LEN {
+                        return Err(serde::de::Error::invalid_length(len + 1, &self));
+                    }
+                    modes[len] = mode;
+                    len += 1;
+                }
+                Rotation::new(&modes[..len])
+                    .ok_or_else(|| serde::de::Error::custom("invalid rotation"))
+            }
+        }
+
+        deserializer.deserialize_seq(Visitor)
+    }
+}
+
+impl Rotation {
+    /// Returns `None` if `modes` is empty, too long, has duplicates or contains configuration pages.
+    pub fn new(modes: &[DisplayMode]) -> Option<Self> {
+        if modes.is_empty() || modes.len() > MAX_ROTATION_LEN {
+            return None;
+        }
+        let mut rotation = Self {
+            modes: [DisplayMode::Time; MAX_ROTATION_LEN],
+            len: 0,
+        };
+        for mode in modes {
+            if !mode.is_view() || rotation.contains(*mode) {
+                return None;
+            }
+            rotation.modes[rotation.len as usize] = *mode;
+            rotation.len += 1;
+        }
+        Some(rotation)
+    }
+
+    pub fn as_slice(&self) -> &[DisplayMode] {
+        &self.modes[..self.len as usize]
+    }
+
+    pub fn contains(&self, mode: DisplayMode) -> bool {
+        self.as_slice().contains(&mode)
+    }
+
+    /// Removes `mode` if it is in the rotation, otherwise appends it. The last remaining mode can
+    /// not be removed.
+    pub fn toggle(&mut self, mode: DisplayMode) {
+        if let Some(i) = self.as_slice().iter().position(|m| *m == mode) {
+            if self.len > 1 {
+                self.modes.copy_within(i + 1..self.len as usize, i);
+                self.len -= 1;
+            }
+        } else if mode.is_view() && (self.len as usize) < MAX_ROTATION_LEN {
+            self.modes[self.len as usize] = mode;
+            self.len += 1;
+        }
+    }
+}
+
+/// Shows `mode` for `duration_secs` at the beginning of every `period_secs` while in the time mode.
+/// Periods are aligned to the local midnight, e.g. `{ Date, 3, 60 }` shows the date at seconds 0-2
+/// of every minute. Both values are limited so that they fit in the configuration page.
+#[derive(Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AutoRotate {
+    pub mode: DisplayMode,
+    pub duration_secs: u8,
+    pub period_secs: u16,
+}
+
+impl AutoRotate {
+    /// Choices offered by the auto-rotate configuration page.
+    pub const PRESETS: [Option<AutoRotate>; 4] = [
+        None,
+        Some(AutoRotate::new_unchecked(DisplayMode::Date, 3, 60)),
+        Some(AutoRotate::new_unchecked(DisplayMode::Date, 5, 30)),
+        Some(AutoRotate::new_unchecked(DisplayMode::Velocity, 3, 60)),
+    ];
+
+    pub fn new(mode: DisplayMode, duration_secs: u8, period_secs: u16) -> Option<Self> {
+        let valid = mode.is_view()
+            && mode != DisplayMode::Time
+            && mode != DisplayMode::SubSecond
+            && duration_secs > 0
+            && u16::from(duration_secs) < period_secs
+            && period_secs < 1000;
+        valid.then_some(Self::new_unchecked(mode, duration_secs, period_secs))
+    }
+
+    const fn new_unchecked(mode: DisplayMode, duration_secs: u8, period_secs: u16) -> Self {
+        Self {
+            mode,
+            duration_secs,
+            period_secs,
+        }
+    }
+
+    pub fn is_active(&self, time: NaiveTime) -> bool {
+        time.num_seconds_from_midnight() % u32::from(self.period_secs)
+            < u32::from(self.duration_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::*;
+
+    #[test]
+    fn rotation_round_trip() {
+        let rotation = Rotation::new(&[DisplayMode::Time, DisplayMode::Velocity]).unwrap();
+        let mut buf = [0; 16];
+        let bytes = postcard::to_slice(&rotation, &mut buf).unwrap();
+        assert!(postcard::from_bytes::<Rotation>(bytes).unwrap() == rotation);
+    }
+
+    #[test]
+    fn rotation_invalid_layout() {
+        // empty, duplicated, a configuration page
+        for (modes, len) in [(0, 0), (0, 2), (DisplayMode::ConfigRotation as u8, 1)] {
+            let mut bytes = [modes; MAX_ROTATION_LEN + 1];
+            bytes[MAX_ROTATION_LEN] = len;
+            assert!(postcard::from_bytes::<Rotation>(&bytes).is_err());
+        }
+    }
+}
@@ -0,0 +1,539 @@
+//! Layouts of [`Config`] stored by older firmware, and how to upgrade them.
+//!
+//! When changing the layout of `Config` (including the types of its fields), keep its current
+//! layout here as `ConfigVn`, increment `Config::VERSION` and add a case to `Config::migrate`.
+
+use crate::envelope::{Error, Versioned};
+use crate::{
+    AutoRotate, Config, Constellations, DisplayMode, ExtInt, OdometerSource, PowerSave, Rate,
+    ReceiverStorage, Rotation, SpeedUnit, TimePulse,
+};
+
+impl Versioned for Config {
+    const MAGIC: u32 = u32::from_le_bytes(*b"GCFG");
+    const VERSION: u16 = 9;
+
+    fn migrate(version: u16, payload: &[u8]) -> Result<Self, Error> {
+        match version {
+            1 => Ok(postcard::from_bytes::<ConfigV1>(payload)?.into()),
+            2 => Ok(postcard::from_bytes::<ConfigV2>(payload)?.into()),
+            3 => Ok(postcard::from_bytes::<ConfigV3>(payload)?.into()),
+            4 => Ok(postcard::from_bytes::<ConfigV4>(payload)?.into()),
+            5 => Ok(postcard::from_bytes::<ConfigV5>(payload)?.into()),
+            6 => Ok(postcard::from_bytes::<ConfigV6>(payload)?.into()),
+            7 => Ok(postcard::from_bytes::<ConfigV7>(payload)?.into()),
+            8 => Ok(postcard::from_bytes::<ConfigV8>(payload)?.into()),
+            v => Err(Error::UnsupportedVersion(v)),
+        }
+    }
+}
+
+/// [`Rotation`] of up to 8 modes, before [`DisplayMode::SubSecond`] was added.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RotationV1 {
+    modes: [DisplayMode; 8],
+    len: u8,
+}
+
+impl From<RotationV1> for Rotation {
+    fn from(v1: RotationV1) -> Self {
+        let len = usize::from(v1.len).min(v1.modes.len());
+        Rotation::new(&v1.modes[..len]).unwrap_or(Config::default().rotation)
+    }
+}
+
+/// The time zone only. Stored without the envelope, COBS-encoded, at the last sector of the flash.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConfigV1 {
+    time_zone_secs: i32,
+}
+
+impl From<ConfigV1> for Config {
+    fn from(v1: ConfigV1) -> Self {
+        Self {
+            time_zone_secs: v1.time_zone_secs,
+            ..Default::default()
+        }
+    }
+}
+
+/// Without `constellations`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConfigV2 {
+    time_zone_secs: i32,
+    rotation: RotationV1,
+    auto_rotate: Option<AutoRotate>,
+    speed_unit: SpeedUnit,
+    odometer_source: OdometerSource,
+}
+
+impl From<ConfigV2> for Config {
+    fn from(v2: ConfigV2) -> Self {
+        Self {
+            time_zone_secs: v2.time_zone_secs,
+            rotation: v2.rotation.into(),
+            auto_rotate: v2.auto_rotate,
+            speed_unit: v2.speed_unit,
+            odometer_source: v2.odometer_source,
+            ..Default::default()
+        }
+    }
+}
+
+/// Without `rate`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConfigV3 {
+    time_zone_secs: i32,
+    rotation: RotationV1,
+    auto_rotate: Option<AutoRotate>,
+    speed_unit: SpeedUnit,
+    odometer_source: OdometerSource,
+    constellations: Constellations,
+}
+
+impl From<ConfigV3> for Config {
+    fn from(v3: ConfigV3) -> Self {
+        Self {
+            time_zone_secs: v3.time_zone_secs,
+            rotation: v3.rotation.into(),
+            auto_rotate: v3.auto_rotate,
+            speed_unit: v3.speed_unit,
+            odometer_source: v3.odometer_source,
+            constellations: v3.constellations,
+            ..Default::default()
+        }
+    }
+}
+
+/// Without `power_save`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConfigV4 {
+    time_zone_secs: i32,
+    rotation: RotationV1,
+    auto_rotate: Option<AutoRotate>,
+    speed_unit: SpeedUnit,
+    odometer_source: OdometerSource,
+    constellations: Constellations,
+    rate: Rate,
+}
+
+impl From<ConfigV4> for Config {
+    fn from(v4: ConfigV4) -> Self {
+        Self {
+            time_zone_secs: v4.time_zone_secs,
+            rotation: v4.rotation.into(),
+            auto_rotate: v4.auto_rotate,
+            speed_unit: v4.speed_unit,
+            odometer_source: v4.odometer_source,
+            constellations: v4.constellations,
+            rate: v4.rate,
+            ..Default::default()
+        }
+    }
+}
+
+/// Without `receiver_storage`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConfigV5 {
+    time_zone_secs: i32,
+    rotation: RotationV1,
+    auto_rotate: Option<AutoRotate>,
+    speed_unit: SpeedUnit,
+    odometer_source: OdometerSource,
+    constellations: Constellations,
+    rate: Rate,
+    power_save: PowerSave,
+}
+
+impl From<ConfigV5> for Config {
+    fn from(v5: ConfigV5) -> Self {
+        Self {
+            time_zone_secs: v5.time_zone_secs,
+            rotation: v5.rotation.into(),
+            auto_rotate: v5.auto_rotate,
+            speed_unit: v5.speed_unit,
+            odometer_source: v5.odometer_source,
+            constellations: v5.constellations,
+            rate: v5.rate,
+            power_save: v5.power_save,
+            ..Default::default()
+        }
+    }
+}
+
+/// Without `time_pulse`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConfigV6 {
+    time_zone_secs: i32,
+    rotation: RotationV1,
+    auto_rotate: Option<AutoRotate>,
+    speed_unit: SpeedUnit,
+    odometer_source: OdometerSource,
+    constellations: Constellations,
+    rate: Rate,
+    power_save: PowerSave,
+    receiver_storage: ReceiverStorage,
+}
+
+impl From<ConfigV6> for Config {
+    fn from(v6: ConfigV6) -> Self {
+        Self {
+            time_zone_secs: v6.time_zone_secs,
+            rotation: v6.rotation.into(),
+            auto_rotate: v6.auto_rotate,
+            speed_unit: v6.speed_unit,
+            odometer_source: v6.odometer_source,
+            constellations: v6.constellations,
+            rate: v6.rate,
+            power_save: v6.power_save,
+            receiver_storage: v6.receiver_storage,
+            ..Default::default()
+        }
+    }
+}
+
+/// Without `ext_int`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConfigV7 {
+    time_zone_secs: i32,
+    rotation: RotationV1,
+    auto_rotate: Option<AutoRotate>,
+    speed_unit: SpeedUnit,
+    odometer_source: OdometerSource,
+    constellations: Constellations,
+    rate: Rate,
+    power_save: PowerSave,
+    receiver_storage: ReceiverStorage,
+    time_pulse: TimePulse,
+}
+
+impl From<ConfigV7> for Config {
+    fn from(v7: ConfigV7) -> Self {
+        Self {
+            time_zone_secs: v7.time_zone_secs,
+            rotation: v7.rotation.into(),
+            auto_rotate: v7.auto_rotate,
+            speed_unit: v7.speed_unit,
+            odometer_source: v7.odometer_source,
+            constellations: v7.constellations,
+            rate: v7.rate,
+            power_save: v7.power_save,
+            receiver_storage: v7.receiver_storage,
+            time_pulse: v7.time_pulse,
+            ..Default::default()
+        }
+    }
+}
+
+/// With [`RotationV1`].
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConfigV8 {
+    time_zone_secs: i32,
+    rotation: RotationV1,
+    auto_rotate: Option<AutoRotate>,
+    speed_unit: SpeedUnit,
+    odometer_source: OdometerSource,
+    constellations: Constellations,
+    rate: Rate,
+    power_save: PowerSave,
+    receiver_storage: ReceiverStorage,
+    time_pulse: TimePulse,
+    ext_int: ExtInt,
+}
+
+impl From<ConfigV8> for Config {
+    fn from(v8: ConfigV8) -> Self {
+        Self {
+            time_zone_secs: v8.time_zone_secs,
+            rotation: v8.rotation.into(),
+            auto_rotate: v8.auto_rotate,
+            speed_unit: v8.speed_unit,
+            odometer_source: v8.odometer_source,
+            constellations: v8.constellations,
+            rate: v8.rate,
+            power_save: v8.power_save,
+            receiver_storage: v8.receiver_storage,
+            time_pulse: v8.time_pulse,
+            ext_int: v8.ext_int,
+        }
+    }
+}
+
+/// Reads the config written before the envelope was introduced. `image` is the beginning of the
+/// sector where it was stored.
+pub fn from_legacy(image: &mut [u8]) -> Option<Config> {
+    let v1: ConfigV1 = postcard::from_bytes_cobs(image).ok()?;
+    // an erased sector may decode into nonsense
+    Some(Config::from(v1)).filter(Config::is_valid)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::envelope::{decode, encode};
+    use crate::migration::*;
+
+    const TZ_JST: i32 = 9 * 60 * 60;
+
+    fn config_v9() -> Config {
+        Config {
+            time_zone_secs: TZ_JST,
+            rotation: Rotation::new(&[DisplayMode::Time, DisplayMode::Velocity]).unwrap(),
+            auto_rotate: AutoRotate::new(DisplayMode::Date, 3, 60),
+            speed_unit: SpeedUnit::Knots,
+            odometer_source: OdometerSource::Receiver,
+            constellations: Constellations {
+                glonass: true,
+                ..Default::default()
+            },
+            rate: Rate {
+                measurement_hz: 10,
+                navigation_hz: 5,
+            },
+            power_save: PowerSave::OnOff { period_secs: 60 },
+            receiver_storage: ReceiverStorage::Bbr,
+            time_pulse: TimePulse {
+                cable_delay_ns: 25,
+                user_delay_ns: -2000,
+            },
+            ext_int: ExtInt::Timestamp,
+        }
+    }
+
+    #[rustfmt::skip]
+    const IMAGE_V9: [u8; 44] = [
+        b'G', b'C', b'F', b'G', 9, 0, 0xf8, 0x02, 0xcc, 0x68,
+        0xa0, 0xfa, 0x03,                   // time_zone_secs
+        0, 2, 0, 0, 0, 0, 0, 0, 0, 2,       // rotation
+        1, 1, 3, 60,                        // auto_rotate
+        2,                                  // speed_unit
+        1,                                  // odometer_source
+        1, 1, 1, 1, 1, 1,                   // constellations
+        10, 5,                              // rate
+        2, 60,                              // power_save
+        1,                                  // receiver_storage
+        50, 0x9f, 0x1f,                     // time_pulse
+        1,                                  // ext_int
+    ];
+
+    #[rustfmt::skip]
+    const IMAGE_V8: [u8; 43] = [
+        b'G', b'C', b'F', b'G', 8, 0, 0x85, 0x0e, 0x04, 0xe0,
+        0xa0, 0xfa, 0x03,                   // time_zone_secs
+        0, 2, 0, 0, 0, 0, 0, 0, 2,          // rotation
+        1, 1, 3, 60,                        // auto_rotate
+        2,                                  // speed_unit
+        1,                                  // odometer_source
+        1, 1, 1, 1, 1, 1,                   // constellations
+        10, 5,                              // rate
+        2, 60,                              // power_save
+        1,                                  // receiver_storage
+        50, 0x9f, 0x1f,                     // time_pulse
+        1,                                  // ext_int
+    ];
+
+    #[rustfmt::skip]
+    const IMAGE_V7: [u8; 42] = [
+        b'G', b'C', b'F', b'G', 7, 0, 0x2d, 0xeb, 0x8d, 0xde,
+        0xa0, 0xfa, 0x03,                   // time_zone_secs
+        0, 2, 0, 0, 0, 0, 0, 0, 2,          // rotation
+        1, 1, 3, 60,                        // auto_rotate
+        2,                                  // speed_unit
+        1,                                  // odometer_source
+        1, 1, 1, 1, 1, 1,                   // constellations
+        10, 5,                              // rate
+        2, 60,                              // power_save
+        1,                                  // receiver_storage
+        50, 0x9f, 0x1f,                     // time_pulse
+    ];
+
+    #[rustfmt::skip]
+    const IMAGE_V6: [u8; 39] = [
+        b'G', b'C', b'F', b'G', 6, 0, 0x0c, 0x7c, 0x94, 0x22,
+        0xa0, 0xfa, 0x03,                   // time_zone_secs
+        0, 2, 0, 0, 0, 0, 0, 0, 2,          // rotation
+        1, 1, 3, 60,                        // auto_rotate
+        2,                                  // speed_unit
+        1,                                  // odometer_source
+        1, 1, 1, 1, 1, 1,                   // constellations
+        10, 5,                              // rate
+        2, 60,                              // power_save
+        1,                                  // receiver_storage
+    ];
+
+    #[rustfmt::skip]
+    const IMAGE_V5: [u8; 38] = [
+        b'G', b'C', b'F', b'G', 5, 0, 0x91, 0xc5, 0x00, 0x99,
+        0xa0, 0xfa, 0x03,                   // time_zone_secs
+        0, 2, 0, 0, 0, 0, 0, 0, 2,          // rotation
+        1, 1, 3, 60,                        // auto_rotate
+        2,                                  // speed_unit
+        1,                                  // odometer_source
+        1, 1, 1, 1, 1, 1,                   // constellations
+        10, 5,                              // rate
+        2, 60,                              // power_save
+    ];
+
+    #[rustfmt::skip]
+    const IMAGE_V4: [u8; 36] = [
+        b'G', b'C', b'F', b'G', 4, 0, 0x61, 0x76, 0x84, 0x47,
+        0xa0, 0xfa, 0x03,                   // time_zone_secs
+        0, 2, 0, 0, 0, 0, 0, 0, 2,          // rotation
+        1, 1, 3, 60,                        // auto_rotate
+        2,                                  // speed_unit
+        1,                                  // odometer_source
+        1, 1, 1, 1, 1, 1,                   // constellations
+        10, 5,                              // rate
+    ];
+
+    #[rustfmt::skip]
+    const IMAGE_V3: [u8; 34] = [
+        b'G', b'C', b'F', b'G', 3, 0, 0xcf, 0x7f, 0xb7, 0xdf,
+        0xa0, 0xfa, 0x03,                   // time_zone_secs
+        0, 2, 0, 0, 0, 0, 0, 0, 2,          // rotation
+        1, 1, 3, 60,                        // auto_rotate
+        2,                                  // speed_unit
+        1,                                  // odometer_source
+        1, 1, 1, 1, 1, 1,                   // constellations
+    ];
+
+    #[rustfmt::skip]
+    const IMAGE_V2: [u8; 28] = [
+        b'G', b'C', b'F', b'G', 2, 0, 0x53, 0x31, 0xd2, 0xab,
+        0xa0, 0xfa, 0x03,                   // time_zone_secs
+        0, 2, 0, 0, 0, 0, 0, 0, 2,          // rotation
+        1, 1, 3, 60,                        // auto_rotate
+        2,                                  // speed_unit
+        1,                                  // odometer_source
+    ];
+
+    #[test]
+    fn current() {
+        let mut buf = [0; 64];
+        let bytes = encode(&config_v9(), &mut buf).unwrap();
+        assert_eq!(bytes, IMAGE_V9);
+        assert!(decode::<Config>(&IMAGE_V9) == Ok(config_v9()));
+    }
+
+    #[test]
+    fn v8() {
+        assert!(decode::<Config>(&IMAGE_V8) == Ok(config_v9()));
+
+        // every view fits now
+        let mut config = config_v9();
+        for mode in DisplayMode::VIEWS {
+            if !config.rotation.contains(mode) {
+                config.rotation.toggle(mode);
+            }
+        }
+        assert_eq!(config.rotation.as_slice().len(), DisplayMode::VIEWS.len());
+        let mut buf = [0; 64];
+        let bytes = encode(&config, &mut buf).unwrap();
+        assert!(decode::<Config>(bytes) == Ok(config));
+    }
+
+    #[test]
+    fn v7() {
+        let expected = Config {
+            ext_int: ExtInt::TxReady,
+            ..config_v9()
+        };
+        assert!(decode::<Config>(&IMAGE_V7) == Ok(expected));
+    }
+
+    #[test]
+    fn v6() {
+        let expected = Config {
+            time_pulse: TimePulse::default(),
+            ext_int: ExtInt::TxReady,
+            ..config_v9()
+        };
+        assert!(decode::<Config>(&IMAGE_V6) == Ok(expected));
+    }
+
+    #[test]
+    fn v5() {
+        let expected = Config {
+            receiver_storage: ReceiverStorage::Ram,
+            time_pulse: TimePulse::default(),
+            ext_int: ExtInt::TxReady,
+            ..config_v9()
+        };
+        assert!(decode::<Config>(&IMAGE_V5) == Ok(expected));
+    }
+
+    #[test]
+    fn v4() {
+        let expected = Config {
+            power_save: PowerSave::Off,
+            receiver_storage: ReceiverStorage::Ram,
+            time_pulse: TimePulse::default(),
+            ext_int: ExtInt::TxReady,
+            ..config_v9()
+        };
+        assert!(decode::<Config>(&IMAGE_V4) == Ok(expected));
+    }
+
+    #[test]
+    fn v3() {
+        let expected = Config {
+            rate: Rate::default(),
+            power_save: PowerSave::Off,
+            receiver_storage: ReceiverStorage::Ram,
+            time_pulse: TimePulse::default(),
+            ext_int: ExtInt::TxReady,
+            ..config_v9()
+        };
+        assert!(decode::<Config>(&IMAGE_V3) == Ok(expected));
+    }
+
+    #[test]
+    fn v2() {
+        let expected = Config {
+            constellations: Constellations::default(),
+            rate: Rate::default(),
+            power_save: PowerSave::Off,
+            receiver_storage: ReceiverStorage::Ram,
+            time_pulse: TimePulse::default(),
+            ext_int: ExtInt::TxReady,
+            ..config_v9()
+        };
+        assert!(decode::<Config>(&IMAGE_V2) == Ok(expected));
+    }
+
+    #[test]
+    fn v1() {
+        let mut buf = [0; 64];
+        let bytes = encode(
+            &ConfigV1 {
+                time_zone_secs: TZ_JST,
+            },
+            &mut buf,
+        )
+        .unwrap();
+        let expected = Config {
+            time_zone_secs: TZ_JST,
+            ..Default::default()
+        };
+        assert!(decode::<Config>(bytes) == Ok(expected));
+    }
+
+    #[test]
+    fn legacy() {
+        let mut image = [0xff; 16];
+        image[..5].copy_from_slice(&[0x04, 0xa0, 0xfa, 0x03, 0x00]);
+        let expected = Config {
+            time_zone_secs: TZ_JST,
+            ..Default::default()
+        };
+        assert!(from_legacy(&mut image) == Some(expected));
+
+        let mut image = [0xff; 16];
+        assert!(from_legacy(&mut image).is_none());
+    }
+
+    impl Versioned for ConfigV1 {
+        const MAGIC: u32 = Config::MAGIC;
+        const VERSION: u16 = 1;
+    }
+}
@@ -0,0 +1,24 @@
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "lowercase")]
+pub enum OdometerSource {
+    /// Integrate the distance between successive UBX-NAV-PVT positions.
+    Positions,
+    /// Use UBX-NAV-ODO computed by the receiver.
+    Receiver,
+}
+
+impl OdometerSource {
+    pub const ALL: [OdometerSource; 2] = [OdometerSource::Positions, OdometerSource::Receiver];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OdometerSource::Positions => "positions",
+            OdometerSource::Receiver => "receiver",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OdometerSource> {
+        OdometerSource::ALL.into_iter().find(|s| s.name() == name)
+    }
+}
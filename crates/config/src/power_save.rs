@@ -0,0 +1,64 @@
+/// Power save mode of the receiver (CFG-PM-OPERATEMODE). Between the fixes, the clock keeps the
+/// time on its own.
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "kebab-case")]
+pub enum PowerSave {
+    /// Continuous tracking
+    #[default]
+    Off,
+    /// Keeps tracking, but with the RF front end duty-cycled. Solutions still arrive at the
+    /// navigation rate.
+    CyclicTracking,
+    /// Switches the receiver off between the fixes taken every `period_secs`.
+    OnOff { period_secs: u16 },
+}
+
+impl PowerSave {
+    /// Limits of `OnOff::period_secs`. Shorter periods save little, as each fix takes a few
+    /// seconds to acquire.
+    pub const MIN_PERIOD_SECS: u16 = 10;
+    pub const MAX_PERIOD_SECS: u16 = 3600;
+
+    pub fn is_valid(&self) -> bool {
+        match self {
+            PowerSave::Off | PowerSave::CyclicTracking => true,
+            PowerSave::OnOff { period_secs } => {
+                (Self::MIN_PERIOD_SECS..=Self::MAX_PERIOD_SECS).contains(period_secs)
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerSave::Off => "off",
+            PowerSave::CyclicTracking => "cyclic-tracking",
+            PowerSave::OnOff { .. } => "on-off",
+        }
+    }
+
+    /// Longest time without a fix, during which the local clock may drift.
+    pub fn fix_interval_secs(&self) -> u16 {
+        match self {
+            PowerSave::Off | PowerSave::CyclicTracking => 1,
+            PowerSave::OnOff { period_secs } => *period_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::power_save::*;
+
+    #[test]
+    fn is_valid() {
+        assert!(PowerSave::Off.is_valid());
+        assert!(PowerSave::CyclicTracking.is_valid());
+        assert!(PowerSave::OnOff { period_secs: 10 }.is_valid());
+        assert!(PowerSave::OnOff { period_secs: 3600 }.is_valid());
+        assert!(!PowerSave::OnOff { period_secs: 9 }.is_valid());
+        assert!(!PowerSave::OnOff { period_secs: 3601 }.is_valid());
+        assert_eq!(PowerSave::OnOff { period_secs: 60 }.fix_interval_secs(), 60);
+    }
+}
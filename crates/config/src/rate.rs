@@ -0,0 +1,80 @@
+/// How often the receiver measures (CFG-RATE-MEAS) and computes a solution out of the
+/// measurements (CFG-RATE-NAV). Higher rates make the velocity more responsive, at the cost of
+/// power.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rate {
+    pub measurement_hz: u8,
+    pub navigation_hz: u8,
+}
+
+impl Default for Rate {
+    fn default() -> Self {
+        Self {
+            measurement_hz: 5,
+            navigation_hz: 5,
+        }
+    }
+}
+
+impl Rate {
+    /// Rates of 1 to 10 Hz whose periods are whole milliseconds, so that the solutions keep
+    /// hitting whole seconds.
+    pub const HZ: [u8; 6] = [1, 2, 4, 5, 8, 10];
+
+    /// Returns `None` unless both rates are in [`Rate::HZ`] and a solution is computed every
+    /// `n` measurements.
+    pub fn new(measurement_hz: u8, navigation_hz: u8) -> Option<Self> {
+        let rate = Self {
+            measurement_hz,
+            navigation_hz,
+        };
+        rate.is_valid().then_some(rate)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        Self::HZ.contains(&self.measurement_hz)
+            && Self::HZ.contains(&self.navigation_hz)
+            && self.measurement_hz.is_multiple_of(self.navigation_hz)
+    }
+
+    /// CFG-RATE-MEAS
+    pub fn measurement_period_ms(&self) -> u16 {
+        1000 / u16::from(self.measurement_hz)
+    }
+
+    /// CFG-RATE-NAV, the number of measurements per solution
+    pub fn navigation_ratio(&self) -> u16 {
+        u16::from(self.measurement_hz / self.navigation_hz)
+    }
+
+    /// Interval between the solutions
+    pub fn navigation_period_ms(&self) -> u16 {
+        1000 / u16::from(self.navigation_hz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::rate::*;
+
+    #[test]
+    fn new() {
+        let rate = Rate::new(10, 5).unwrap();
+        assert_eq!(rate.measurement_period_ms(), 100);
+        assert_eq!(rate.navigation_ratio(), 2);
+        assert_eq!(rate.navigation_period_ms(), 200);
+
+        let rate = Rate::default();
+        assert_eq!(rate.measurement_period_ms(), 200);
+        assert_eq!(rate.navigation_ratio(), 1);
+
+        assert_eq!(Rate::new(8, 1).map(|r| r.navigation_ratio()), Some(8));
+        assert_eq!(Rate::new(5, 10), None);
+        assert_eq!(Rate::new(8, 5), None);
+        assert_eq!(Rate::new(3, 3), None);
+        assert_eq!(Rate::new(20, 10), None);
+        assert_eq!(Rate::new(0, 0), None);
+    }
+}
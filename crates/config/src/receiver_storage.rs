@@ -0,0 +1,33 @@
+/// Where the receiver keeps the configuration written by the clock.
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiverStorage {
+    /// Lost on every reset, written again by each setup.
+    #[default]
+    Ram,
+    /// Battery-backed RAM, kept over resets while the backup supply lasts.
+    Bbr,
+    /// BBR and flash, kept over power cycles. Needs a flash attached to the receiver.
+    Flash,
+}
+
+impl ReceiverStorage {
+    pub const ALL: [ReceiverStorage; 3] = [
+        ReceiverStorage::Ram,
+        ReceiverStorage::Bbr,
+        ReceiverStorage::Flash,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReceiverStorage::Ram => "ram",
+            ReceiverStorage::Bbr => "bbr",
+            ReceiverStorage::Flash => "flash",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ReceiverStorage> {
+        ReceiverStorage::ALL.into_iter().find(|s| s.name() == name)
+    }
+}
@@ -0,0 +1,66 @@
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpeedUnit {
+    #[serde(rename = "kmh")]
+    KilometersPerHour,
+    #[serde(rename = "mph")]
+    MilesPerHour,
+    #[serde(rename = "knots")]
+    Knots,
+    #[serde(rename = "mps")]
+    MetersPerSecond,
+}
+
+impl SpeedUnit {
+    pub const ALL: [SpeedUnit; 4] = [
+        SpeedUnit::KilometersPerHour,
+        SpeedUnit::MilesPerHour,
+        SpeedUnit::Knots,
+        SpeedUnit::MetersPerSecond,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpeedUnit::KilometersPerHour => "kmh",
+            SpeedUnit::MilesPerHour => "mph",
+            SpeedUnit::Knots => "knots",
+            SpeedUnit::MetersPerSecond => "mps",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SpeedUnit> {
+        SpeedUnit::ALL.into_iter().find(|u| u.name() == name)
+    }
+
+    /// Converts the speed in mm/s into thousandths of this unit.
+    pub fn from_mm_s(&self, mm_s: u32) -> u32 {
+        // (mm/s) * num / den = (1/1000 unit)
+        let (num, den) = match self {
+            SpeedUnit::KilometersPerHour => (36, 10),
+            SpeedUnit::MilesPerHour => (3_600_000, 1_609_344),
+            SpeedUnit::Knots => (3_600, 1_852),
+            SpeedUnit::MetersPerSecond => (1, 1),
+        };
+        (u64::from(mm_s) * num / den) as u32
+    }
+
+    /// Name of the unit of distance used along with this unit.
+    pub fn distance_name(&self) -> &'static str {
+        match self {
+            SpeedUnit::KilometersPerHour | SpeedUnit::MetersPerSecond => "km",
+            SpeedUnit::MilesPerHour => "mi",
+            SpeedUnit::Knots => "nmi",
+        }
+    }
+
+    /// Converts the distance in meters into thousandths of the unit of distance.
+    pub fn distance_from_m(&self, m: u32) -> u32 {
+        // m * num / den = (1/1000 unit)
+        let (num, den) = match self {
+            SpeedUnit::KilometersPerHour | SpeedUnit::MetersPerSecond => (1, 1),
+            SpeedUnit::MilesPerHour => (1_000_000, 1_609_344),
+            SpeedUnit::Knots => (1_000, 1_852),
+        };
+        (u64::from(m) * num / den) as u32
+    }
+}
@@ -0,0 +1,241 @@
+//! Human-readable (JSON) representation of [`Config`], to copy the settings between clocks.
+
+use crate::Config;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    BufferTooSmall,
+    Syntax,
+    /// Well-formed, but not a valid config (missing fields, values out of range, ...).
+    Invalid,
+}
+
+impl From<serde_json_core::ser::Error> for Error {
+    fn from(_: serde_json_core::ser::Error) -> Self {
+        Self::BufferTooSmall
+    }
+}
+
+impl From<serde_json_core::de::Error> for Error {
+    fn from(e: serde_json_core::de::Error) -> Self {
+        match e {
+            serde_json_core::de::Error::CustomError => Self::Invalid,
+            _ => Self::Syntax,
+        }
+    }
+}
+
+pub fn export<'b>(config: &Config, buf: &'b mut [u8]) -> Result<&'b str, Error> {
+    to_str(config, buf)
+}
+
+/// Parses the output of [`export`]. All the fields are required.
+pub fn import(text: &str) -> Result<Config, Error> {
+    let (config, len): (Config, _) = serde_json_core::from_str(text)?;
+    if !text[len..].trim().is_empty() {
+        return Err(Error::Syntax);
+    }
+    if !config.is_valid() {
+        return Err(Error::Invalid);
+    }
+    Ok(config)
+}
+
+/// Calls `f` with the name, the old value and the new value of each field which differs.
+pub fn diff(old: &Config, new: &Config, mut f: impl FnMut(&str, &str, &str)) -> Result<(), Error> {
+    // fails to compile when a field is added, so that it is not forgotten here
+    let Config {
+        time_zone_secs,
+        rotation,
+        auto_rotate,
+        speed_unit,
+        odometer_source,
+        constellations,
+        rate,
+        power_save,
+        receiver_storage,
+        time_pulse,
+        ext_int,
+    } = new;
+
+    let mut buf_old = [0; 128];
+    let mut buf_new = [0; 128];
+    macro_rules! field {
+        ($name:ident) => {
+            if old.$name != *$name {
+                f(
+                    stringify!($name),
+                    to_str(&old.$name, &mut buf_old)?,
+                    to_str($name, &mut buf_new)?,
+                );
+            }
+        };
+    }
+    field!(time_zone_secs);
+    field!(rotation);
+    field!(auto_rotate);
+    field!(speed_unit);
+    field!(odometer_source);
+    field!(constellations);
+    field!(rate);
+    field!(power_save);
+    field!(receiver_storage);
+    field!(time_pulse);
+    field!(ext_int);
+    Ok(())
+}
+
+fn to_str<'b, T: serde::Serialize>(value: &T, buf: &'b mut [u8]) -> Result<&'b str, Error> {
+    let len = serde_json_core::to_slice(value, buf)?;
+    // the serializer only writes UTF-8
+    core::str::from_utf8(&buf[..len]).map_err(|_| Error::BufferTooSmall)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::text::*;
+    use crate::{
+        AutoRotate, Constellations, DisplayMode, ExtInt, OdometerSource, PowerSave, Rate,
+        ReceiverStorage, Rotation, SpeedUnit, TimePulse,
+    };
+    use std::{string::String, vec::Vec};
+
+    const TEXT: &str = concat!(
+        r#"{"time_zone_secs":32400,"#,
+        r#""rotation":["time","velocity","max-speed"],"#,
+        r#""auto_rotate":{"mode":"date","duration_secs":3,"period_secs":60},"#,
+        r#""speed_unit":"knots","odometer_source":"receiver","#,
+        r#""constellations":{"gps":true,"galileo":true,"beidou":false,"glonass":true,"#,
+        r#""qzss":true,"sbas":false},"rate":{"measurement_hz":10,"navigation_hz":5},"#,
+        r#""power_save":{"on-off":{"period_secs":60}},"receiver_storage":"bbr","#,
+        r#""time_pulse":{"cable_delay_ns":25,"user_delay_ns":-2000},"ext_int":"timestamp"}"#,
+    );
+
+    fn config() -> Config {
+        Config {
+            time_zone_secs: 9 * 60 * 60,
+            rotation: Rotation::new(&[
+                DisplayMode::Time,
+                DisplayMode::Velocity,
+                DisplayMode::MaxSpeed,
+            ])
+            .unwrap(),
+            auto_rotate: AutoRotate::new(DisplayMode::Date, 3, 60),
+            speed_unit: SpeedUnit::Knots,
+            odometer_source: OdometerSource::Receiver,
+            constellations: Constellations {
+                gps: true,
+                galileo: true,
+                beidou: false,
+                glonass: true,
+                qzss: true,
+                sbas: false,
+            },
+            rate: Rate {
+                measurement_hz: 10,
+                navigation_hz: 5,
+            },
+            power_save: PowerSave::OnOff { period_secs: 60 },
+            receiver_storage: ReceiverStorage::Bbr,
+            time_pulse: TimePulse {
+                cable_delay_ns: 25,
+                user_delay_ns: -2000,
+            },
+            ext_int: ExtInt::Timestamp,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0; 512];
+        assert_eq!(export(&config(), &mut buf), Ok(TEXT));
+        assert!(import(TEXT) == Ok(config()));
+        assert!(import(&std::format!(" {TEXT}\r\n")) == Ok(config()));
+
+        let mut buf = [0; 512];
+        let text = export(&Config::default(), &mut buf).unwrap();
+        assert!(import(text) == Ok(Config::default()));
+
+        assert_eq!(export(&config(), &mut [0; 64]), Err(Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(import("").is_err_and(|e| e == Error::Syntax));
+        assert!(import(&TEXT[1..]).is_err_and(|e| e == Error::Syntax));
+        assert!(import(&std::format!("{TEXT}}}")).is_err_and(|e| e == Error::Syntax));
+        // missing field
+        let text = TEXT.replace(r#","odometer_source":"receiver""#, "");
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        // no constellation to fix the position
+        let text = TEXT
+            .replace(r#""gps":true"#, r#""gps":false"#)
+            .replace(r#""galileo":true"#, r#""galileo":false"#)
+            .replace(r#""glonass":true"#, r#""glonass":false"#);
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        // unknown mode
+        let text = TEXT.replace("max-speed", "max");
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        // duplicate mode
+        let text = TEXT.replace("max-speed", "time");
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        let text = TEXT.replace(
+            r#""rotation":["time","velocity","max-speed"]"#,
+            r#""rotation":[]"#,
+        );
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        // out of range
+        let text = TEXT.replace("32400", "86400");
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        let text = TEXT.replace(r#""duration_secs":3"#, r#""duration_secs":60"#);
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        let text = TEXT.replace(r#""navigation_hz":5"#, r#""navigation_hz":4"#);
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        let text = TEXT.replace(r#""period_secs":60}}"#, r#""period_secs":5}}"#);
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        let text = TEXT.replace(r#""cable_delay_ns":25"#, r#""cable_delay_ns":-25"#);
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+        // unknown use of EXTINT
+        let text = TEXT.replace(r#""ext_int":"timestamp""#, r#""ext_int":"event""#);
+        assert!(import(&text).is_err_and(|e| e == Error::Invalid));
+    }
+
+    #[test]
+    fn diff_fields() {
+        let mut changes = Vec::new();
+        let mut f = |name: &str, old: &str, new: &str| {
+            changes.push(std::format!("{name}: {old} -> {new}"));
+        };
+        diff(&config(), &config(), &mut f).unwrap();
+        diff(&Config::default(), &config(), &mut f).unwrap();
+        assert_eq!(
+            changes,
+            [
+                "time_zone_secs: 0 -> 32400",
+                r#"rotation: ["time","date","velocity"] -> ["time","velocity","max-speed"]"#,
+                r#"auto_rotate: null -> {"mode":"date","duration_secs":3,"period_secs":60}"#,
+                r#"speed_unit: "kmh" -> "knots""#,
+                r#"odometer_source: "positions" -> "receiver""#,
+                concat!(
+                    r#"constellations: {"gps":true,"galileo":true,"beidou":true,"glonass":false,"#,
+                    r#""qzss":true,"sbas":true} -> {"gps":true,"galileo":true,"beidou":false,"#,
+                    r#""glonass":true,"qzss":true,"sbas":false}"#,
+                ),
+                concat!(
+                    r#"rate: {"measurement_hz":5,"navigation_hz":5} -> "#,
+                    r#"{"measurement_hz":10,"navigation_hz":5}"#,
+                ),
+                r#"power_save: "off" -> {"on-off":{"period_secs":60}}"#,
+                r#"receiver_storage: "ram" -> "bbr""#,
+                concat!(
+                    r#"time_pulse: {"cable_delay_ns":50,"user_delay_ns":0} -> "#,
+                    r#"{"cable_delay_ns":25,"user_delay_ns":-2000}"#,
+                ),
+                r#"ext_int: "tx-ready" -> "timestamp""#,
+            ]
+            .map(String::from)
+        );
+    }
+}
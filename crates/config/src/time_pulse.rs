@@ -0,0 +1,69 @@
+/// Delays compensated by the receiver when it generates the time pulse (CFG-TP-ANT_CABLEDELAY and
+/// CFG-TP-USER_DELAY_TP1), so that the seconds shown flip on the second of UTC.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimePulse {
+    /// Propagation delay of the antenna cable, about 5 ns/m
+    pub cable_delay_ns: i16,
+    /// Added to the pulse, e.g. negative to make up for the latency of the display
+    pub user_delay_ns: i32,
+}
+
+impl Default for TimePulse {
+    fn default() -> Self {
+        // the default of the receiver
+        Self {
+            cable_delay_ns: 50,
+            user_delay_ns: 0,
+        }
+    }
+}
+
+impl TimePulse {
+    /// 2 km of cable
+    pub const MAX_CABLE_DELAY_NS: i16 = 10_000;
+    /// Longer delays are rather an error than something to compensate.
+    pub const MAX_USER_DELAY_NS: i32 = 1_000_000;
+
+    pub fn is_valid(&self) -> bool {
+        (0..=Self::MAX_CABLE_DELAY_NS).contains(&self.cable_delay_ns)
+            && self.user_delay_ns.unsigned_abs() <= Self::MAX_USER_DELAY_NS as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::time_pulse::*;
+
+    #[test]
+    fn is_valid() {
+        assert!(TimePulse::default().is_valid());
+        let tp = TimePulse {
+            cable_delay_ns: 10_000,
+            user_delay_ns: -1_000_000,
+        };
+        assert!(tp.is_valid());
+        assert!(
+            !TimePulse {
+                cable_delay_ns: -1,
+                ..tp
+            }
+            .is_valid()
+        );
+        assert!(
+            !TimePulse {
+                cable_delay_ns: 10_001,
+                ..tp
+            }
+            .is_valid()
+        );
+        assert!(
+            !TimePulse {
+                user_delay_ns: 1_000_001,
+                ..tp
+            }
+            .is_valid()
+        );
+    }
+}
@@ -1,13 +1,20 @@
 #![no_std]
 #![no_main]
 
+use core::cell::RefCell;
+
+use embassy_embedded_hal::flash::partition::BlockingPartition;
 use embassy_executor::Spawner;
+use embassy_rp::flash::{Blocking, Flash};
+use embassy_sync::blocking_mutex::{Mutex, raw::NoopRawMutex};
 
+use config::envelope::Versioned;
 use gnss_7_seg_clock::flash::NonVolatileConfig;
 
 use {defmt_rtt as _, panic_probe as _};
 
 const ADDR_OFFSET: u32 = 0x100000;
+const PARTITION_SIZE: u32 = 0x4000;
 const FLASH_SIZE: usize = 2 * 1024 * 1024;
 
 #[derive(serde::Serialize, serde::Deserialize, defmt::Format)]
@@ -21,11 +28,23 @@ impl Default for Config {
     }
 }
 
+impl Versioned for Config {
+    const MAGIC: u32 = u32::from_le_bytes(*b"EXMP");
+    const VERSION: u16 = 1;
+}
+
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
 
-    let mut nvcfg = NonVolatileConfig::<_, _, FLASH_SIZE, ADDR_OFFSET, 512>::new(p.FLASH);
+    let flash = Mutex::<NoopRawMutex, _>::new(RefCell::new(
+        Flash::<_, Blocking, FLASH_SIZE>::new_blocking(p.FLASH),
+    ));
+    let mut nvcfg = defmt::unwrap!(NonVolatileConfig::<_, _, 512>::new(BlockingPartition::new(
+        &flash,
+        ADDR_OFFSET,
+        PARTITION_SIZE
+    )));
     let mut cfg: Config = defmt::unwrap!(nvcfg.read_or_default());
 
     defmt::info!("got: {}", cfg);
@@ -0,0 +1,287 @@
+//! AssistNow Offline (and Autonomous) data, i.e. the orbits of the satellites predicted for the
+//! days to come, kept on the flash so that the receiver needs no network to find the satellites
+//! sooner after a reset.
+//!
+//! The data is a file of UBX-MGA frames, uploaded from the host through the USB shell. The header
+//! is written once the whole file has been checked, so that an interrupted upload is not used.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::RawMutex};
+use embedded_storage::nor_flash::NorFlash;
+
+use ubx::UbxFrame;
+
+/// Longer than any UBX-MGA frame, e.g. UBX-MGA-ANO takes 84 bytes.
+pub const MAX_FRAME_LEN: usize = 256;
+
+const MAGIC: u32 = u32::from_le_bytes(*b"MGAO");
+/// Magic, length and the number of frames
+const HEADER_LEN: usize = 12;
+/// The frames follow the header in its own page.
+const DATA_OFFSET: u32 = 256;
+
+/// UBX-MGA
+const MGA_CLASS: u8 = 0x13;
+
+/// Checked by one call of [`Storage::finish`], i.e. a sector
+const CHECK_CHUNK_LEN: u32 = 4096;
+
+#[derive(Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    Flash,
+    /// More than the partition holds
+    TooLarge,
+    /// [`Storage::write`] or [`Storage::finish`] without [`Storage::begin`]
+    NotStarted,
+    /// Not a valid UBX-MGA frame at `offset` of the file
+    Invalid {
+        offset: u32,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub struct Summary {
+    pub len: u32,
+    pub frames: u32,
+}
+
+/// Position in the stored file, to read all the frames with [`Storage::read_next`].
+#[derive(Clone, Copy, Default, Debug, PartialEq, defmt::Format)]
+pub struct Cursor {
+    offset: u32,
+}
+
+/// Progress of [`Storage::finish`], from the first frame (`Check::default()`).
+#[derive(Clone, Copy, Default, Debug, PartialEq, defmt::Format)]
+pub struct Check {
+    offset: u32,
+    frames: u32,
+}
+
+/// Access for the USB shell, which uploads the file, and the receiver, which reads it.
+pub trait Storage {
+    /// Returns `None` unless a file has been uploaded completely.
+    fn summary(&self) -> Result<Option<Summary>, Error>;
+
+    /// Reads the frame at `cursor`, from the first one (`Cursor::default()`).
+    fn read_next<'b>(
+        &self,
+        cursor: &mut Cursor,
+        buf: &'b mut [u8; MAX_FRAME_LEN],
+    ) -> Result<Option<&'b [u8]>, Error>;
+
+    /// Discards the stored file, and starts a new one.
+    fn begin(&self) -> Result<(), Error>;
+
+    /// Appends `data`, which may split the frames anywhere. The sectors are erased as `data`
+    /// reaches them, i.e. at most one if it is shorter than a sector.
+    fn write(&self, data: &[u8]) -> Result<(), Error>;
+
+    /// Checks the next chunk of the frames written since [`Storage::begin`], so that the caller
+    /// can yield in between. Once all of them have been checked, makes them available to
+    /// [`Storage::read_next`] and returns the summary. An invalid frame ends the upload.
+    fn finish(&self, check: &mut Check) -> Result<Option<Summary>, Error>;
+
+    fn clear(&self) -> Result<(), Error>;
+}
+
+/// The file in a dedicated partition. The sectors are erased one by one as the upload reaches
+/// them, each stalling the execution as long as [`crate::flash::BackgroundWriter`] does, so the
+/// callers erase and write right after a time pulse as it does.
+pub struct AssistNow<M: RawMutex, F: NorFlash> {
+    inner: Mutex<M, RefCell<Inner<F>>>,
+}
+
+struct Inner<F> {
+    flash: F,
+    /// Bytes written since [`Storage::begin`], `None` unless uploading
+    written: Option<u32>,
+    /// End of the sectors erased since then
+    erased: u32,
+}
+
+impl<M, F> AssistNow<M, F>
+where
+    M: RawMutex,
+    F: NorFlash,
+    F::Error: defmt::Format,
+{
+    pub fn new(flash: F) -> Self {
+        // the frames are written as they arrive, without aligning them
+        const { assert!(F::WRITE_SIZE == 1) };
+        Self {
+            inner: Mutex::new(RefCell::new(Inner {
+                flash,
+                written: None,
+                erased: 0,
+            })),
+        }
+    }
+}
+
+impl<F> Inner<F>
+where
+    F: NorFlash,
+    F::Error: defmt::Format,
+{
+    fn summary(&mut self) -> Result<Option<Summary>, Error> {
+        let mut header = [0; HEADER_LEN];
+        self.read(0, &mut header)?;
+        let [magic, len, frames] = [0, 4, 8]
+            .map(|i| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]));
+        let valid = magic == MAGIC && len <= self.flash.capacity() as u32 - DATA_OFFSET;
+        Ok(valid.then_some(Summary { len, frames }))
+    }
+
+    /// Reads the frame at `offset` of the file of `len` bytes.
+    fn read_frame<'b>(
+        &mut self,
+        offset: u32,
+        len: u32,
+        buf: &'b mut [u8; MAX_FRAME_LEN],
+    ) -> Result<&'b [u8], Error> {
+        let (header, _) = buf.split_at_mut(6);
+        if offset + header.len() as u32 > len {
+            return Err(Error::Invalid { offset });
+        }
+        self.read(DATA_OFFSET + offset, header)?;
+        let frame_len = UbxFrame::len_from_header(header).ok_or(Error::Invalid { offset })?;
+        if frame_len > MAX_FRAME_LEN || offset + frame_len as u32 > len {
+            return Err(Error::Invalid { offset });
+        }
+        let frame = &mut buf[..frame_len];
+        self.read(DATA_OFFSET + offset, frame)?;
+        match UbxFrame::parse(frame) {
+            Some(f) if f.class == MGA_CLASS => Ok(frame),
+            _ => Err(Error::Invalid { offset }),
+        }
+    }
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Error> {
+        self.flash.read(offset, buf).map_err(|e| {
+            defmt::error!("failed to read the AssistNow data: {}", e);
+            Error::Flash
+        })
+    }
+
+    /// Erases the sectors up to `end`.
+    fn erase_until(&mut self, end: u32) -> Result<(), Error> {
+        let end = end.next_multiple_of(F::ERASE_SIZE as u32);
+        if end > self.erased {
+            self.flash.erase(self.erased, end).map_err(|e| {
+                defmt::error!("failed to erase the AssistNow data: {}", e);
+                Error::Flash
+            })?;
+            self.erased = end;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        self.flash.write(offset, data).map_err(|e| {
+            defmt::error!("failed to write the AssistNow data: {}", e);
+            Error::Flash
+        })
+    }
+}
+
+impl<M, F> Storage for AssistNow<M, F>
+where
+    M: RawMutex,
+    F: NorFlash,
+    F::Error: defmt::Format,
+{
+    fn summary(&self) -> Result<Option<Summary>, Error> {
+        self.inner.lock(|i| i.borrow_mut().summary())
+    }
+
+    fn read_next<'b>(
+        &self,
+        cursor: &mut Cursor,
+        buf: &'b mut [u8; MAX_FRAME_LEN],
+    ) -> Result<Option<&'b [u8]>, Error> {
+        self.inner.lock(|i| {
+            let mut inner = i.borrow_mut();
+            let Some(summary) = inner.summary()? else {
+                return Ok(None);
+            };
+            if cursor.offset >= summary.len {
+                return Ok(None);
+            }
+            let frame = inner.read_frame(cursor.offset, summary.len, buf)?;
+            cursor.offset += frame.len() as u32;
+            Ok(Some(frame))
+        })
+    }
+
+    fn begin(&self) -> Result<(), Error> {
+        self.inner.lock(|i| {
+            let mut inner = i.borrow_mut();
+            inner.written = None;
+            inner.erased = 0;
+            inner.erase_until(DATA_OFFSET)?;
+            inner.written = Some(0);
+            Ok(())
+        })
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), Error> {
+        self.inner.lock(|i| {
+            let mut inner = i.borrow_mut();
+            let written = inner.written.ok_or(Error::NotStarted)?;
+            let offset = DATA_OFFSET + written;
+            let end = offset as usize + data.len();
+            if end > inner.flash.capacity() {
+                return Err(Error::TooLarge);
+            }
+            inner.erase_until(end as u32)?;
+            inner.write(offset, data)?;
+            inner.written = Some(written + data.len() as u32);
+            Ok(())
+        })
+    }
+
+    fn finish(&self, check: &mut Check) -> Result<Option<Summary>, Error> {
+        self.inner.lock(|i| {
+            let mut inner = i.borrow_mut();
+            let len = inner.written.ok_or(Error::NotStarted)?;
+            let mut buf = [0; MAX_FRAME_LEN];
+            let end = check.offset.saturating_add(CHECK_CHUNK_LEN).min(len);
+            while check.offset < end {
+                match inner.read_frame(check.offset, len, &mut buf) {
+                    Ok(frame) => check.offset += frame.len() as u32,
+                    Err(e) => {
+                        inner.written = None;
+                        return Err(e);
+                    }
+                }
+                check.frames += 1;
+            }
+            if check.offset < len {
+                return Ok(None);
+            }
+
+            inner.written = None;
+            let summary = Summary {
+                len,
+                frames: check.frames,
+            };
+            let mut header = [0; HEADER_LEN];
+            for (dst, v) in header.chunks_mut(4).zip([MAGIC, len, summary.frames]) {
+                dst.copy_from_slice(&v.to_le_bytes());
+            }
+            inner.write(0, &header)?;
+            Ok(Some(summary))
+        })
+    }
+
+    fn clear(&self) -> Result<(), Error> {
+        self.inner.lock(|i| {
+            let mut inner = i.borrow_mut();
+            inner.written = None;
+            inner.erased = 0;
+            inner.erase_until(DATA_OFFSET)
+        })
+    }
+}
@@ -0,0 +1,4 @@
+pub use config::{
+    AutoRotate, Config, Constellation, Constellations, DisplayMode, ExtInt, MAX_ROTATION_LEN,
+    PowerSave, Rate, ReceiverStorage, Rotation, TimePulse,
+};
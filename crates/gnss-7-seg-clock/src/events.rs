@@ -1,22 +1,43 @@
 use embassy_futures::select::*;
 use embassy_rp::gpio;
-use embassy_sync::{blocking_mutex::raw::RawMutex, channel::Receiver};
-use embassy_time::Timer;
+use embassy_sync::{
+    blocking_mutex::raw::RawMutex,
+    channel::{DynamicReceiver, DynamicSender, Receiver},
+    signal::Signal,
+    watch::DynSender,
+};
+use embassy_time::{Duration, Instant, Timer};
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, TimeDelta, Timelike};
 
-use crate::max_m10s::Event as MaxM10sEvent;
+use crate::config::{Config, PowerSave, Rate};
+use crate::fault_log;
+use crate::flash::Completion;
+use crate::max_m10s::{Event as MaxM10sEvent, ExternalEvent};
+use crate::odometer::{Odometer, OdometerRecord, OdometerSource};
+use crate::position::{Position, PositionRecord, PositionTracker};
+use crate::shell::{ConfigUpdate, Request as ShellRequest};
+use crate::speed;
+use crate::status::{ConfigStore, Status};
+use crate::watchdog::Heartbeat;
 
 pub enum Event {
     DateTimeAndVelocity {
         datetime: NaiveDateTime,
-        ground_speed_meter_hour: u32,
+        ground_speed_mm_s: u32,
     },
     DateTimeNextPulse(NaiveDateTime),
     Sw3Pressed,
     Sw4Pressed,
     Sw5Pressed,
-    TimePulse,
+    /// The rising edge of the time pulse, and when it was seen on the board
+    TimePulse(Instant),
+    ConfigChanged(ConfigUpdate),
+    ReceiverHealthChanged,
+    /// [`Status::antenna_fault`] changed.
+    AntennaChanged,
+    /// An edge on EXTINT, also sent to the shell.
+    ExternalEvent(ExternalEvent),
 }
 
 pub struct EventSources<'d, M: RawMutex, const N: usize> {
@@ -24,65 +45,285 @@ pub struct EventSources<'d, M: RawMutex, const N: usize> {
     gpio_sw3: DebouncedInput<'d>,
     gpio_sw4: DebouncedInput<'d>,
     gpio_sw5: DebouncedInput<'d>,
-    gpio_pps: gpio::Input<'d>,
+    pulses: &'d Signal<M, Instant>,
+    receiver_shell: DynamicReceiver<'d, ShellRequest>,
+    sender_status: DynSender<'d, Status>,
+    signal_odometer: &'d Signal<M, OdometerRecord>,
+    signal_position: &'d Signal<M, PositionRecord>,
+    sender_time_pulse: DynSender<'d, ()>,
+    receiver_config_store: DynamicReceiver<'d, Completion<Config>>,
+    sender_external_event: DynamicSender<'d, ExternalEvent>,
+    heartbeat: &'d Heartbeat,
+    time_pulse_pending: bool,
+    /// Last time from a solution not flagged as spoofed, and when it arrived
+    trusted: Option<(NaiveDateTime, Instant)>,
+    /// When the last solution arrived, spoofed or not
+    last_solution: Option<Instant>,
+    /// When the last second was ticked by the local clock since then
+    last_tick: Option<Instant>,
+    odometer: Odometer,
+    position: PositionTracker,
+    smoothing: speed::Smoothing,
+    pub odometer_source: OdometerSource,
+    pub rate: Rate,
+    pub power_save: PowerSave,
     pub datetime: Option<NaiveDateTime>,
     pub datetime_next_pulse: Option<NaiveDateTime>,
-    pub ground_speed_meter_hour: Option<u32>,
+    pub status: Status,
 }
 
 impl<'d, M: RawMutex, const N: usize> EventSources<'d, M, N> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         receiver_nmea: Receiver<'d, M, MaxM10sEvent, N>,
         gpio_sw3: gpio::Input<'d>,
         gpio_sw4: gpio::Input<'d>,
         gpio_sw5: gpio::Input<'d>,
-        gpio_pps: gpio::Input<'d>,
+        pulses: &'d Signal<M, Instant>,
+        receiver_shell: DynamicReceiver<'d, ShellRequest>,
+        sender_status: DynSender<'d, Status>,
+        signal_odometer: &'d Signal<M, OdometerRecord>,
+        signal_position: &'d Signal<M, PositionRecord>,
+        sender_time_pulse: DynSender<'d, ()>,
+        receiver_config_store: DynamicReceiver<'d, Completion<Config>>,
+        sender_external_event: DynamicSender<'d, ExternalEvent>,
+        heartbeat: &'d Heartbeat,
+        odometer: OdometerRecord,
+        odometer_source: OdometerSource,
+        rate: Rate,
+        power_save: PowerSave,
+        position: PositionRecord,
     ) -> Self {
         Self {
             receiver_nmea,
             gpio_sw3: DebouncedInput(gpio_sw3),
             gpio_sw4: DebouncedInput(gpio_sw4),
             gpio_sw5: DebouncedInput(gpio_sw5),
-            gpio_pps,
+            pulses,
+            receiver_shell,
+            sender_status,
+            signal_odometer,
+            signal_position,
+            sender_time_pulse,
+            receiver_config_store,
+            sender_external_event,
+            heartbeat,
+            time_pulse_pending: false,
+            trusted: None,
+            last_solution: None,
+            last_tick: None,
+            odometer: Odometer::new(odometer),
+            position: PositionTracker::new(position),
+            smoothing: speed::Smoothing::default(),
+            odometer_source,
+            rate,
+            power_save,
             datetime: None,
             datetime_next_pulse: None,
-            ground_speed_meter_hour: None,
+            status: Status {
+                odometer,
+                ..Default::default()
+            },
         }
     }
 
+    pub fn reset_trip(&mut self) {
+        self.odometer.reset_trip();
+        self.status.odometer = self.odometer.record();
+        self.signal_odometer.signal(self.status.odometer);
+        self.sender_status.send(self.status);
+    }
+
+    fn update_odometer(&mut self) {
+        self.status.odometer = self.odometer.record();
+        if let Some(record) = self.odometer.take_record_to_save() {
+            self.signal_odometer.signal(record);
+        }
+    }
+
+    pub fn reset_speed_stats(&mut self) {
+        self.status.speed_stats.reset();
+        self.sender_status.send(self.status);
+    }
+
+    pub fn set_config_store(&mut self, state: ConfigStore) {
+        self.status.config_store = state;
+        self.sender_status.send(self.status);
+    }
+
+    /// While the receiver indicates spoofing, the time is not taken from it but kept running from
+    /// the last trusted one on the local clock. `None` if there has been none.
+    fn holdover(&self) -> Option<NaiveDateTime> {
+        let (datetime, at) = self.trusted?;
+        // rounded to the interval of the solutions, so that whole seconds are still hit
+        let period_ms = u64::from(self.rate.navigation_period_ms());
+        let elapsed_ms = (at.elapsed().as_millis() + period_ms / 2) / period_ms * period_ms;
+        Some(datetime + TimeDelta::milliseconds(elapsed_ms as i64))
+    }
+
+    /// While the receiver saves power, the solutions may stop for a while (e.g. it is off between
+    /// the fixes). Returns the next whole second to be shown from the local clock meanwhile, and
+    /// when it is due. `None` while not saving power or there has been no trusted time.
+    fn next_tick(&self) -> Option<(NaiveDateTime, Instant)> {
+        if self.power_save == PowerSave::Off {
+            return None;
+        }
+        let (datetime, at) = self.trusted?;
+        let base = datetime.with_nanosecond(0).unwrap();
+        let base_at = at - Duration::from_millis((datetime - base).num_milliseconds() as u64);
+        // the solutions are late by then
+        let earliest = match self.last_tick {
+            Some(tick) => tick + Duration::from_millis(1),
+            None => self.last_solution? + Duration::from_secs(1),
+        };
+        let secs = earliest.duration_since(base_at).as_millis().div_ceil(1000);
+        Some((
+            base + TimeDelta::seconds(secs as i64),
+            base_at + Duration::from_secs(secs),
+        ))
+    }
+
     pub async fn wait(&mut self) -> Event {
-        #[allow(clippy::never_loop)]
+        // the handler has refreshed the display by now, let the flash writers go
+        if core::mem::take(&mut self.time_pulse_pending) {
+            self.sender_time_pulse.send(());
+        }
+
         loop {
-            match select5(
+            let next_tick = self.next_tick();
+            let tick = async {
+                match next_tick {
+                    Some((datetime, at)) => {
+                        Timer::at(at).await;
+                        (datetime, at)
+                    }
+                    None => core::future::pending().await,
+                }
+            };
+            let events = select6(
                 self.receiver_nmea.receive(),
                 self.gpio_sw3.wait_for_falling_edge(),
                 self.gpio_sw4.wait_for_falling_edge(),
                 self.gpio_sw5.wait_for_falling_edge(),
-                self.gpio_pps.wait_for_rising_edge(),
-            )
-            .await
-            {
-                Either5::First(MaxM10sEvent::DateTimeAndVelocity {
-                    datetime,
-                    ground_speed_meter_hour,
-                }) => {
+                self.pulses.wait(),
+                select3(
+                    self.receiver_shell.receive(),
+                    self.receiver_config_store.receive(),
+                    tick,
+                ),
+            );
+            match self.heartbeat.idle(events).await {
+                Either6::First(MaxM10sEvent::NavigationSolution { datetime, pvt }) => {
+                    self.last_solution = Some(Instant::now());
+                    self.last_tick = None;
+                    let ground_speed_mm_s =
+                        speed::suppress_noise(pvt.g_speed.max(0) as u32, pvt.s_acc);
+                    let moving = ground_speed_mm_s > 0;
+                    self.status.heading_1e5_deg = moving.then_some(pvt.head_mot);
+                    self.status.speed_stats.update(ground_speed_mm_s);
+                    let ground_speed_mm_s = self
+                        .smoothing
+                        .update(ground_speed_mm_s, self.rate.navigation_hz);
+                    self.status.ground_speed_mm_s = Some(ground_speed_mm_s);
+                    if self.odometer_source == OdometerSource::Positions {
+                        self.odometer.update_position(&pvt, moving);
+                        self.update_odometer();
+                    }
+                    self.sender_status.send(self.status);
+
+                    let datetime = if self.status.receiver.spoofing.is_spoofed() {
+                        let Some(datetime) = self.holdover() else {
+                            continue;
+                        };
+                        // the pulses are still there, but what they mark is decided here
+                        let next_pulse =
+                            datetime.with_nanosecond(0).unwrap() + TimeDelta::seconds(1);
+                        if self.datetime_next_pulse != Some(next_pulse) {
+                            self.datetime = Some(datetime);
+                            self.datetime_next_pulse = Some(next_pulse);
+                            return Event::DateTimeNextPulse(next_pulse);
+                        }
+                        datetime
+                    } else {
+                        self.trusted = Some((datetime, Instant::now()));
+                        fault_log::set_utc(datetime);
+                        if let Some(record) = Position::from_pvt(&pvt)
+                            .and_then(|p| self.position.take_record_to_save(p))
+                        {
+                            self.signal_position.signal(record);
+                        }
+                        datetime
+                    };
                     self.datetime = Some(datetime);
-                    self.ground_speed_meter_hour = Some(ground_speed_meter_hour);
                     return Event::DateTimeAndVelocity {
                         datetime,
-                        ground_speed_meter_hour,
+                        ground_speed_mm_s,
                     };
                 }
-                Either5::First(MaxM10sEvent::DateTimeNextPulse(datetime)) => {
+                Either6::First(MaxM10sEvent::DateTimeNextPulse(datetime)) => {
+                    if self.status.receiver.spoofing.is_spoofed() {
+                        continue;
+                    }
                     self.datetime_next_pulse = Some(datetime);
                     return Event::DateTimeNextPulse(datetime);
                 }
-                Either5::Second(..) => return Event::Sw3Pressed,
-                Either5::Third(..) => return Event::Sw4Pressed,
-                Either5::Fourth(..) => return Event::Sw5Pressed,
-                Either5::Fifth(..) => {
+                Either6::First(MaxM10sEvent::Odometer(odo)) => {
+                    if self.odometer_source == OdometerSource::Receiver {
+                        self.odometer.update_receiver_distance(&odo);
+                        self.update_odometer();
+                        self.sender_status.send(self.status);
+                    }
+                }
+                Either6::First(MaxM10sEvent::Health(health)) => {
+                    let changed = health.state != self.status.receiver.state;
+                    self.status.receiver = health;
+                    self.sender_status.send(self.status);
+                    if changed {
+                        return Event::ReceiverHealthChanged;
+                    }
+                }
+                Either6::First(MaxM10sEvent::Rf(rf)) => {
+                    let fault = self.status.antenna_fault();
+                    self.status.rf = Some(rf);
+                    self.sender_status.send(self.status);
+                    if self.status.antenna_fault() != fault {
+                        return Event::AntennaChanged;
+                    }
+                }
+                Either6::First(MaxM10sEvent::ExternalEvent(event)) => {
+                    // nobody may be listening on the shell
+                    let _ = self.sender_external_event.try_send(event);
+                    return Event::ExternalEvent(event);
+                }
+                Either6::Second(..) => return Event::Sw3Pressed,
+                Either6::Third(..) => return Event::Sw4Pressed,
+                Either6::Fourth(..) => return Event::Sw5Pressed,
+                Either6::Fifth(at) => {
+                    self.datetime_next_pulse = None;
+                    self.time_pulse_pending = true;
+                    return Event::TimePulse(at);
+                }
+                Either6::Sixth(Either3::Third((datetime, at))) => {
+                    self.last_tick = Some(at);
+                    self.datetime = Some(datetime);
                     self.datetime_next_pulse = None;
-                    return Event::TimePulse;
+                    return Event::DateTimeAndVelocity {
+                        datetime,
+                        ground_speed_mm_s: self.status.ground_speed_mm_s.unwrap_or(0),
+                    };
+                }
+                Either6::Sixth(Either3::First(ShellRequest::UpdateConfig(update))) => {
+                    return Event::ConfigChanged(update);
+                }
+                Either6::Sixth(Either3::First(ShellRequest::ResetSpeedStats)) => {
+                    self.reset_speed_stats()
+                }
+                Either6::Sixth(Either3::First(ShellRequest::ResetTrip)) => self.reset_trip(),
+                Either6::Sixth(Either3::Second(Completion::Saved(..))) => {
+                    self.set_config_store(ConfigStore::Saved)
+                }
+                Either6::Sixth(Either3::Second(Completion::Failed(..))) => {
+                    self.set_config_store(ConfigStore::Failed)
                 }
             }
         }
@@ -0,0 +1,365 @@
+//! Persistent log of the faults, so that the clocks in the field can be diagnosed without a debug
+//! probe attached.
+//!
+//! Faults are recorded from anywhere with [`record`] and written to the flash by
+//! [`FaultLog::run`]. A panic can not wait for that, so [`save_panic`] keeps it in the RAM which
+//! survives the reboot, and it is written by [`FaultLog::new`] on the next boot.
+
+use core::cell::{Cell, RefCell};
+use core::fmt::{self, Write as _};
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+
+use embassy_sync::{
+    blocking_mutex::{
+        Mutex,
+        raw::{CriticalSectionRawMutex, RawMutex},
+    },
+    channel::Channel,
+    watch::DynReceiver,
+};
+use embassy_time::{Instant, with_timeout};
+use embedded_storage::nor_flash::NorFlash;
+
+use chrono::{DateTime, NaiveDateTime};
+
+use misc::log_store::{self, Cursor, LogStore};
+
+use crate::flash::MAX_WRITE_DELAY;
+use crate::watchdog::Heartbeat;
+
+/// Large enough for any encoded [`Entry`].
+pub const MAX_ENTRY_LEN: usize = 192;
+
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format, serde::Serialize, serde::Deserialize)]
+pub enum Fault<'a> {
+    Panic {
+        location: &'a str,
+        message: &'a str,
+    },
+    WatchdogReset,
+    /// The task stopped calling [`crate::watchdog::Heartbeat::beat`].
+    TaskStalled {
+        task: &'a str,
+    },
+    ReceiverPowerCycle(ReceiverFault),
+    /// Failed to write the value identified by [`config::envelope::Versioned::MAGIC`].
+    FlashWrite {
+        magic: u32,
+    },
+    /// The receiver rejected the configuration frame named `frame` by UBX-ACK-NAK.
+    ConfigRejected {
+        frame: &'a str,
+    },
+    /// UBX-SEC-SIG started to indicate spoofing.
+    Spoofing,
+}
+
+/// Why the receiver has been power-cycled.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format, serde::Serialize, serde::Deserialize)]
+pub enum ReceiverFault {
+    /// The receiver did not respond on I2C after the reset.
+    NotReady,
+    /// No response in time, EXTINT (TX_READY) was not asserted or no UBX-ACK arrived.
+    Timeout,
+    I2c,
+    Uart,
+    /// A request was rejected by UBX-ACK-NAK.
+    Nak,
+}
+
+impl ReceiverFault {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::NotReady => "not ready",
+            Self::Timeout => "no response",
+            Self::I2c => "I2C error",
+            Self::Uart => "UART errors",
+            Self::Nak => "NAK",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format, serde::Serialize, serde::Deserialize)]
+pub struct Entry<'a> {
+    /// Seconds since the boot.
+    pub uptime_secs: u32,
+    /// UNIX time estimated from the last navigation solution, if any.
+    pub utc_secs: Option<u32>,
+    #[serde(borrow)]
+    pub fault: Fault<'a>,
+}
+
+#[derive(Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    Flash,
+    Corrupted,
+}
+
+static FAULTS: Channel<CriticalSectionRawMutex, Entry<'static>, 4> = Channel::new();
+
+/// Last known UTC and the uptime at that moment, both in seconds.
+static CLOCK: Mutex<CriticalSectionRawMutex, Cell<Option<(u32, u32)>>> =
+    Mutex::new(Cell::new(None));
+
+/// Queues `fault` to be written. Dropped if the queue is full.
+pub fn record(fault: Fault<'static>) {
+    let entry = Entry::now(fault);
+    defmt::warn!("fault: {}", entry);
+    if FAULTS.try_send(entry).is_err() {
+        defmt::warn!("fault log queue full");
+    }
+}
+
+/// Updates the clock used to timestamp the entries.
+pub fn set_utc(datetime: NaiveDateTime) {
+    let utc_secs = datetime.and_utc().timestamp().clamp(0, u32::MAX.into()) as u32;
+    CLOCK.lock(|c| c.set(Some((utc_secs, uptime_secs()))));
+}
+
+fn uptime_secs() -> u32 {
+    Instant::now().as_secs() as u32
+}
+
+/// Returns the uptime and the UTC, if known.
+fn timestamp() -> (u32, Option<u32>) {
+    let uptime_secs = uptime_secs();
+    let utc_secs = CLOCK
+        .lock(|c| c.get())
+        .map(|(utc, at)| utc.saturating_add(uptime_secs - at));
+    (uptime_secs, utc_secs)
+}
+
+impl Entry<'_> {
+    pub fn now(fault: Fault<'_>) -> Entry<'_> {
+        let (uptime_secs, utc_secs) = timestamp();
+        Entry {
+            uptime_secs,
+            utc_secs,
+            fault,
+        }
+    }
+}
+
+impl fmt::Display for Entry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self
+            .utc_secs
+            .and_then(|t| DateTime::from_timestamp(t.into(), 0))
+        {
+            Some(t) => write!(f, "{} UTC", t.naive_utc())?,
+            None => write!(f, "{} s after boot", self.uptime_secs)?,
+        }
+        match self.fault {
+            Fault::Panic { location, message } => write!(f, ": panic at {}: {}", location, message),
+            Fault::WatchdogReset => write!(f, ": watchdog reset"),
+            Fault::TaskStalled { task } => write!(f, ": task stalled: {}", task),
+            Fault::ReceiverPowerCycle(reason) => {
+                write!(f, ": receiver power-cycled ({})", reason.description())
+            }
+            Fault::FlashWrite { magic } => {
+                let magic = magic.to_le_bytes();
+                let name = core::str::from_utf8(&magic).unwrap_or("?");
+                write!(f, ": failed to write {} to the flash", name)
+            }
+            Fault::ConfigRejected { frame } => {
+                write!(f, ": receiver rejected the configuration ({})", frame)
+            }
+            Fault::Spoofing => write!(f, ": spoofing indicated"),
+        }
+    }
+}
+
+/// Read access for the USB shell.
+pub trait Reader {
+    /// Reads the entry at `cursor`, from the oldest one (`Cursor::default()`).
+    fn read_next<'b>(
+        &self,
+        cursor: &mut Cursor,
+        buf: &'b mut [u8; MAX_ENTRY_LEN],
+    ) -> Result<Option<Entry<'b>>, Error>;
+
+    fn clear(&self) -> Result<(), Error>;
+}
+
+/// Ring of entries in a dedicated partition of at least 2 sectors. The oldest entries are erased
+/// with their sector once the partition is full.
+pub struct FaultLog<M: RawMutex, F: NorFlash> {
+    store: Mutex<M, RefCell<LogStore<F>>>,
+}
+
+impl<M, F> FaultLog<M, F>
+where
+    M: RawMutex,
+    F: NorFlash,
+    F::Error: defmt::Format,
+{
+    /// Also writes the panic saved by [`save_panic`] before the reboot.
+    pub fn new(flash: F) -> Result<Self, log_store::Error<F::Error>> {
+        let log = Self {
+            store: Mutex::new(RefCell::new(LogStore::new(flash)?)),
+        };
+        if let Some(panic) = take_panic() {
+            let _ = log.append(&panic.entry());
+        }
+        Ok(log)
+    }
+
+    pub fn append(&self, entry: &Entry<'_>) -> Result<(), Error> {
+        let mut buf = [0; MAX_ENTRY_LEN];
+        let data = postcard::to_slice(entry, &mut buf).map_err(|_| Error::Corrupted)?;
+        self.store
+            .lock(|s| s.borrow_mut().append(data))
+            .map_err(|e| {
+                defmt::error!("failed to write the fault log: {}", e);
+                Error::Flash
+            })
+    }
+
+    /// Writes the entries queued by [`record`], right after the display has been refreshed as
+    /// [`crate::flash::BackgroundWriter`] does.
+    pub async fn run(&self, mut time_pulse: DynReceiver<'_, ()>, heartbeat: &Heartbeat) -> ! {
+        loop {
+            let entry = heartbeat.idle(FAULTS.receive()).await;
+            let _ = time_pulse.try_changed();
+            let _ = with_timeout(MAX_WRITE_DELAY, time_pulse.changed()).await;
+            let _ = self.append(&entry);
+            while let Ok(entry) = FAULTS.try_receive() {
+                let _ = self.append(&entry);
+            }
+        }
+    }
+}
+
+impl<M, F> Reader for FaultLog<M, F>
+where
+    M: RawMutex,
+    F: NorFlash,
+    F::Error: defmt::Format,
+{
+    fn read_next<'b>(
+        &self,
+        cursor: &mut Cursor,
+        buf: &'b mut [u8; MAX_ENTRY_LEN],
+    ) -> Result<Option<Entry<'b>>, Error> {
+        let data = self
+            .store
+            .lock(|s| s.borrow_mut().read_next(cursor, buf))
+            .map_err(|e| {
+                defmt::error!("failed to read the fault log: {}", e);
+                match e {
+                    log_store::Error::Flash(..) => Error::Flash,
+                    _ => Error::Corrupted,
+                }
+            })?;
+        match data {
+            Some(data) => Ok(Some(
+                postcard::from_bytes(data).map_err(|_| Error::Corrupted)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn clear(&self) -> Result<(), Error> {
+        self.store.lock(|s| s.borrow_mut().clear()).map_err(|e| {
+            defmt::error!("failed to clear the fault log: {}", e);
+            Error::Flash
+        })
+    }
+}
+
+const PANIC_MAGIC: u32 = u32::from_le_bytes(*b"PANC");
+
+/// Kept in the RAM which is not initialized at the boot. Only plain integers, as the memory may
+/// hold anything after a power-on.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct PanicRecord {
+    magic: u32,
+    uptime_secs: u32,
+    /// 0 if unknown
+    utc_secs: u32,
+    location: Text<48>,
+    message: Text<96>,
+}
+
+#[unsafe(link_section = ".uninit.fault_log")]
+static mut PANIC: MaybeUninit<PanicRecord> = MaybeUninit::uninit();
+
+/// Keeps the panic to be written to the log on the next boot.
+pub fn save_panic(info: &PanicInfo) {
+    let (uptime_secs, utc_secs) = timestamp();
+    let mut record = PanicRecord {
+        magic: PANIC_MAGIC,
+        uptime_secs,
+        utc_secs: utc_secs.unwrap_or(0),
+        location: Text::new(),
+        message: Text::new(),
+    };
+    if let Some(l) = info.location() {
+        let _ = write!(record.location, "{}:{}", l.file(), l.line());
+    }
+    let _ = write!(record.message, "{}", info.message());
+    // SAFETY: only accessed here and in `take_panic`, from the thread mode
+    unsafe { (&raw mut PANIC).write_volatile(MaybeUninit::new(record)) };
+}
+
+fn take_panic() -> Option<PanicRecord> {
+    // SAFETY: see `save_panic`; every bit pattern is a valid `PanicRecord`
+    let record = unsafe { (&raw const PANIC).read_volatile().assume_init() };
+    if record.magic != PANIC_MAGIC {
+        return None;
+    }
+    // SAFETY: as above, `magic` is the first field
+    unsafe { (&raw mut PANIC).cast::<u32>().write_volatile(0) };
+    Some(record)
+}
+
+impl PanicRecord {
+    fn entry(&self) -> Entry<'_> {
+        Entry {
+            uptime_secs: self.uptime_secs,
+            utc_secs: (self.utc_secs != 0).then_some(self.utc_secs),
+            fault: Fault::Panic {
+                location: self.location.as_str(),
+                message: self.message.as_str(),
+            },
+        }
+    }
+}
+
+/// String truncated to `N` bytes.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Text<const N: usize> {
+    len: u32,
+    bytes: [u8; N],
+}
+
+impl<const N: usize> Text<N> {
+    fn new() -> Self {
+        Self {
+            len: 0,
+            bytes: [0; N],
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        let bytes = self.bytes.get(..self.len as usize).unwrap_or_default();
+        core::str::from_utf8(bytes).unwrap_or("?")
+    }
+}
+
+impl<const N: usize> fmt::Write for Text<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let len = self.len as usize;
+            let Some(dst) = self.bytes.get_mut(len..len + c.len_utf8()) else {
+                return Err(fmt::Error);
+            };
+            c.encode_utf8(dst);
+            self.len += c.len_utf8() as u32;
+        }
+        Ok(())
+    }
+}
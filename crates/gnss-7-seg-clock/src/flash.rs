@@ -1,71 +1,171 @@
-use embassy_rp::Peri;
-use embassy_rp::flash::{self, Blocking, ERASE_SIZE, Flash, Instance};
+use embassy_sync::{
+    blocking_mutex::raw::RawMutex, channel::DynamicSender, signal::Signal, watch::DynReceiver,
+};
+use embassy_time::{Duration, with_timeout};
+use embedded_storage::nor_flash::NorFlash;
+
+use config::envelope::{self, Versioned};
+use misc::log_store::{self, LogStore};
+
+use crate::fault_log::{self, Fault};
+use crate::watchdog::Heartbeat;
 
 #[derive(defmt::Format, Debug)]
-pub enum Error {
-    DataTooLarge(usize),
-    BufferTooSmall(usize),
-    Flash(flash::Error),
-    Postcard(postcard::Error),
+pub enum Error<E> {
+    Store(log_store::Error<E>),
+    Envelope(envelope::Error),
 }
 
-pub struct NonVolatileConfig<'d, C, T, const FLASH_SIZE: usize, const OFFSET: u32, const N: usize>
+/// Stores a value in `flash`, which is expected to be a dedicated partition of several sectors.
+/// Every write appends a new record (see [`LogStore`]), so that a power loss while writing falls
+/// back to the previous value. Values stored with an older layout are upgraded by
+/// [`Versioned::migrate`].
+pub struct NonVolatileConfig<C, F, const N: usize>
 where
-    T: Instance,
+    F: NorFlash,
 {
     buf: [u8; N],
-    flash: Flash<'d, T, Blocking, FLASH_SIZE>,
+    store: LogStore<F>,
     _pd: core::marker::PhantomData<C>,
 }
 
-impl<'d, C, T, const FLASH_SIZE: usize, const OFFSET: u32, const N: usize>
-    NonVolatileConfig<'d, C, T, FLASH_SIZE, OFFSET, N>
+impl<C, F, const N: usize> NonVolatileConfig<C, F, N>
 where
-    T: Instance,
-    C: Default + serde::Serialize + serde::de::DeserializeOwned,
+    F: NorFlash,
+    C: Default + Versioned,
 {
-    pub fn new(flash: Peri<'d, T>) -> Self {
-        Self {
+    pub fn new(flash: F) -> Result<Self, Error<F::Error>> {
+        Ok(Self {
             buf: [0; N],
-            flash: Flash::<_, Blocking, FLASH_SIZE>::new_blocking(flash),
+            store: LogStore::new(flash)?,
             _pd: core::marker::PhantomData,
-        }
+        })
     }
 
-    pub fn read_or_default(&mut self) -> Result<C, Error> {
+    pub fn read_or_default(&mut self) -> Result<C, Error<F::Error>> {
+        self.read_or_else(C::default)
+    }
+
+    /// Reads the value, or writes and returns `f()` if there is none or it cannot be decoded.
+    pub fn read_or_else(&mut self, f: impl FnOnce() -> C) -> Result<C, Error<F::Error>> {
         match self.read() {
-            r @ Ok(..) | r @ Err(Error::Flash(..)) => r,
-            _ => {
+            Ok(Some(value)) => Ok(value),
+            Err(e @ Error::Store(..)) => Err(e),
+            r => {
+                if let Err(Error::Envelope(e)) = r {
+                    defmt::warn!("failed to decode the stored value: {}", e);
+                }
                 defmt::info!("fallback to default config");
-                let default = C::default();
-                self.write(&default)?;
-                Ok(default)
+                let value = f();
+                self.write(&value)?;
+                Ok(value)
             }
         }
     }
 
-    pub fn read(&mut self) -> Result<C, Error> {
-        self.flash.blocking_read(OFFSET, self.buf.as_mut_slice())?;
-        Ok(postcard::from_bytes_cobs(self.buf.as_mut_slice())?)
+    /// Returns `None` if nothing has been written yet.
+    pub fn read(&mut self) -> Result<Option<C>, Error<F::Error>> {
+        match self.store.read_latest(self.buf.as_mut_slice())? {
+            Some(data) => Ok(Some(envelope::decode(data)?)),
+            None => Ok(None),
+        }
     }
 
-    pub fn write(&mut self, value: &C) -> Result<(), Error> {
-        let data = postcard::to_slice_cobs(value, self.buf.as_mut_slice())?;
-        let erase_size = ((data.len() + ERASE_SIZE) / ERASE_SIZE * ERASE_SIZE) as u32;
-        self.flash.blocking_erase(OFFSET, OFFSET + erase_size)?;
-        self.flash.blocking_write(OFFSET, data)?;
+    pub fn write(&mut self, value: &C) -> Result<(), Error<F::Error>> {
+        let data = envelope::encode(value, self.buf.as_mut_slice())?;
+        self.store.append(data)?;
         Ok(())
     }
 }
 
-impl From<flash::Error> for Error {
-    fn from(e: flash::Error) -> Self {
-        Self::Flash(e)
+/// Erases the whole `flash`, so that [`NonVolatileConfig::read_or_default`] falls back to the
+/// default.
+pub fn erase<F: NorFlash>(flash: &mut F) -> Result<(), F::Error> {
+    flash.erase(0, flash.capacity() as u32)
+}
+
+/// Writes are delayed by up to this long waiting for a time pulse.
+pub(crate) const MAX_WRITE_DELAY: Duration = Duration::from_secs(3);
+
+#[derive(defmt::Format)]
+pub enum Completion<C> {
+    Saved(C),
+    Failed(C),
+}
+
+/// Writes the values requested through `requests` in the background. The flash is blocking and
+/// stalls the execution while erasing, so the writes are done right after the display has been
+/// refreshed by a time pulse (or `MAX_WRITE_DELAY` without the pulses), well before the next one.
+/// Requests made in the meantime are coalesced and only the latest one is written.
+pub struct BackgroundWriter<'d, M, C, F, const N: usize>
+where
+    M: RawMutex,
+    F: NorFlash,
+{
+    nv: NonVolatileConfig<C, F, N>,
+    requests: &'d Signal<M, C>,
+    time_pulse: DynReceiver<'d, ()>,
+    completions: Option<DynamicSender<'d, Completion<C>>>,
+    heartbeat: &'d Heartbeat,
+}
+
+impl<'d, M, C, F, const N: usize> BackgroundWriter<'d, M, C, F, N>
+where
+    M: RawMutex,
+    F: NorFlash,
+    F::Error: defmt::Format,
+    C: Default + Versioned,
+{
+    pub fn new(
+        nv: NonVolatileConfig<C, F, N>,
+        requests: &'d Signal<M, C>,
+        time_pulse: DynReceiver<'d, ()>,
+        completions: Option<DynamicSender<'d, Completion<C>>>,
+        heartbeat: &'d Heartbeat,
+    ) -> Self {
+        Self {
+            nv,
+            requests,
+            time_pulse,
+            completions,
+            heartbeat,
+        }
+    }
+
+    pub async fn run(&mut self) -> ! {
+        loop {
+            let mut value = self.heartbeat.idle(self.requests.wait()).await;
+
+            // the pulse seen before the request does not count
+            let _ = self.time_pulse.try_changed();
+            let _ = with_timeout(MAX_WRITE_DELAY, self.time_pulse.changed()).await;
+            if let Some(newer) = self.requests.try_take() {
+                value = newer;
+            }
+
+            let completion = match self.nv.write(&value) {
+                Ok(()) => Completion::Saved(value),
+                Err(e) => {
+                    defmt::error!("failed to write: {}", e);
+                    fault_log::record(Fault::FlashWrite { magic: C::MAGIC });
+                    Completion::Failed(value)
+                }
+            };
+            if let Some(completions) = &self.completions {
+                self.heartbeat.idle(completions.send(completion)).await;
+            }
+        }
+    }
+}
+
+impl<E> From<log_store::Error<E>> for Error<E> {
+    fn from(e: log_store::Error<E>) -> Self {
+        Self::Store(e)
     }
 }
 
-impl From<postcard::Error> for Error {
-    fn from(e: postcard::Error) -> Self {
-        Self::Postcard(e)
+impl<E> From<envelope::Error> for Error<E> {
+    fn from(e: envelope::Error) -> Self {
+        Self::Envelope(e)
     }
 }
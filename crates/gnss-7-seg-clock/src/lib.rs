@@ -1,6 +1,16 @@
 #![no_std]
 
+pub mod assist_now;
+pub mod config;
 pub mod display;
 pub mod events;
+pub mod fault_log;
 pub mod flash;
 pub mod max_m10s;
+pub mod odometer;
+pub mod position;
+pub mod shell;
+pub mod speed;
+pub mod status;
+pub mod timing;
+pub mod watchdog;
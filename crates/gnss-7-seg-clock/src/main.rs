@@ -1,38 +1,94 @@
 #![no_std]
 #![no_main]
 
+use embassy_embedded_hal::flash::partition::BlockingPartition;
 use embassy_executor::Spawner;
+use embassy_futures::select::*;
 use embassy_rp::dma;
 use embassy_rp::flash;
 use embassy_rp::gpio;
 use embassy_rp::i2c;
-use embassy_rp::peripherals::{DMA_CH0, I2C1, UART1};
+use embassy_rp::peripherals::{DMA_CH0, FLASH, I2C1, UART1, USB};
 use embassy_rp::spi;
 use embassy_rp::uart;
+use embassy_rp::usb;
+use embassy_rp::watchdog::{ResetReason, Watchdog};
 use embassy_sync::{
-    blocking_mutex::raw::{RawMutex, ThreadModeRawMutex},
+    blocking_mutex::{
+        Mutex,
+        raw::{RawMutex, ThreadModeRawMutex},
+    },
     channel::Channel,
+    signal::Signal,
+    watch::{DynAnonReceiver, DynReceiver, Watch},
 };
+use embassy_time::{Duration, Instant, Ticker, Timer, with_timeout};
+use embassy_usb::class::cdc_acm::{self, CdcAcmClass};
 use static_cell::StaticCell;
 
-use chrono::{Datelike, FixedOffset, NaiveDate, NaiveTime, Timelike};
+use core::cell::RefCell;
+use core::fmt::{self, Write as _};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use config::envelope::Versioned;
+
+use ubx::mon::AntennaStatus;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
 
 use gnss_7_seg_clock::{
+    assist_now::AssistNow,
+    config::{AutoRotate, Config, Constellation, DisplayMode},
     display::{self, Display},
     events::*,
-    flash::NonVolatileConfig,
-    max_m10s::{Event as MaxM10sEvent, MaxM10s},
+    fault_log::{self, Fault, FaultLog},
+    flash::{self as nv, BackgroundWriter, Completion, NonVolatileConfig},
+    max_m10s::{
+        self, Command as MaxM10sCommand, Event as MaxM10sEvent, ExternalEvent, HealthState, MaxM10s,
+    },
+    odometer::OdometerRecord,
+    position::PositionRecord,
+    shell::{ConfigUpdate, Request as ShellRequest, Shell},
+    speed::SpeedUnit,
+    status::{ConfigStore, Status},
+    timing,
+    watchdog::{Heartbeat, Supervisor, WATCHDOG_TIMEOUT},
 };
 
-use {defmt_rtt as _, panic_probe as _};
+use defmt_rtt as _;
 
 embassy_rp::bind_interrupts!(struct Irqs {
     DMA_IRQ_0 => dma::InterruptHandler<DMA_CH0>;
     I2C1_IRQ => i2c::InterruptHandler<I2C1>;
     UART1_IRQ => uart::BufferedInterruptHandler<UART1>;
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
 });
 
 type MaxM10sEventChannel = Channel<ThreadModeRawMutex, MaxM10sEvent, 8>;
+type MaxM10sCommandSignal = Signal<ThreadModeRawMutex, MaxM10sCommand>;
+type MaxM10sDoneSignal = Signal<ThreadModeRawMutex, bool>;
+type FactoryResetChannel = Channel<ThreadModeRawMutex, (), 1>;
+type ShellRequestChannel = Channel<ThreadModeRawMutex, ShellRequest, 4>;
+type ExternalEventChannel = Channel<ThreadModeRawMutex, ExternalEvent, 8>;
+type ConfigWatch = Watch<ThreadModeRawMutex, Config, 1>;
+type StatusWatch = Watch<ThreadModeRawMutex, Status, 1>;
+type ModeWatch = Watch<ThreadModeRawMutex, DisplayMode, 1>;
+type OdometerSignal = Signal<ThreadModeRawMutex, OdometerRecord>;
+type PositionSignal = Signal<ThreadModeRawMutex, PositionRecord>;
+type ConfigSignal = Signal<ThreadModeRawMutex, Config>;
+type ConfigStoreChannel = Channel<ThreadModeRawMutex, Completion<Config>, 1>;
+type PulseSignal = Signal<ThreadModeRawMutex, Instant>;
+type TimePulseWatch = Watch<ThreadModeRawMutex, (), 5>;
+type SharedFlash =
+    Mutex<ThreadModeRawMutex, RefCell<flash::Flash<'static, FLASH, flash::Blocking, FLASH_SIZE>>>;
+type FlashPartition = BlockingPartition<
+    'static,
+    ThreadModeRawMutex,
+    flash::Flash<'static, FLASH, flash::Blocking, FLASH_SIZE>,
+>;
+type FaultLogPartition = FaultLog<ThreadModeRawMutex, FlashPartition>;
+type AssistNowPartition = AssistNow<ThreadModeRawMutex, FlashPartition>;
 
 //
 //     +- A -+
@@ -55,9 +111,82 @@ const TABLE: [u8; 10] = [
     0b11011011_u8, // '9'
 ];
 
-#[allow(dead_code)]
 const MASK_DP: u8 = 0b00100000;
 
+const CHAR_F: u8 = 0b11010100;
+const CHAR_G: u8 = TABLE[9];
+const CHAR_H: u8 = 0b11000101;
+const CHAR_K: u8 = 0b11010101;
+const CHAR_N: u8 = 0b10000101;
+const CHAR_O: u8 = 0b10000111;
+const CHAR_P: u8 = 0b11011100;
+const CHAR_S: u8 = TABLE[5];
+const CHAR_E: u8 = 0b11010110;
+const CHAR_R: u8 = 0b10000100;
+const CHAR_T: u8 = 0b11000110;
+
+/// Segments of `c`, blank if it can not be shown. Some letters are only approximated.
+const fn glyph(c: char) -> u8 {
+    match c {
+        '0'..='9' => TABLE[c as usize - '0' as usize],
+        'A' | 'a' => 0b11011101,
+        'B' | 'b' => 0b11000111,
+        'C' => 0b01010110,
+        'c' => 0b10000110,
+        'D' | 'd' => 0b10001111,
+        'E' | 'e' => CHAR_E,
+        'F' | 'f' => CHAR_F,
+        'G' => 0b01010111,
+        'g' => CHAR_G,
+        'H' | 'X' | 'x' => 0b11001101,
+        'h' => CHAR_H,
+        'I' => 0b01000100,
+        'i' => 0b00000001,
+        'J' | 'j' => 0b00001111,
+        'K' | 'k' => CHAR_K,
+        'L' | 'l' => 0b01000110,
+        'M' | 'm' => 0b01011101,
+        'N' | 'n' => CHAR_N,
+        'O' | 'o' => CHAR_O,
+        'P' | 'p' => CHAR_P,
+        'Q' | 'q' => 0b11011001,
+        'R' | 'r' => CHAR_R,
+        'S' | 's' => CHAR_S,
+        'T' | 't' => CHAR_T,
+        'U' | 'V' => 0b01001111,
+        'u' | 'v' => 0b00000111,
+        'W' | 'w' => 0b01001010,
+        'Y' | 'y' => 0b11001011,
+        'Z' | 'z' => TABLE[2],
+        '-' => 0b10000000,
+        '_' => 0b00000010,
+        '=' => 0b10000010,
+        '(' | '[' => 0b01010110,
+        ')' | ']' => 0b00011011,
+        '.' | ',' => MASK_DP,
+        _ => 0,
+    }
+}
+
+/// Segments of the text written, with the dots merged into the preceding characters.
+struct Segments<const N: usize>(heapless::Vec<u8, N>);
+
+impl<const N: usize> fmt::Write for Segments<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if c == '.'
+                && let Some(last) = self.0.last_mut()
+                && *last & MASK_DP == 0
+            {
+                *last |= MASK_DP;
+                continue;
+            }
+            self.0.push(glyph(c)).map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
 // "--.--.--"
 const PATTERN_NO_TIME: display::Payload = display::Payload([
     0b10000000_u8,
@@ -68,6 +197,20 @@ const PATTERN_NO_TIME: display::Payload = display::Payload([
     0b10000000_u8,
 ]);
 
+// "Err gn", the receiver keeps failing
+const PATTERN_ERR_GNSS: display::Payload =
+    display::Payload([CHAR_N, CHAR_G, 0, CHAR_R, CHAR_R, CHAR_E]);
+
+// "rESEt"
+const PATTERN_RESET: display::Payload =
+    display::Payload([CHAR_T, CHAR_E, CHAR_S, CHAR_E, CHAR_R, 0]);
+
+// "Ant SH" / "Ant oP", the antenna supervisor reports a short / open circuit
+const PATTERN_ANT_SHORT: display::Payload =
+    display::Payload([glyph('H'), CHAR_S, 0, CHAR_T, CHAR_N, glyph('A')]);
+const PATTERN_ANT_OPEN: display::Payload =
+    display::Payload([CHAR_P, CHAR_O, 0, CHAR_T, CHAR_N, glyph('A')]);
+
 fn date_to_display_payload(date: NaiveDate) -> display::Payload {
     display::Payload([
         TABLE[date.day() as usize % 10],
@@ -90,6 +233,34 @@ fn time_to_display_payload(time: NaiveTime) -> display::Payload {
     ])
 }
 
+/// `mm.ss.t` or `mm.ss.hh`, the minutes and seconds staying in place
+fn sub_second_to_display_payload(time: NaiveTime, hundredths: bool) -> display::Payload {
+    // beyond a second during a leap second
+    let centis = time.nanosecond() as usize / 10_000_000;
+    display::Payload([
+        if hundredths { TABLE[centis % 10] } else { 0 },
+        TABLE[centis / 10 % 10],
+        TABLE[time.second() as usize % 10] | MASK_DP,
+        TABLE[time.second() as usize / 10 % 10],
+        TABLE[time.minute() as usize % 10] | MASK_DP,
+        TABLE[time.minute() as usize / 10 % 10],
+    ])
+}
+
+/// "ss.ffff", i.e. to 100 µs
+fn external_event_to_display_payload(time: NaiveTime) -> display::Payload {
+    // beyond a second during a leap second
+    let frac = time.nanosecond() as usize / 100_000;
+    display::Payload([
+        TABLE[frac % 10],
+        TABLE[frac / 10 % 10],
+        TABLE[frac / 100 % 10],
+        TABLE[frac / 1000 % 10],
+        TABLE[time.second() as usize % 10] | MASK_DP,
+        TABLE[time.second() as usize / 10 % 10],
+    ])
+}
+
 fn u32_to_display_payload(value: u32) -> display::Payload {
     let mut arr = [
         TABLE[value as usize % 10],
@@ -110,36 +281,23 @@ fn u32_to_display_payload(value: u32) -> display::Payload {
 }
 
 const FLASH_SIZE: usize = 4 * 1024 * 1024; // W25Q32JVSS
-const ADDR_OFFSET: u32 = (FLASH_SIZE - flash::ERASE_SIZE) as u32;
-
-#[derive(Copy, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize, defmt::Format)]
-struct Config {
-    time_zone_secs: i32,
-}
-
-impl Config {
-    fn time_zone(&self) -> FixedOffset {
-        FixedOffset::east_opt(self.time_zone_secs).unwrap()
-    }
-}
+// each partition has 4 sectors for the wear levelling
+const PARTITION_SIZE: u32 = 4 * flash::ERASE_SIZE as u32;
+const CONFIG_OFFSET: u32 = FLASH_SIZE as u32 - PARTITION_SIZE;
+const ODOMETER_OFFSET: u32 = CONFIG_OFFSET - PARTITION_SIZE;
+// a ring of 2 sectors, the older one is erased when the newer one is full
+const FAULT_LOG_SIZE: u32 = 2 * flash::ERASE_SIZE as u32;
+const FAULT_LOG_OFFSET: u32 = ODOMETER_OFFSET - FAULT_LOG_SIZE;
+const POSITION_OFFSET: u32 = FAULT_LOG_OFFSET - PARTITION_SIZE;
+// AssistNow Offline data for a few weeks of the major constellations
+const ASSIST_NOW_SIZE: u32 = 512 * 1024;
+const ASSIST_NOW_OFFSET: u32 = POSITION_OFFSET - ASSIST_NOW_SIZE;
+// where the config was stored before `NonVolatileConfig` used the log, within the config partition
+const LEGACY_CONFIG_OFFSET: u32 = (FLASH_SIZE - flash::ERASE_SIZE) as u32;
 
-#[derive(Copy, Clone, PartialEq, defmt::Format)]
-enum DisplayMode {
-    Time,
-    Date,
-    Velocity,
-    ConfigTimeZone,
-}
-
-impl DisplayMode {
-    fn next_state(&self) -> DisplayMode {
-        match self {
-            DisplayMode::Time => DisplayMode::Date,
-            DisplayMode::Date => DisplayMode::Velocity,
-            DisplayMode::Velocity => DisplayMode::ConfigTimeZone,
-            DisplayMode::ConfigTimeZone => DisplayMode::Time,
-        }
-    }
+enum Exit {
+    NextMode,
+    ConfigChanged(ConfigUpdate),
 }
 
 #[embassy_executor::main]
@@ -148,15 +306,97 @@ async fn main(spawner: Spawner) {
 
     defmt::info!("Hello World!");
 
-    let mut nvcfg = NonVolatileConfig::<_, _, FLASH_SIZE, ADDR_OFFSET, 512>::new(p.FLASH);
-    let mut cfg: Config = defmt::unwrap!(nvcfg.read_or_default());
-    defmt::info!("{}", cfg);
+    static FLASH: StaticCell<SharedFlash> = StaticCell::new();
+    let shared_flash = FLASH.init(Mutex::new(RefCell::new(flash::Flash::new_blocking(
+        p.FLASH,
+    ))));
+
+    static FAULT_LOG: StaticCell<FaultLogPartition> = StaticCell::new();
+    let fault_log = FAULT_LOG.init(defmt::unwrap!(FaultLog::new(BlockingPartition::new(
+        shared_flash,
+        FAULT_LOG_OFFSET,
+        FAULT_LOG_SIZE,
+    ))));
+    let mut watchdog = Watchdog::new(p.WATCHDOG);
+    if watchdog.reset_reason() == Some(ResetReason::TimedOut) {
+        fault_log::record(Fault::WatchdogReset);
+    }
+    watchdog.pause_on_debug(true);
+    watchdog.start(WATCHDOG_TIMEOUT);
+
+    static MAIN_HEARTBEAT: Heartbeat = Heartbeat::new("main");
+    static MAX_M10S_HEARTBEAT: Heartbeat = Heartbeat::new("max-m10s");
+    static CONFIG_STORE_HEARTBEAT: Heartbeat = Heartbeat::new("config-store");
+    static ODOMETER_STORE_HEARTBEAT: Heartbeat = Heartbeat::new("odometer-store");
+    static FAULT_LOG_HEARTBEAT: Heartbeat = Heartbeat::new("fault-log");
+    static POSITION_STORE_HEARTBEAT: Heartbeat = Heartbeat::new("position-store");
+    static LEDS_HEARTBEAT: Heartbeat = Heartbeat::new("leds");
+    static PPS_HEARTBEAT: Heartbeat = Heartbeat::new("pps");
+    static USB_HEARTBEAT: Heartbeat = Heartbeat::new("usb");
+    static SHELL_HEARTBEAT: Heartbeat = Heartbeat::new("shell");
+    static HEARTBEATS: [&Heartbeat; 10] = [
+        &MAIN_HEARTBEAT,
+        &MAX_M10S_HEARTBEAT,
+        &CONFIG_STORE_HEARTBEAT,
+        &ODOMETER_STORE_HEARTBEAT,
+        &FAULT_LOG_HEARTBEAT,
+        &POSITION_STORE_HEARTBEAT,
+        &LEDS_HEARTBEAT,
+        &PPS_HEARTBEAT,
+        &USB_HEARTBEAT,
+        &SHELL_HEARTBEAT,
+    ];
+    for h in HEARTBEATS {
+        h.beat();
+    }
+    spawner.spawn(defmt::unwrap!(task_supervisor(
+        Supervisor::new(watchdog, &HEARTBEATS),
+        fault_log,
+    )));
 
     let sw3 = gpio::Input::new(p.PIN_0, gpio::Pull::None);
     let sw4 = gpio::Input::new(p.PIN_6, gpio::Pull::None);
     let sw5 = gpio::Input::new(p.PIN_7, gpio::Pull::None);
 
-    let mut leds = [
+    // SW3+SW5 held at power-up
+    let factory_reset = sw3.is_low() && sw5.is_low();
+    if factory_reset {
+        defmt::info!("factory reset");
+        erase_config(shared_flash);
+    }
+
+    let mut nvcfg = defmt::unwrap!(NonVolatileConfig::<_, _, 512>::new(BlockingPartition::new(
+        shared_flash,
+        CONFIG_OFFSET,
+        PARTITION_SIZE,
+    )));
+    let mut cfg: Config = defmt::unwrap!(nvcfg.read_or_else(|| read_legacy_config(shared_flash)));
+    defmt::info!("{}", cfg);
+
+    let mut nvodo = defmt::unwrap!(NonVolatileConfig::<_, _, 64>::new(BlockingPartition::new(
+        shared_flash,
+        ODOMETER_OFFSET,
+        PARTITION_SIZE,
+    )));
+    let odometer: OdometerRecord = defmt::unwrap!(nvodo.read_or_default());
+    defmt::info!("{}", odometer);
+
+    let mut nvpos = defmt::unwrap!(NonVolatileConfig::<_, _, 64>::new(BlockingPartition::new(
+        shared_flash,
+        POSITION_OFFSET,
+        PARTITION_SIZE,
+    )));
+    let position: PositionRecord = defmt::unwrap!(nvpos.read_or_default());
+    defmt::info!("{}", position);
+
+    static ASSIST_NOW: StaticCell<AssistNowPartition> = StaticCell::new();
+    let assist_now = ASSIST_NOW.init(AssistNow::new(BlockingPartition::new(
+        shared_flash,
+        ASSIST_NOW_OFFSET,
+        ASSIST_NOW_SIZE,
+    )));
+
+    let leds = [
         gpio::Output::new(p.PIN_1, gpio::Level::Low),
         gpio::Output::new(p.PIN_2, gpio::Level::Low),
         gpio::Output::new(p.PIN_3, gpio::Level::Low),
@@ -169,13 +409,20 @@ async fn main(spawner: Spawner) {
         p.SPI1, p.PIN_14, p.PIN_15, p.DMA_CH0, p.PIN_11, p.PIN_13, Irqs,
     );
 
+    if factory_reset {
+        display.shift(&PATTERN_RESET).await;
+        display.refresh().await;
+        display.output(true);
+        Timer::after_secs(2).await;
+    }
+
     display.shift(&PATTERN_NO_TIME).await;
     display.refresh().await;
     display.output(true);
 
     let _uart1_tx = gpio::Input::new(p.PIN_20, gpio::Pull::Up);
     let mut max_m10s_pps = gpio::Input::new(p.PIN_19, gpio::Pull::Down);
-    let max_m10s = {
+    let mut max_m10s = {
         static UART1_BUF_RX: StaticCell<[u8; 1024]> = StaticCell::new();
         MaxM10s::new(
             p.UART1,
@@ -189,51 +436,279 @@ async fn main(spawner: Spawner) {
             Irqs,
         )
     };
+    max_m10s.set_position(position.last);
 
     static MAX_M10S_EVENT_CHANNEL: MaxM10sEventChannel = MaxM10sEventChannel::new();
+    static MAX_M10S_COMMAND_SIGNAL: MaxM10sCommandSignal = MaxM10sCommandSignal::new();
+    static MAX_M10S_DONE_SIGNAL: MaxM10sDoneSignal = MaxM10sDoneSignal::new();
+    static CONFIG_WATCH: ConfigWatch = ConfigWatch::new();
+
+    CONFIG_WATCH.sender().send(cfg);
+
+    if factory_reset {
+        MAX_M10S_COMMAND_SIGNAL.signal(MaxM10sCommand::FactoryReset);
+    }
 
     spawner.spawn(defmt::unwrap!(task_max_m10s(
         max_m10s,
-        &MAX_M10S_EVENT_CHANNEL
+        &MAX_M10S_EVENT_CHANNEL,
+        &MAX_M10S_COMMAND_SIGNAL,
+        &MAX_M10S_DONE_SIGNAL,
+        defmt::unwrap!(CONFIG_WATCH.dyn_receiver()),
+        assist_now,
+        &MAX_M10S_HEARTBEAT,
     )));
 
-    max_m10s_pps.wait_for_low().await;
+    let usb_config = {
+        let mut c = embassy_usb::Config::new(0x2e8a, 0x75e9);
+        c.manufacturer = Some("myon.info");
+        c.product = Some("GNSS 7-seg Clock");
+        c.serial_number = Some("12345678");
+        c.max_power = 100;
+        c.max_packet_size_0 = 64;
+        c
+    };
+    let mut usb_builder = {
+        static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+        static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+        static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+        embassy_usb::Builder::new(
+            usb::Driver::new(p.USB, Irqs),
+            usb_config,
+            CONFIG_DESCRIPTOR.init([0; 256]),
+            BOS_DESCRIPTOR.init([0; 256]),
+            &mut [],
+            CONTROL_BUF.init([0; 64]),
+        )
+    };
 
-    let mut mode = DisplayMode::Time;
+    static SHELL_REQUEST_CHANNEL: ShellRequestChannel = ShellRequestChannel::new();
+    static CONFIG_REPLACED_SIGNAL: ConfigSignal = ConfigSignal::new();
+    static FACTORY_RESET_CHANNEL: FactoryResetChannel = FactoryResetChannel::new();
+    static EXTERNAL_EVENT_CHANNEL: ExternalEventChannel = ExternalEventChannel::new();
+    static STATUS_WATCH: StatusWatch = StatusWatch::new();
+    static MODE_WATCH: ModeWatch = ModeWatch::new();
+
+    let shell = {
+        static STATE: StaticCell<cdc_acm::State> = StaticCell::new();
+        let state = STATE.init(cdc_acm::State::new());
+        Shell::new(
+            CdcAcmClass::new(&mut usb_builder, state, 64),
+            SHELL_REQUEST_CHANNEL.dyn_sender(),
+            &CONFIG_REPLACED_SIGNAL,
+            FACTORY_RESET_CHANNEL.dyn_sender(),
+            CONFIG_WATCH.dyn_anon_receiver(),
+            STATUS_WATCH.dyn_anon_receiver(),
+            fault_log,
+            assist_now,
+            EXTERNAL_EVENT_CHANNEL.dyn_receiver(),
+            defmt::unwrap!(TIME_PULSE_WATCH.dyn_receiver()),
+            &SHELL_HEARTBEAT,
+        )
+    };
+
+    spawner.spawn(defmt::unwrap!(task_leds(
+        leds,
+        defmt::unwrap!(MODE_WATCH.dyn_receiver()),
+        STATUS_WATCH.dyn_anon_receiver(),
+        &LEDS_HEARTBEAT,
+    )));
+    spawner.spawn(defmt::unwrap!(task_usb(
+        usb_builder.build(),
+        &USB_HEARTBEAT
+    )));
+    spawner.spawn(defmt::unwrap!(task_shell(shell)));
+    spawner.spawn(defmt::unwrap!(task_factory_reset(
+        shared_flash,
+        &FACTORY_RESET_CHANNEL,
+        &MAX_M10S_COMMAND_SIGNAL,
+        &MAX_M10S_DONE_SIGNAL,
+    )));
+
+    static TIME_PULSE_WATCH: TimePulseWatch = TimePulseWatch::new();
+    static CONFIG_SIGNAL: ConfigSignal = ConfigSignal::new();
+    static CONFIG_STORE_CHANNEL: ConfigStoreChannel = ConfigStoreChannel::new();
+    static ODOMETER_SIGNAL: OdometerSignal = OdometerSignal::new();
+    static POSITION_SIGNAL: PositionSignal = PositionSignal::new();
+
+    spawner.spawn(defmt::unwrap!(task_config_store(BackgroundWriter::new(
+        nvcfg,
+        &CONFIG_SIGNAL,
+        defmt::unwrap!(TIME_PULSE_WATCH.dyn_receiver()),
+        Some(CONFIG_STORE_CHANNEL.dyn_sender()),
+        &CONFIG_STORE_HEARTBEAT,
+    ))));
+    spawner.spawn(defmt::unwrap!(task_fault_log(
+        fault_log,
+        defmt::unwrap!(TIME_PULSE_WATCH.dyn_receiver()),
+        &FAULT_LOG_HEARTBEAT,
+    )));
+    spawner.spawn(defmt::unwrap!(task_odometer_store(BackgroundWriter::new(
+        nvodo,
+        &ODOMETER_SIGNAL,
+        defmt::unwrap!(TIME_PULSE_WATCH.dyn_receiver()),
+        None,
+        &ODOMETER_STORE_HEARTBEAT,
+    ))));
+    spawner.spawn(defmt::unwrap!(task_position_store(BackgroundWriter::new(
+        nvpos,
+        &POSITION_SIGNAL,
+        defmt::unwrap!(TIME_PULSE_WATCH.dyn_receiver()),
+        None,
+        &POSITION_STORE_HEARTBEAT,
+    ))));
+
+    MAIN_HEARTBEAT
+        .idle(PPS_HEARTBEAT.idle(max_m10s_pps.wait_for_low()))
+        .await;
+    static PULSE_SIGNAL: PulseSignal = PulseSignal::new();
+    spawner.spawn(defmt::unwrap!(task_pps(
+        max_m10s_pps,
+        &PULSE_SIGNAL,
+        &PPS_HEARTBEAT
+    )));
+
+    let mut mode = cfg.rotation.as_slice()[0];
     let mut es = EventSources::new(
         MAX_M10S_EVENT_CHANNEL.receiver(),
         sw3,
         sw4,
         sw5,
-        max_m10s_pps,
+        &PULSE_SIGNAL,
+        SHELL_REQUEST_CHANNEL.dyn_receiver(),
+        STATUS_WATCH.dyn_sender(),
+        &ODOMETER_SIGNAL,
+        &POSITION_SIGNAL,
+        TIME_PULSE_WATCH.dyn_sender(),
+        CONFIG_STORE_CHANNEL.dyn_receiver(),
+        EXTERNAL_EVENT_CHANNEL.dyn_sender(),
+        &MAIN_HEARTBEAT,
+        odometer,
+        cfg.odometer_source,
+        cfg.rate,
+        cfg.power_save,
+        position,
     );
 
     loop {
         defmt::info!("mode: {}", mode);
-        set_leds(&mut leds, mode);
-        match mode {
+        MODE_WATCH.sender().send(mode);
+        let mut new_cfg = cfg;
+        let exit = match mode {
             DisplayMode::Time => handle_mode_time(&mut es, &cfg, &mut display).await,
             DisplayMode::Date => handle_mode_date(&mut es, &cfg, &mut display).await,
-            DisplayMode::Velocity => handle_mode_velocity(&mut es, &cfg, &mut display).await,
+            DisplayMode::Velocity | DisplayMode::MaxSpeed | DisplayMode::AverageSpeed => {
+                handle_mode_speed(&mut es, &cfg, &mut display, mode).await
+            }
+            DisplayMode::Trip | DisplayMode::Odometer => {
+                handle_mode_odometer(&mut es, &cfg, &mut display, mode).await
+            }
+            DisplayMode::Heading => handle_mode_heading(&mut es, &mut display).await,
+            DisplayMode::SubSecond => handle_mode_sub_second(&mut es, &cfg, &mut display).await,
             DisplayMode::ConfigTimeZone => {
-                let t = handle_mode_config_time_zone(&mut es, &cfg, &mut display).await;
-                if t != cfg.time_zone_secs {
-                    cfg.time_zone_secs = t;
-                    defmt::unwrap!(nvcfg.write(&cfg));
+                handle_mode_config_time_zone(&mut es, &mut new_cfg, &mut display).await
+            }
+            DisplayMode::ConfigRotation => {
+                handle_mode_config_rotation(&mut es, &mut new_cfg, &mut display).await
+            }
+            DisplayMode::ConfigAutoRotate => {
+                handle_mode_config_auto_rotate(&mut es, &mut new_cfg, &mut display).await
+            }
+            DisplayMode::ConfigSpeedUnit => {
+                handle_mode_config_speed_unit(&mut es, &mut new_cfg, &mut display).await
+            }
+            DisplayMode::ConfigConstellations => {
+                handle_mode_config_constellations(&mut es, &mut new_cfg, &mut display).await
+            }
+            DisplayMode::Info => handle_mode_info(&mut es, &mut display).await,
+            DisplayMode::Timing => handle_mode_timing(&mut es, &mut display).await,
+        };
+        mode = match exit {
+            Exit::NextMode => new_cfg.next_mode(mode),
+            Exit::ConfigChanged(update) => {
+                if let ConfigUpdate::Import(..) = update {
+                    // for the shell to show what the import changed
+                    CONFIG_REPLACED_SIGNAL.signal(new_cfg);
+                }
+                update.apply(&mut new_cfg);
+                if new_cfg.is_reachable(mode) {
+                    mode
+                } else {
+                    new_cfg.next_mode(mode)
                 }
             }
+        };
+        if new_cfg != cfg {
+            cfg = new_cfg;
+            defmt::info!("{}", cfg);
+            CONFIG_SIGNAL.signal(cfg);
+            es.set_config_store(ConfigStore::Pending);
+            CONFIG_WATCH.sender().send(cfg);
+            es.odometer_source = cfg.odometer_source;
+            es.rate = cfg.rate;
+            es.power_save = cfg.power_save;
+        }
+    }
+}
+
+/// Upgrades the config written by older firmware. The log starts at the beginning of the partition,
+/// so the legacy sector is left untouched until the log wraps around.
+fn read_legacy_config(flash: &SharedFlash) -> Config {
+    let mut image = [0; 64];
+    let read = flash.lock(|f| {
+        f.borrow_mut()
+            .blocking_read(LEGACY_CONFIG_OFFSET, &mut image)
+    });
+    match read {
+        Ok(()) => config::migration::from_legacy(&mut image).unwrap_or_default(),
+        Err(e) => {
+            defmt::warn!("failed to read the legacy config: {}", e);
+            Config::default()
         }
-        mode = mode.next_state();
     }
 }
 
-fn set_leds(leds: &mut [gpio::Output<'_>; 5], mode: DisplayMode) {
-    let bits = match mode {
+/// Erases the config partition, including the legacy sector. The odometer is kept.
+fn erase_config(flash: &'static SharedFlash) {
+    let mut partition = BlockingPartition::new(flash, CONFIG_OFFSET, PARTITION_SIZE);
+    if let Err(e) = nv::erase(&mut partition) {
+        defmt::error!("failed to erase the config: {}", e);
+        fault_log::record(Fault::FlashWrite {
+            magic: <Config as Versioned>::MAGIC,
+        });
+    }
+}
+
+fn mode_leds(mode: DisplayMode) -> u8 {
+    match mode {
         DisplayMode::Time => 0b0_0001_u8,
         DisplayMode::Date => 0b0_0010_u8,
         DisplayMode::Velocity => 0b0_0011_u8,
+        DisplayMode::MaxSpeed => 0b0_0100_u8,
+        DisplayMode::AverageSpeed => 0b0_0101_u8,
+        DisplayMode::Trip => 0b0_0110_u8,
+        DisplayMode::Odometer => 0b0_0111_u8,
+        DisplayMode::Heading => 0b0_1000_u8,
+        DisplayMode::SubSecond => 0b0_1001_u8,
         DisplayMode::ConfigTimeZone => 0b1_0001_u8,
-    };
+        DisplayMode::ConfigRotation => 0b1_0010_u8,
+        DisplayMode::ConfigAutoRotate => 0b1_0011_u8,
+        DisplayMode::ConfigSpeedUnit => 0b1_0100_u8,
+        DisplayMode::ConfigConstellations => 0b1_0101_u8,
+        DisplayMode::Info => 0b1_0110_u8,
+        DisplayMode::Timing => 0b1_0111_u8,
+    }
+}
+
+/// Alternates with the mode while the antenna is faulty.
+fn antenna_fault_leds(fault: AntennaStatus) -> u8 {
+    match fault {
+        AntennaStatus::Short => 0b1_1001_u8,
+        _ => 0b1_1010_u8,
+    }
+}
+
+fn set_leds(leds: &mut [gpio::Output<'_>; 5], bits: u8) {
     leds[0].set_level((bits & 0b1_0000 > 0).into());
     leds[1].set_level((bits & 0b0_1000 > 0).into());
     leds[2].set_level((bits & 0b0_0100 > 0).into());
@@ -241,41 +716,125 @@ fn set_leds(leds: &mut [gpio::Output<'_>; 5], mode: DisplayMode) {
     leds[4].set_level((bits & 0b0_0001 > 0).into());
 }
 
-async fn handle_mode_time<R: RawMutex, Spi: spi::Instance, const N: usize>(
-    es: &mut EventSources<'_, R, N>,
+/// The time and date can not be shown until the receiver recovers.
+fn receiver_failed<R: RawMutex, const N: usize>(es: &EventSources<'_, R, N>) -> bool {
+    es.status.receiver.state == HealthState::Failed
+}
+
+/// Shown in place of the time and date until they are known.
+fn no_time_payload<R: RawMutex, const N: usize>(es: &EventSources<'_, R, N>) -> display::Payload {
+    if receiver_failed(es) {
+        return PATTERN_ERR_GNSS;
+    }
+    match es.status.antenna_fault() {
+        Some(AntennaStatus::Short) => PATTERN_ANT_SHORT,
+        Some(..) => PATTERN_ANT_OPEN,
+        None => PATTERN_NO_TIME,
+    }
+}
+
+fn time_mode_payload<R: RawMutex, const N: usize>(
+    es: &EventSources<'_, R, N>,
+    cfg: &Config,
+    datetime: NaiveDateTime,
+) -> display::Payload {
+    let t = datetime + cfg.time_zone();
+    match cfg.auto_rotate {
+        Some(a) if a.is_active(t.time()) => match a.mode {
+            DisplayMode::Date => date_to_display_payload(t.date()),
+            m @ (DisplayMode::Velocity | DisplayMode::MaxSpeed | DisplayMode::AverageSpeed) => {
+                speed_payload(es, cfg, m)
+            }
+            m @ (DisplayMode::Trip | DisplayMode::Odometer) => odometer_payload(es, cfg, m),
+            DisplayMode::Heading => heading_payload(es),
+            _ => time_to_display_payload(t.time()),
+        },
+        _ => time_to_display_payload(t.time()),
+    }
+}
+
+/// How long an edge on EXTINT is shown in the time mode
+const EXTERNAL_EVENT_DURATION: Duration = Duration::from_secs(2);
+
+/// Shows the current time, and shifts the one of the next pulse in advance if known.
+async fn show_time<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &EventSources<'_, R, N>,
     cfg: &Config,
     display: &mut Display<'_, Spi>,
 ) {
-    if let Some(datetime) = es.datetime {
-        let t = datetime + cfg.time_zone();
-        display.shift(&time_to_display_payload(t.time())).await;
+    if receiver_failed(es) {
+        display.shift(&PATTERN_ERR_GNSS).await;
+        display.refresh().await;
+    } else if let Some(datetime) = es.datetime {
+        display.shift(&time_mode_payload(es, cfg, datetime)).await;
         display.refresh().await;
         if let Some(datetime_next_pulse) = es.datetime_next_pulse {
-            let t_next = datetime_next_pulse + cfg.time_zone();
-            display.shift(&time_to_display_payload(t_next.time())).await;
+            display
+                .shift(&time_mode_payload(es, cfg, datetime_next_pulse))
+                .await;
         }
     } else {
-        display.shift(&PATTERN_NO_TIME).await;
+        display.shift(&no_time_payload(es)).await;
         display.refresh().await;
     }
+}
+
+// "hh.mm.ss", or the seconds of the last edge on EXTINT as "ss.ffff" for a while.
+async fn handle_mode_time<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    cfg: &Config,
+    display: &mut Display<'_, Spi>,
+) -> Exit {
+    show_time(es, cfg, display).await;
+    // until when an edge is shown instead of the time
+    let mut event_until: Option<Instant> = None;
 
     loop {
-        match es.wait().await {
-            Event::DateTimeAndVelocity { datetime, .. } => {
+        let expired = async {
+            match event_until {
+                Some(at) => Timer::at(at).await,
+                None => core::future::pending().await,
+            }
+        };
+        let event = match select(es.wait(), expired).await {
+            Either::First(event) => event,
+            Either::Second(()) => {
+                event_until = None;
+                show_time(es, cfg, display).await;
+                continue;
+            }
+        };
+        match event {
+            Event::DateTimeAndVelocity { datetime, .. } if event_until.is_none() => {
                 if es.datetime_next_pulse.is_none() && datetime.nanosecond() == 0 {
-                    let t = datetime + cfg.time_zone();
-                    display.shift(&time_to_display_payload(t.time())).await;
+                    display.shift(&time_mode_payload(es, cfg, datetime)).await;
                     display.refresh().await;
                 }
             }
-            Event::DateTimeNextPulse(datetime) => {
-                let t = datetime + cfg.time_zone();
-                display.shift(&time_to_display_payload(t.time())).await;
+            Event::DateTimeNextPulse(datetime) if event_until.is_none() => {
+                display.shift(&time_mode_payload(es, cfg, datetime)).await;
             }
-            Event::TimePulse => {
+            Event::TimePulse(..) if event_until.is_none() => {
                 display.refresh().await;
             }
-            Event::Sw3Pressed => return,
+            Event::ExternalEvent(e) => {
+                let t = e.datetime + cfg.time_zone();
+                display
+                    .shift(&external_event_to_display_payload(t.time()))
+                    .await;
+                display.refresh().await;
+                event_until = Some(Instant::now() + EXTERNAL_EVENT_DURATION);
+            }
+            Event::ReceiverHealthChanged if receiver_failed(es) => {
+                display.shift(&PATTERN_ERR_GNSS).await;
+                display.refresh().await;
+            }
+            Event::AntennaChanged if es.datetime.is_none() => {
+                display.shift(&no_time_payload(es)).await;
+                display.refresh().await;
+            }
+            Event::Sw3Pressed => return Exit::NextMode,
+            Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
             _ => (),
         }
     }
@@ -285,8 +844,11 @@ async fn handle_mode_date<R: RawMutex, Spi: spi::Instance, const N: usize>(
     es: &mut EventSources<'_, R, N>,
     cfg: &Config,
     display: &mut Display<'_, Spi>,
-) {
-    if let Some(datetime) = es.datetime {
+) -> Exit {
+    if receiver_failed(es) {
+        display.shift(&PATTERN_ERR_GNSS).await;
+        display.refresh().await;
+    } else if let Some(datetime) = es.datetime {
         let t = datetime + cfg.time_zone();
         display.shift(&date_to_display_payload(t.date())).await;
         display.refresh().await;
@@ -295,7 +857,7 @@ async fn handle_mode_date<R: RawMutex, Spi: spi::Instance, const N: usize>(
             display.shift(&date_to_display_payload(t_next.date())).await;
         }
     } else {
-        display.shift(&PATTERN_NO_TIME).await;
+        display.shift(&no_time_payload(es)).await;
         display.refresh().await;
     }
 
@@ -312,54 +874,240 @@ async fn handle_mode_date<R: RawMutex, Spi: spi::Instance, const N: usize>(
                 let t = datetime + cfg.time_zone();
                 display.shift(&date_to_display_payload(t.date())).await;
             }
-            Event::TimePulse => {
+            Event::TimePulse(..) => {
+                display.refresh().await;
+            }
+            Event::ReceiverHealthChanged if receiver_failed(es) => {
+                display.shift(&PATTERN_ERR_GNSS).await;
+                display.refresh().await;
+            }
+            Event::AntennaChanged if es.datetime.is_none() => {
+                display.shift(&no_time_payload(es)).await;
                 display.refresh().await;
             }
-            Event::Sw3Pressed => return,
+            Event::Sw3Pressed => return Exit::NextMode,
+            Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
             _ => (),
         }
     }
 }
 
-async fn handle_mode_velocity<R: RawMutex, Spi: spi::Instance, const N: usize>(
+/// The fraction is not interpolated for longer than this from the last pulse (or whole second).
+const MAX_INTERPOLATION: Duration = Duration::from_secs(2);
+
+// "mm.ss.t" refreshed at 10 Hz, or "mm.ss.hh" at 100 Hz. The fraction is counted by the clock of
+// the board from the last time pulse, or from the whole seconds of the solutions (late by their
+// latency) while there are no pulses.
+//  - SW4: switch between the tenths and hundredths
+async fn handle_mode_sub_second<R: RawMutex, Spi: spi::Instance, const N: usize>(
     es: &mut EventSources<'_, R, N>,
-    _cfg: &Config,
+    cfg: &Config,
     display: &mut Display<'_, Spi>,
-) {
-    if let Some(ground_speed_meter_hour) = es.ground_speed_meter_hour {
-        display
-            .shift(&u32_to_display_payload(ground_speed_meter_hour))
-            .await;
+) -> Exit {
+    let mut hundredths = false;
+    let mut next_pulse = es.datetime_next_pulse;
+    let mut last_pulse: Option<Instant> = None;
+    // a whole second, and when it began
+    let mut anchor: Option<(NaiveDateTime, Instant)> = None;
+
+    loop {
+        let period = Duration::from_millis(if hundredths { 10 } else { 100 });
+        // the next frame on the grid of the period from the anchor, shifted in advance
+        let frame = anchor
+            .filter(|_| !receiver_failed(es))
+            .and_then(|(datetime, at)| {
+                let n = at.elapsed().as_ticks() / period.as_ticks() + 1;
+                let elapsed = Duration::from_ticks(n * period.as_ticks());
+                (elapsed <= MAX_INTERPOLATION).then(|| {
+                    let micros = TimeDelta::microseconds(elapsed.as_micros() as i64);
+                    (datetime + micros, at + elapsed)
+                })
+            });
+        match frame {
+            Some((datetime, _)) => {
+                let t = datetime + cfg.time_zone();
+                display
+                    .shift(&sub_second_to_display_payload(t.time(), hundredths))
+                    .await;
+            }
+            None if receiver_failed(es) => {
+                display.shift(&PATTERN_ERR_GNSS).await;
+                display.refresh().await;
+            }
+            None => {
+                display.shift(&no_time_payload(es)).await;
+                display.refresh().await;
+            }
+        }
+
+        let due = async {
+            match frame {
+                Some((_, at)) => Timer::at(at).await,
+                None => core::future::pending().await,
+            }
+        };
+        match select(es.wait(), due).await {
+            Either::Second(()) => display.refresh().await,
+            Either::First(Event::TimePulse(at)) => {
+                if let Some(datetime) = next_pulse.take() {
+                    last_pulse = Some(at);
+                    anchor = Some((datetime, at));
+                    // unless the grid was off, e.g. on the first pulse
+                    if frame.is_some_and(|(d, _)| d == datetime) {
+                        display.refresh().await;
+                    }
+                }
+            }
+            Either::First(Event::DateTimeNextPulse(datetime)) => next_pulse = Some(datetime),
+            Either::First(Event::DateTimeAndVelocity { datetime, .. }) => {
+                if datetime.nanosecond() == 0
+                    && last_pulse.is_none_or(|at| at.elapsed() > MAX_INTERPOLATION)
+                {
+                    anchor = Some((datetime, Instant::now()));
+                }
+            }
+            Either::First(Event::Sw3Pressed) => return Exit::NextMode,
+            Either::First(Event::Sw4Pressed) => hundredths = !hundredths,
+            Either::First(Event::ConfigChanged(c)) => return Exit::ConfigChanged(c),
+            Either::First(..) => (),
+        }
+    }
+}
+
+fn speed_payload<R: RawMutex, const N: usize>(
+    es: &EventSources<'_, R, N>,
+    cfg: &Config,
+    mode: DisplayMode,
+) -> display::Payload {
+    let speed_mm_s = match mode {
+        DisplayMode::MaxSpeed => Some(es.status.speed_stats.max_mm_s()),
+        DisplayMode::AverageSpeed => Some(es.status.speed_stats.average_mm_s()),
+        _ => es.status.ground_speed_mm_s,
+    };
+    speed_mm_s.map_or(PATTERN_NO_TIME, |s| {
+        u32_to_display_payload(cfg.speed_unit.from_mm_s(s))
+    })
+}
+
+// Velocity, MaxSpeed and AverageSpeed
+//  - SW5: reset the statistics (MaxSpeed and AverageSpeed only)
+async fn handle_mode_speed<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    cfg: &Config,
+    display: &mut Display<'_, Spi>,
+    mode: DisplayMode,
+) -> Exit {
+    display.shift(&speed_payload(es, cfg, mode)).await;
+    display.refresh().await;
+
+    loop {
+        match es.wait().await {
+            Event::DateTimeAndVelocity { .. } => (),
+            Event::Sw5Pressed if mode != DisplayMode::Velocity => es.reset_speed_stats(),
+            Event::Sw3Pressed => return Exit::NextMode,
+            Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
+            _ => continue,
+        }
+        display.shift(&speed_payload(es, cfg, mode)).await;
         display.refresh().await;
+    }
+}
+
+fn odometer_payload<R: RawMutex, const N: usize>(
+    es: &EventSources<'_, R, N>,
+    cfg: &Config,
+    mode: DisplayMode,
+) -> display::Payload {
+    let record = es.status.odometer;
+    if mode == DisplayMode::Trip {
+        u32_to_display_payload(cfg.speed_unit.distance_from_m(record.trip_m))
     } else {
-        display.shift(&PATTERN_NO_TIME).await;
+        // whole units, up to 999999
+        let total = cfg.speed_unit.distance_from_m(record.total_m) / 1000;
+        let mut arr = [0; 6];
+        for (i, c) in arr.iter_mut().enumerate() {
+            let d = total / 10_u32.pow(i as u32);
+            if d > 0 || i == 0 {
+                *c = TABLE[d as usize % 10];
+            }
+        }
+        display::Payload(arr)
+    }
+}
+
+// Trip and Odometer
+//  - SW5: reset the trip (Trip only)
+async fn handle_mode_odometer<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    cfg: &Config,
+    display: &mut Display<'_, Spi>,
+    mode: DisplayMode,
+) -> Exit {
+    display.shift(&odometer_payload(es, cfg, mode)).await;
+    display.refresh().await;
+
+    loop {
+        match es.wait().await {
+            Event::DateTimeAndVelocity { .. } => (),
+            Event::Sw5Pressed if mode == DisplayMode::Trip => es.reset_trip(),
+            Event::Sw3Pressed => return Exit::NextMode,
+            Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
+            _ => continue,
+        }
+        display.shift(&odometer_payload(es, cfg, mode)).await;
         display.refresh().await;
     }
+}
+
+// "ddd.dd" degrees clockwise from the north, or "---.--" while stationary
+fn heading_payload<R: RawMutex, const N: usize>(es: &EventSources<'_, R, N>) -> display::Payload {
+    let Some(heading) = es.status.heading_1e5_deg else {
+        return display::Payload([
+            0b10000000_u8,
+            0b10000000_u8,
+            0b10100000_u8,
+            0b10000000_u8,
+            0b10000000_u8,
+            0,
+        ]);
+    };
+    let h = (heading.rem_euclid(360_00000) / 1000) as usize;
+    display::Payload([
+        TABLE[h % 10],
+        TABLE[h / 10 % 10],
+        TABLE[h / 100 % 10] | MASK_DP,
+        TABLE[h / 1000 % 10],
+        TABLE[h / 10000 % 10],
+        0,
+    ])
+}
+
+async fn handle_mode_heading<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    display: &mut Display<'_, Spi>,
+) -> Exit {
+    display.shift(&heading_payload(es)).await;
+    display.refresh().await;
 
     loop {
         match es.wait().await {
-            Event::DateTimeAndVelocity {
-                ground_speed_meter_hour,
-                ..
-            } => {
-                display
-                    .shift(&u32_to_display_payload(ground_speed_meter_hour))
-                    .await;
-                display.refresh().await;
-            }
-            Event::Sw3Pressed => return,
-            _ => (),
+            Event::DateTimeAndVelocity { .. } => (),
+            Event::Sw3Pressed => return Exit::NextMode,
+            Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
+            _ => continue,
         }
+        display.shift(&heading_payload(es)).await;
+        display.refresh().await;
     }
 }
 
 async fn handle_mode_config_time_zone<R: RawMutex, Spi: spi::Instance, const N: usize>(
     es: &mut EventSources<'_, R, N>,
-    cfg: &Config,
+    cfg: &mut Config,
     display: &mut Display<'_, Spi>,
-) -> i32 {
+) -> Exit {
     let mut time_zone_secs = cfg.time_zone_secs;
-    'outer: loop {
+    loop {
         let hour = time_zone_secs / 60 / 60;
         let min = (time_zone_secs / 60 % 60).unsigned_abs();
         let payload = display::Payload([
@@ -377,14 +1125,296 @@ async fn handle_mode_config_time_zone<R: RawMutex, Spi: spi::Instance, const N:
         display.shift(&payload).await;
         display.refresh().await;
 
-        time_zone_secs = 'inner: loop {
+        time_zone_secs = loop {
+            match es.wait().await {
+                Event::Sw3Pressed => {
+                    cfg.time_zone_secs = time_zone_secs;
+                    return Exit::NextMode;
+                }
+                // `FixedOffset` excludes ±24 hours
+                Event::Sw4Pressed => break (time_zone_secs + 30 * 60).min(23 * 60 * 60 + 30 * 60),
+                Event::Sw5Pressed => break (time_zone_secs - 30 * 60).max(-23 * 60 * 60 - 30 * 60),
+                Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
+                _ => (),
+            }
+        };
+    }
+}
+
+// "n  on" / "n oFF", where `n` is the position of the mode in `DisplayMode::VIEWS` (same as LEDs)
+//  - SW4: select the next mode
+//  - SW5: add/remove the selected mode to/from the rotation
+async fn handle_mode_config_rotation<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    cfg: &mut Config,
+    display: &mut Display<'_, Spi>,
+) -> Exit {
+    let mut rotation = cfg.rotation;
+    let mut i = 0;
+    loop {
+        let payload = display::Payload(if rotation.contains(DisplayMode::VIEWS[i]) {
+            [CHAR_N, CHAR_O, 0, 0, 0, TABLE[i + 1]]
+        } else {
+            [CHAR_F, CHAR_F, CHAR_O, 0, 0, TABLE[i + 1]]
+        });
+        display.shift(&payload).await;
+        display.refresh().await;
+
+        loop {
+            match es.wait().await {
+                Event::Sw3Pressed => {
+                    cfg.rotation = rotation;
+                    return Exit::NextMode;
+                }
+                Event::Sw4Pressed => {
+                    i = (i + 1) % DisplayMode::VIEWS.len();
+                    break;
+                }
+                Event::Sw5Pressed => {
+                    rotation.toggle(DisplayMode::VIEWS[i]);
+                    break;
+                }
+                Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
+                _ => (),
+            }
+        }
+    }
+}
+
+// "oFF" or "n dd.ppp", where `n` is the position of the mode in `DisplayMode::VIEWS`, `dd` is the
+// duration and `ppp` is the period in seconds
+//  - SW4/SW5: select the next/previous preset
+async fn handle_mode_config_auto_rotate<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    cfg: &mut Config,
+    display: &mut Display<'_, Spi>,
+) -> Exit {
+    let presets = AutoRotate::PRESETS;
+    let mut i = presets
+        .iter()
+        .position(|p| *p == cfg.auto_rotate)
+        .unwrap_or(0);
+    let mut auto_rotate = cfg.auto_rotate;
+    loop {
+        let payload = display::Payload(match auto_rotate {
+            Some(a) => {
+                let n = DisplayMode::VIEWS
+                    .iter()
+                    .position(|m| *m == a.mode)
+                    .unwrap_or(0);
+                let d = a.duration_secs as usize;
+                let p = a.period_secs as usize;
+                [
+                    TABLE[p % 10],
+                    if p >= 10 { TABLE[p / 10 % 10] } else { 0 },
+                    if p >= 100 { TABLE[p / 100 % 10] } else { 0 },
+                    TABLE[d % 10] | MASK_DP,
+                    if d >= 10 { TABLE[d / 10 % 10] } else { 0 },
+                    TABLE[n + 1],
+                ]
+            }
+            None => [CHAR_F, CHAR_F, CHAR_O, 0, 0, 0],
+        });
+        display.shift(&payload).await;
+        display.refresh().await;
+
+        loop {
+            match es.wait().await {
+                Event::Sw3Pressed => {
+                    cfg.auto_rotate = auto_rotate;
+                    return Exit::NextMode;
+                }
+                Event::Sw4Pressed => {
+                    i = (i + 1) % presets.len();
+                    break;
+                }
+                Event::Sw5Pressed => {
+                    i = (i + presets.len() - 1) % presets.len();
+                    break;
+                }
+                Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
+                _ => (),
+            }
+        }
+        auto_rotate = presets[i];
+    }
+}
+
+// "kPh" (km/h), "nPh" (mph), "kn" (knots) or "nPS" (m/s)
+//  - SW4/SW5: select the next/previous unit
+async fn handle_mode_config_speed_unit<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    cfg: &mut Config,
+    display: &mut Display<'_, Spi>,
+) -> Exit {
+    let units = SpeedUnit::ALL;
+    let mut i = units.iter().position(|u| *u == cfg.speed_unit).unwrap_or(0);
+    loop {
+        let payload = display::Payload(match units[i] {
+            SpeedUnit::KilometersPerHour => [CHAR_H, CHAR_P, CHAR_K, 0, 0, 0],
+            SpeedUnit::MilesPerHour => [CHAR_H, CHAR_P, CHAR_N, 0, 0, 0],
+            SpeedUnit::Knots => [CHAR_N, CHAR_K, 0, 0, 0, 0],
+            SpeedUnit::MetersPerSecond => [CHAR_S, CHAR_P, CHAR_N, 0, 0, 0],
+        });
+        display.shift(&payload).await;
+        display.refresh().await;
+
+        i = loop {
+            match es.wait().await {
+                Event::Sw3Pressed => {
+                    cfg.speed_unit = units[i];
+                    return Exit::NextMode;
+                }
+                Event::Sw4Pressed => break (i + 1) % units.len(),
+                Event::Sw5Pressed => break (i + units.len() - 1) % units.len(),
+                Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
+                _ => (),
+            }
+        };
+    }
+}
+
+// "GECrJS" for GPS, Galileo, BeiDou, GLONASS, QZSS and SBAS, "-" in place of those disabled, with
+// the DP after the selected one
+//  - SW4: select the next constellation
+//  - SW5: enable/disable the selected constellation, unless none of the major ones would be left
+async fn handle_mode_config_constellations<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    cfg: &mut Config,
+    display: &mut Display<'_, Spi>,
+) -> Exit {
+    const LETTERS: [char; 6] = ['G', 'E', 'C', 'r', 'J', 'S'];
+    let mut constellations = cfg.constellations;
+    let mut i = 0;
+    loop {
+        let payload = display::Payload(core::array::from_fn(|j| {
+            let k = LETTERS.len() - 1 - j;
+            let c = Constellation::ALL[k];
+            let segments = glyph(if constellations.contains(c) {
+                LETTERS[k]
+            } else {
+                '-'
+            });
+            if k == i { segments | MASK_DP } else { segments }
+        }));
+        display.shift(&payload).await;
+        display.refresh().await;
+
+        loop {
             match es.wait().await {
-                Event::Sw3Pressed => break 'outer time_zone_secs,
-                Event::Sw4Pressed => break 'inner (time_zone_secs + 30 * 60).min(24 * 60 * 60),
-                Event::Sw5Pressed => break 'inner (time_zone_secs - 30 * 60).max(-24 * 60 * 60),
+                Event::Sw3Pressed => {
+                    cfg.constellations = constellations;
+                    return Exit::NextMode;
+                }
+                Event::Sw4Pressed => {
+                    i = (i + 1) % Constellation::ALL.len();
+                    break;
+                }
+                Event::Sw5Pressed => {
+                    let c = Constellation::ALL[i];
+                    let mut toggled = constellations;
+                    toggled.set(c, !constellations.contains(c));
+                    if toggled.is_valid() {
+                        constellations = toggled;
+                        break;
+                    }
+                }
+                Event::ConfigChanged(c) => return Exit::ConfigChanged(c),
                 _ => (),
             }
+        }
+    }
+}
+
+/// Text of the info mode, "-" until the version has been polled.
+fn info_segments() -> Segments<256> {
+    let mut text = Segments(heapless::Vec::new());
+    // the rest is cut off if too long
+    let _ = match max_m10s::version() {
+        Some(version) => {
+            let version = version.decode();
+            let _ = write!(text, "{}  hw {}", version.sw_version, version.hw_version);
+            version
+                .extensions()
+                .try_for_each(|e| write!(text, "  {}", e))
+        }
+        None => write!(text, "-"),
+    };
+    text
+}
+
+// the firmware version of the receiver (UBX-MON-VER), scrolling from the right
+//  - SW4: scroll from the beginning
+async fn handle_mode_info<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    display: &mut Display<'_, Spi>,
+) -> Exit {
+    const WIDTH: usize = 6;
+    let mut ticker = Ticker::every(Duration::from_millis(300));
+    let mut pos = 0;
+    loop {
+        let text = info_segments();
+        // preceded by the blank display
+        let payload = display::Payload(core::array::from_fn(|i| {
+            (pos + WIDTH - 1 - i)
+                .checked_sub(WIDTH)
+                .and_then(|j| text.0.get(j).copied())
+                .unwrap_or(0)
+        }));
+        display.shift(&payload).await;
+        display.refresh().await;
+
+        match select(es.wait(), ticker.next()).await {
+            Either::First(Event::Sw3Pressed) => return Exit::NextMode,
+            Either::First(Event::Sw4Pressed) => pos = 0,
+            Either::First(Event::ConfigChanged(c)) => return Exit::ConfigChanged(c),
+            Either::First(..) => (),
+            Either::Second(()) => pos = (pos + 1) % (text.0.len() + WIDTH),
+        }
+    }
+}
+
+// "t nnnnn": the accuracy of the time estimated by the receiver (tAcc of UBX-NAV-TIMEUTC), or of
+// the intervals between the time pulses on the clock of the board over the last minute,
+// "J nnnnn": the jitter (peak to peak) or "A nnnnn": the Allan deviation at 1 s, all in ns
+//  - SW4: select the next value
+//  - SW5: start the statistics over
+async fn handle_mode_timing<R: RawMutex, Spi: spi::Instance, const N: usize>(
+    es: &mut EventSources<'_, R, N>,
+    display: &mut Display<'_, Spi>,
+) -> Exit {
+    const LABELS: [char; 3] = ['t', 'J', 'A'];
+    let mut ticker = Ticker::every(Duration::from_secs(1));
+    let mut i = 0;
+    loop {
+        let report = timing::report();
+        let value = match i {
+            0 => report.utc_t_acc_ns,
+            1 => report.pulse_error.map(|s| s.max.abs_diff(s.min)),
+            _ => report.pulse_adev,
+        };
+        let mut text = Segments::<6>(heapless::Vec::new());
+        // "-" until known, capped to fit
+        let _ = match value {
+            Some(v) => write!(text, "{}{:>5}", LABELS[i], v.min(99_999)),
+            None => write!(text, "{}    -", LABELS[i]),
         };
+        let payload = display::Payload(core::array::from_fn(|j| {
+            text.0
+                .get(text.0.len().wrapping_sub(1 + j))
+                .copied()
+                .unwrap_or(0)
+        }));
+        display.shift(&payload).await;
+        display.refresh().await;
+
+        match select(es.wait(), ticker.next()).await {
+            Either::First(Event::Sw3Pressed) => return Exit::NextMode,
+            Either::First(Event::Sw4Pressed) => i = (i + 1) % LABELS.len(),
+            Either::First(Event::Sw5Pressed) => timing::clear(),
+            Either::First(Event::ConfigChanged(c)) => return Exit::ConfigChanged(c),
+            Either::First(..) | Either::Second(()) => (),
+        }
     }
 }
 
@@ -392,6 +1422,153 @@ async fn handle_mode_config_time_zone<R: RawMutex, Spi: spi::Instance, const N:
 async fn task_max_m10s(
     mut max_m10s: MaxM10s<'static, I2C1>,
     channel: &'static MaxM10sEventChannel,
+    commands: &'static MaxM10sCommandSignal,
+    done: &'static MaxM10sDoneSignal,
+    config: DynReceiver<'static, Config>,
+    assist_now: &'static AssistNowPartition,
+    heartbeat: &'static Heartbeat,
 ) {
-    max_m10s.run(channel.sender()).await;
+    max_m10s
+        .run(
+            channel.sender(),
+            commands,
+            done,
+            config,
+            assist_now,
+            heartbeat,
+        )
+        .await;
+}
+
+/// Shows the mode, alternating with the code of the antenna fault if any.
+#[embassy_executor::task]
+async fn task_leds(
+    mut leds: [gpio::Output<'static>; 5],
+    mut modes: DynReceiver<'static, DisplayMode>,
+    mut status: DynAnonReceiver<'static, Status>,
+    heartbeat: &'static Heartbeat,
+) -> ! {
+    let mut mode = heartbeat.idle(modes.get()).await;
+    let mut ticker = Ticker::every(Duration::from_millis(500));
+    let mut blink = false;
+    loop {
+        let fault = status.try_get().and_then(|s| s.antenna_fault());
+        blink = !blink && fault.is_some();
+        let bits = match fault {
+            Some(fault) if blink => antenna_fault_leds(fault),
+            _ => mode_leds(mode),
+        };
+        set_leds(&mut leds, bits);
+        if let Either::First(m) = heartbeat.idle(select(modes.changed(), ticker.next())).await {
+            mode = m;
+        }
+    }
+}
+
+/// Timestamps the rising edges of the time pulse as soon as the executor gets to them, rather than
+/// when the main loop is done with the display.
+#[embassy_executor::task]
+async fn task_pps(
+    mut pps: gpio::Input<'static>,
+    pulses: &'static PulseSignal,
+    heartbeat: &'static Heartbeat,
+) -> ! {
+    loop {
+        heartbeat.idle(pps.wait_for_rising_edge()).await;
+        let at = Instant::now();
+        timing::record_pulse(at);
+        pulses.signal(at);
+    }
+}
+
+#[embassy_executor::task]
+async fn task_usb(
+    mut usb: embassy_usb::UsbDevice<'static, usb::Driver<'static, USB>>,
+    heartbeat: &'static Heartbeat,
+) -> ! {
+    heartbeat.idle(usb.run()).await
+}
+
+#[embassy_executor::task]
+async fn task_shell(mut shell: Shell<'static, usb::Driver<'static, USB>>) {
+    shell.run().await;
+}
+
+#[embassy_executor::task]
+async fn task_config_store(
+    mut writer: BackgroundWriter<'static, ThreadModeRawMutex, Config, FlashPartition, 512>,
+) {
+    writer.run().await;
+}
+
+#[embassy_executor::task]
+async fn task_odometer_store(
+    mut writer: BackgroundWriter<'static, ThreadModeRawMutex, OdometerRecord, FlashPartition, 64>,
+) {
+    writer.run().await;
+}
+
+#[embassy_executor::task]
+async fn task_position_store(
+    mut writer: BackgroundWriter<'static, ThreadModeRawMutex, PositionRecord, FlashPartition, 64>,
+) {
+    writer.run().await;
+}
+
+#[embassy_executor::task]
+async fn task_factory_reset(
+    flash: &'static SharedFlash,
+    requests: &'static FactoryResetChannel,
+    commands: &'static MaxM10sCommandSignal,
+    done: &'static MaxM10sDoneSignal,
+) {
+    requests.receive().await;
+    defmt::info!("factory reset");
+
+    done.reset();
+    commands.signal(MaxM10sCommand::FactoryReset);
+    match with_timeout(Duration::from_secs(5), done.wait()).await {
+        Ok(true) => (),
+        Ok(false) | Err(..) => defmt::warn!("failed to reset the receiver"),
+    }
+
+    // the background writers do not run until the reboot
+    erase_config(flash);
+    cortex_m::peripheral::SCB::sys_reset();
+}
+
+#[embassy_executor::task]
+async fn task_fault_log(
+    fault_log: &'static FaultLogPartition,
+    time_pulse: embassy_sync::watch::DynReceiver<'static, ()>,
+    heartbeat: &'static Heartbeat,
+) {
+    fault_log.run(time_pulse, heartbeat).await;
+}
+
+#[embassy_executor::task]
+async fn task_supervisor(
+    mut supervisor: Supervisor<'static>,
+    fault_log: &'static FaultLogPartition,
+) {
+    let stalled = supervisor.run().await;
+    let _ = fault_log.append(&fault_log::Entry::now(Fault::TaskStalled {
+        task: stalled.name(),
+    }));
+    supervisor.reboot();
+}
+
+/// Reboots rather than halting as `panic_probe` does, nobody attaches a debugger to a clock on
+/// the wall. The panic is written to the fault log on the next boot.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    static PANICKED: AtomicBool = AtomicBool::new(false);
+
+    cortex_m::interrupt::disable();
+    if !PANICKED.load(Ordering::Relaxed) {
+        PANICKED.store(true, Ordering::Relaxed);
+        defmt::error!("{}", defmt::Display2Format(info));
+        fault_log::save_panic(info);
+    }
+    cortex_m::peripheral::SCB::sys_reset()
 }
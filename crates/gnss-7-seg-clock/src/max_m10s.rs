@@ -1,15 +1,292 @@
+use core::cell::Cell;
+
 use embassy_futures::select::*;
 use embassy_rp::{Peri, gpio, i2c, interrupt::typelevel::Binding, uart};
-use embassy_sync::{blocking_mutex::raw::RawMutex, channel::Sender};
+use embassy_sync::{
+    blocking_mutex::{
+        Mutex,
+        raw::{CriticalSectionRawMutex, RawMutex},
+    },
+    channel::Sender,
+    signal::Signal,
+    watch::DynReceiver,
+};
 use embassy_time::{Duration, Instant, Timer};
 use embedded_io_async::Read;
 
 use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
 
-use ubx::{UbxFrame, UbxStream};
+use ubx::{
+    UbxFrame, UbxStream,
+    ack::Ack,
+    cfg::{
+        LAYER_BBR, LAYER_FLASH, LAYER_RAM, NAV_BBR_COLD_START, RESET_MODE_SOFTWARE, Rst, ValDel,
+        ValGet, ValSet,
+    },
+    mga::{Ack as MgaAck, Ano, IniPosLlh, IniTimeUtc},
+    mon::{JammingState, MonRf, MonVer, RfBlock},
+    nav::{NavOdo, NavPvt, NavTimeUtc},
+    sec::{SecSig, SpoofingState},
+    tim::{TimTm2, TimTp, TimeBase},
+};
+
+use misc::backoff::Backoff;
+
+use crate::assist_now;
+use crate::config::{
+    Config, Constellation, Constellations, ExtInt, PowerSave, Rate, ReceiverStorage, TimePulse,
+};
+use crate::fault_log::{self, Fault, ReceiverFault};
+use crate::position::Position;
+use crate::timing;
+use crate::watchdog::Heartbeat;
 
 const MAX_M10S_I2C_ADDRESS: u16 = 0x42;
 
+/// How long the receiver may take to acknowledge a request.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Assumed for the 12 MHz crystal of the board, including its stability over the temperature, to
+/// estimate the drift of the local clock since the last fix.
+pub const CLOCK_TOLERANCE_PPM: u32 = 40;
+
+/// Accuracy claimed for the last position given to the receiver, which may have been carried far
+/// while switched off.
+const POSITION_ACCURACY_CM: u32 = 100 * 1000 * 100;
+
+/// `valid` of UBX-NAV-PVT: validDate and validTime
+const VALID_DATE_TIME: u8 = 0x03;
+
+/// Written by `do_setup`, one UBX-CFG-VALSET per group so that a rejected one can
+/// be told apart. The keys are deleted from BBR and flash by a factory reset.
+///
+/// The groups which need a newer firmware than SPG 5.00, the first one of u-blox M10, are
+/// skipped on older receivers.
+const SETUP: [Group; 6] = [
+    Group {
+        name: "protocols",
+        protver: None,
+        items: &[
+            // in the same VALSET as the output turned on below, so that UART1 never runs at
+            // the default 9600 baud meanwhile
+            (0x40520001, 115200), // CFG-UART1-BAUDRATE
+            (0x10710001, 1),      // CFG-I2CINPROT-UBX
+            (0x10710002, 0),      // CFG-I2CINPROT-NMEA
+            (0x10720001, 1),      // CFG-I2COUTPROT-UBX
+            (0x10720002, 0),      // CFG-I2COUTPROT-NMEA
+            (0x10730001, 0),      // CFG-UART1INPROT-UBX
+            (0x10730002, 0),      // CFG-UART1INPROT-NMEA
+            (0x10740001, 1),      // CFG-UART1OUTPROT-UBX
+            (0x10740002, 0),      // CFG-UART1OUTPROT-NMEA
+        ],
+    },
+    Group {
+        name: "messages",
+        protver: None,
+        items: &[
+            (0x20910007, 1), // CFG-MSGOUT-UBX_NAV_PVT_UART1
+            (0x2091017e, 1), // CFG-MSGOUT-UBX_TIM_TP_UART1
+            (0x2091005c, 1), // CFG-MSGOUT-UBX_NAV_TIMEUTC_UART1
+            (0x2091007f, 1), // CFG-MSGOUT-UBX_NAV_ODO_UART1
+            (0x2091035a, 5), // CFG-MSGOUT-UBX_MON_RF_UART1 (=every 5 solutions)
+        ],
+    },
+    Group {
+        name: "security",
+        protver: Some(PROTVER_SPG_5_10),
+        items: &[
+            (0x1041000d, 1), // CFG-ITFM-ENABLE (=true, jamming detection)
+            (0x20910635, 1), // CFG-MSGOUT-UBX_SEC_SIG_UART1 (=every solution)
+        ],
+    },
+    Group {
+        name: "antenna",
+        protver: Some(PROTVER_SPG_5_10),
+        items: &[
+            (0x20a30054, 1), // CFG-HW-ANT_SUP_ENGINE (=1=MADC, built-in supervisor)
+            (0x10a3002f, 1), // CFG-HW-ANT_CFG_SHORTDET (=true)
+            (0x10a30031, 1), // CFG-HW-ANT_CFG_OPENDET (=true)
+            (0x10a30035, 1), // CFG-HW-ANT_CFG_RECOVER (=true)
+        ],
+    },
+    Group {
+        name: "odometer",
+        protver: None,
+        items: &[
+            (0x10220001, 1), // CFG-ODO-USE_ODO (=true)
+        ],
+    },
+    Group {
+        name: "assistance",
+        protver: None,
+        items: &[
+            (0x10110025, 1), // CFG-NAVSPG-ACKAIDING (=true, UBX-MGA-ACK for flow control)
+        ],
+    },
+];
+
+/// Protocol version of the receiver (PROTVER of UBX-MON-VER), major and minor
+type ProtVer = (u8, u8);
+
+/// Keys written by one UBX-CFG-VALSET
+struct Group {
+    name: &'static str,
+    /// Needed by the keys, if newer than SPG 5.00
+    protver: Option<ProtVer>,
+    items: &'static [(u32, u64)],
+}
+
+/// Of SPG 5.10, which added UBX-SEC-SIG and the built-in antenna supervisor (CFG-HW-ANT_*)
+const PROTVER_SPG_5_10: ProtVer = (34, 10);
+
+/// Most keys written by one UBX-CFG-VALSET, i.e. in a group of `SETUP` or the like
+const MAX_GROUP_KEYS: usize = 16;
+
+/// CFG-SIGNAL-*: the key to enable each constellation, followed by those of its signals.
+const SIGNALS: [(Constellation, &[u32]); 6] = [
+    (
+        Constellation::Gps,
+        &[
+            0x1031001f, // CFG-SIGNAL-GPS_ENA
+            0x10310001, // CFG-SIGNAL-GPS_L1CA_ENA
+        ],
+    ),
+    (
+        Constellation::Galileo,
+        &[
+            0x10310021, // CFG-SIGNAL-GAL_ENA
+            0x10310007, // CFG-SIGNAL-GAL_E1_ENA
+        ],
+    ),
+    (
+        Constellation::Beidou,
+        &[
+            0x10310022, // CFG-SIGNAL-BDS_ENA
+            0x1031000d, // CFG-SIGNAL-BDS_B1_ENA
+        ],
+    ),
+    (
+        Constellation::Glonass,
+        &[
+            0x10310025, // CFG-SIGNAL-GLO_ENA
+            0x10310018, // CFG-SIGNAL-GLO_L1_ENA
+        ],
+    ),
+    (
+        Constellation::Qzss,
+        &[
+            0x10310024, // CFG-SIGNAL-QZSS_ENA
+            0x10310012, // CFG-SIGNAL-QZSS_L1CA_ENA
+            0x10310014, // CFG-SIGNAL-QZSS_L1S_ENA
+        ],
+    ),
+    (
+        Constellation::Sbas,
+        &[
+            0x10310020, // CFG-SIGNAL-SBAS_ENA
+            0x10310005, // CFG-SIGNAL-SBAS_L1CA_ENA
+        ],
+    ),
+];
+
+fn signal_items(constellations: &Constellations) -> heapless::Vec<(u32, u64), 16> {
+    let mut items = heapless::Vec::new();
+    for (c, keys) in SIGNALS {
+        for key in keys {
+            defmt::unwrap!(items.push((*key, constellations.contains(c).into())));
+        }
+    }
+    items
+}
+
+/// CFG-RATE-*
+fn rate_items(rate: &Rate) -> [(u32, u64); 2] {
+    [
+        (0x30210001, rate.measurement_period_ms().into()), // CFG-RATE-MEAS
+        (0x30210002, rate.navigation_ratio().into()),      // CFG-RATE-NAV
+    ]
+}
+
+/// CFG-PM-*
+fn power_save_items(power_save: &PowerSave) -> [(u32, u64); 4] {
+    let (mode, period_secs) = match power_save {
+        PowerSave::Off => (0, 0),
+        PowerSave::OnOff { period_secs } => (1, *period_secs),
+        PowerSave::CyclicTracking => (2, 0),
+    };
+    [
+        (0x20d00001, mode),               // CFG-PM-OPERATEMODE (=0=FULL, 1=PSMOO, 2=PSMCT)
+        (0x40d00002, period_secs.into()), // CFG-PM-POSUPDATEPERIOD
+        (0x10d00009, 1),                  // CFG-PM-WAITTIMEFIX (=true, the time is what counts)
+        (0x10d0000a, 1),                  // CFG-PM-UPDATEEPH (=true)
+    ]
+}
+
+/// CFG-TXREADY-* and CFG-MSGOUT-UBX_TIM_TM2_UART1: EXTINT is either TX_READY for the I2C, or
+/// timestamps the edges of the signal wired to it.
+fn ext_int_items(ext_int: &ExtInt) -> [(u32, u64); 6] {
+    let (tx_ready, tm2) = match ext_int {
+        ExtInt::TxReady => (1, 0),
+        ExtInt::Timestamp => (0, 1),
+    };
+    [
+        (0x10a20001, tx_ready), // CFG-TXREADY-ENABLED
+        (0x10a20002, 1),        // CFG-TXREADY-POLARITY (=true=low-active)
+        (0x20a20003, 5),        // CFG-TXREADY-PIN (=5=EXTINT)
+        (0x30a20004, 1),        // CFG-TXREADY-THRESHOLD (=8/8)
+        (0x20a20005, 0),        // CFG-TXREADY-INTERFACE (=0=I2C)
+        (0x20910179, tm2),      // CFG-MSGOUT-UBX_TIM_TM2_UART1
+    ]
+}
+
+/// CFG-TP-*: a rising edge of 100 ms on each second of UTC, only while locked to GNSS, as
+/// `DateTimeNextPulse` expects. The delays are compensated by the receiver.
+fn time_pulse_items(time_pulse: &TimePulse) -> [(u32, u64); 14] {
+    // the signed values are truncated to the size of the key
+    let cable_delay_ns = u64::from(time_pulse.cable_delay_ns as u16);
+    let user_delay_ns = u64::from(time_pulse.user_delay_ns as u32);
+    [
+        (0x20050023, 0),              // CFG-TP-PULSE_DEF (=0=period)
+        (0x20050030, 1),              // CFG-TP-PULSE_LENGTH_DEF (=1=length)
+        (0x30050001, cable_delay_ns), // CFG-TP-ANT_CABLEDELAY
+        (0x40050002, 1_000_000),      // CFG-TP-PERIOD_TP1 (us)
+        (0x40050003, 1_000_000),      // CFG-TP-PERIOD_LOCK_TP1 (us)
+        (0x40050004, 0),              // CFG-TP-LEN_TP1 (=no pulse until locked)
+        (0x40050005, 100_000),        // CFG-TP-LEN_LOCK_TP1 (us)
+        (0x40050006, user_delay_ns),  // CFG-TP-USER_DELAY_TP1
+        (0x10050007, 1),              // CFG-TP-TP1_ENA
+        (0x10050008, 1),              // CFG-TP-SYNC_GNSS_TP1
+        (0x10050009, 1),              // CFG-TP-USE_LOCKED_TP1
+        (0x1005000a, 1),              // CFG-TP-ALIGN_TO_TOW_TP1
+        (0x1005000b, 1),              // CFG-TP-POL_TP1 (=rising edge)
+        (0x2005000c, 0),              // CFG-TP-TIMEGRID_TP1 (=0=UTC)
+    ]
+}
+
+/// Keys written by the clock, all deleted by one UBX-CFG-VALDEL in `delete_stored`
+const STORED_KEYS: usize = {
+    let mut n = 0;
+    let mut i = 0;
+    while i < SETUP.len() {
+        n += SETUP[i].items.len();
+        i += 1;
+    }
+    let mut i = 0;
+    while i < SIGNALS.len() {
+        n += SIGNALS[i].1.len();
+        i += 1;
+    }
+    n + item_count(rate_items)
+        + item_count(power_save_items)
+        + item_count(ext_int_items)
+        + item_count(time_pulse_items)
+};
+const _: () = assert!(STORED_KEYS <= ValDel::MAX_KEYS);
+
+const fn item_count<T, const N: usize>(_: fn(&T) -> [(u32, u64); N]) -> usize {
+    N
+}
+
 const GPS_EPOCH: NaiveDateTime = NaiveDate::from_ymd_opt(1980, 1, 6)
     .unwrap()
     .and_time(NaiveTime::MIN);
@@ -22,21 +299,212 @@ where
     i2c: i2c::I2c<'d, I2c, i2c::Async>,
     gpio_nreset: gpio::Output<'d>,
     gpio_extint: gpio::Input<'d>,
+    /// Whether the receiver asserts EXTINT when it has data for the I2C, otherwise it is polled.
+    tx_ready: bool,
+    /// Polled before the setup
+    protocol_version: Option<ProtVer>,
+    /// Whether the receiver accepted to output UBX-SEC-SIG
+    sec_sig: bool,
+    health: Health,
+    backoff: Backoff,
+    /// When the receiver was last set up
+    ready_since: Option<Instant>,
+    config: Config,
+    /// Last position fixed, or the one saved before
+    position: Option<Position>,
+    /// Time of the last fix, and when it arrived
+    last_fix: Option<(NaiveDateTime, Instant)>,
+    /// AssistNow data to send after the reset, `None` once all have been sent
+    assistance: Option<Assistance>,
+}
+
+/// Progress of sending the AssistNow data, a frame at a time between the reads of UART1.
+#[derive(Default)]
+struct Assistance {
+    /// Next frame
+    cursor: assist_now::Cursor,
+    /// The frame written last, until UBX-MGA-ACK for it arrives
+    pending: Option<PendingMga>,
+}
+
+struct PendingMga {
+    id: u8,
+    /// Echoed by UBX-MGA-ACK
+    payload_start: heapless::Vec<u8, 4>,
+    deadline: Instant,
 }
 
+impl PendingMga {
+    fn new(frame: &UbxFrame<'_>, deadline: Instant) -> Self {
+        let len = frame.payload.len().min(4);
+        Self {
+            id: frame.id,
+            payload_start: defmt::unwrap!(heapless::Vec::from_slice(&frame.payload[..len]).ok()),
+            deadline,
+        }
+    }
+
+    /// Whether the receiver used the frame, if `f` is UBX-MGA-ACK for it.
+    fn ack(&self, f: &UbxFrame<'_>) -> Option<bool> {
+        if (f.class, f.id) == (MgaAck::CLASS, MgaAck::ID)
+            && let Some(ack) = MgaAck::parse(f.payload)
+            && ack.is_for(self.id, &self.payload_start)
+        {
+            if !ack.accepted {
+                defmt::debug!(
+                    "UBX-MGA-ACK: {:02x} not used ({})",
+                    ack.msg_id,
+                    ack.info_code
+                );
+            }
+            return Some(ack.accepted);
+        }
+        None
+    }
+}
+
+/// The receiver is held in reset for this long after the first failure, doubling on each
+/// consecutive one up to `MAX_RESET_MS`.
+const RESET_MS: u32 = 500;
+const MAX_RESET_MS: u32 = 60_000;
+/// Consecutive failures until [`HealthState::Failed`].
+const FAILED_AFTER: u32 = 3;
+/// The failures are forgotten once the receiver has been ready for this long, so that one which
+/// keeps failing after the setup still backs off.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
 #[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
 enum State {
     PowerCycle,
     Setup,
     Ready,
+    FactoryReset,
+}
+
+pub enum Command {
+    /// Deletes the configuration saved in BBR and flash, and cold-starts the receiver. The result
+    /// is signalled through `done` of [`MaxM10s::run`].
+    FactoryReset,
 }
 
 pub enum Event {
-    DateTimeAndVelocity {
+    NavigationSolution {
         datetime: NaiveDateTime,
-        ground_speed_meter_hour: u32,
+        pvt: NavPvt,
     },
     DateTimeNextPulse(NaiveDateTime),
+    Odometer(NavOdo),
+    Health(Health),
+    /// The first block of UBX-MON-RF, every 5 solutions.
+    Rf(RfBlock),
+    ExternalEvent(ExternalEvent),
+}
+
+/// An edge of the signal wired to EXTINT, timestamped by the receiver (UBX-TIM-TM2).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ExternalEvent {
+    /// UTC, to the ns
+    pub datetime: NaiveDateTime,
+    pub rising: bool,
+    /// Rising edges counted by the receiver, wrapping around
+    pub count: u16,
+    /// Accuracy of `datetime` estimated by the receiver, in ns
+    pub acc_est_ns: u32,
+}
+
+/// Polled once the receiver has been set up.
+static VERSION: Mutex<CriticalSectionRawMutex, Cell<Option<Version>>> = Mutex::new(Cell::new(None));
+
+/// Firmware version of the receiver, `None` until polled.
+pub fn version() -> Option<Version> {
+    VERSION.lock(|v| v.get())
+}
+
+/// UBX-MON-VER kept as received, decoded by [`MonVer`].
+#[derive(Copy, Clone, PartialEq)]
+pub struct Version {
+    payload: [u8; Version::MAX_LEN],
+    len: u16,
+}
+
+impl Version {
+    /// Up to 8 extensions
+    const MAX_LEN: usize =
+        MonVer::SW_VERSION_LEN + MonVer::HW_VERSION_LEN + 8 * MonVer::EXTENSION_LEN;
+
+    fn new(payload: &[u8]) -> Option<Self> {
+        MonVer::parse(payload)?;
+        let mut v = Self {
+            payload: [0; Self::MAX_LEN],
+            len: 0,
+        };
+        // the extensions which do not fit are dropped
+        let len = payload.len().min(Self::MAX_LEN);
+        let len =
+            len - (len - MonVer::SW_VERSION_LEN - MonVer::HW_VERSION_LEN) % MonVer::EXTENSION_LEN;
+        v.payload[..len].copy_from_slice(&payload[..len]);
+        v.len = len as u16;
+        Some(v)
+    }
+
+    pub fn decode(&self) -> MonVer<'_> {
+        defmt::unwrap!(MonVer::parse(&self.payload[..self.len as usize]))
+    }
+}
+
+impl defmt::Format for Version {
+    fn format(&self, fmt: defmt::Formatter) {
+        let v = self.decode();
+        defmt::write!(fmt, "{=str} ({=str})", v.sw_version, v.hw_version);
+    }
+}
+
+/// Condition of the receiver, sent as [`Event::Health`] whenever it changes.
+#[derive(Copy, Clone, Default, PartialEq, defmt::Format)]
+pub struct Health {
+    pub state: HealthState,
+    /// Consecutive attempts which failed to set up the receiver or keep it running
+    pub failures: u32,
+    pub power_cycles: u32,
+    pub i2c_errors: u32,
+    pub uart_errors: u32,
+    pub ack_naks: u32,
+    pub last_fault: Option<ReceiverFault>,
+    /// From UBX-SEC-SIG, or UBX-MON-RF if the former does not detect jamming
+    pub jamming: JammingState,
+    /// From UBX-SEC-SIG
+    pub spoofing: SpoofingState,
+}
+
+#[derive(Copy, Clone, Default, PartialEq, defmt::Format)]
+pub enum HealthState {
+    #[default]
+    Starting,
+    Ready,
+    /// Being power-cycled after a failure.
+    Retrying,
+    /// Failed `FAILED_AFTER` times in a row, still retrying.
+    Failed,
+}
+
+/// Why a request to the receiver failed.
+#[derive(Debug, defmt::Format)]
+pub enum RequestError {
+    I2c(i2c::Error),
+    /// Neither UBX-ACK-ACK nor UBX-ACK-NAK arrived within `RESPONSE_TIMEOUT`.
+    Timeout,
+    /// Rejected by UBX-ACK-NAK.
+    Nak,
+}
+
+impl From<RequestError> for ReceiverFault {
+    fn from(e: RequestError) -> Self {
+        match e {
+            RequestError::I2c(..) => Self::I2c,
+            RequestError::Timeout => Self::Timeout,
+            RequestError::Nak => Self::Nak,
+        }
+    }
 }
 
 impl<'d, I2c> MaxM10s<'d, I2c>
@@ -70,149 +538,752 @@ where
             i2c: i2c::I2c::new_async(i2c, i2c_scl, i2c_sda, irq, i2c::Config::default()),
             gpio_nreset: gpio::Output::new(gpio_nreset, gpio::Level::Low),
             gpio_extint: gpio::Input::new(gpio_extint, gpio::Pull::Up),
+            tx_ready: false,
+            protocol_version: None,
+            sec_sig: false,
+            health: Health::default(),
+            backoff: Backoff::new(RESET_MS, MAX_RESET_MS),
+            ready_since: None,
+            config: Config::default(),
+            position: None,
+            last_fix: None,
+            assistance: None,
         }
     }
 
-    pub async fn run<M: RawMutex, const N: usize>(&mut self, sender: Sender<'_, M, Event, N>) {
+    /// Where the clock was last, if known, given to the receiver until it fixes.
+    pub fn set_position(&mut self, position: Option<Position>) {
+        self.position = position;
+    }
+
+    /// `commands` and the changes of `config` are handled once the receiver has been set up.
+    /// The frames in `assist_now` are sent after each reset, once the date is known.
+    pub async fn run<M: RawMutex, const N: usize>(
+        &mut self,
+        sender: Sender<'_, M, Event, N>,
+        commands: &Signal<M, Command>,
+        done: &Signal<M, bool>,
+        mut config: DynReceiver<'_, Config>,
+        assist_now: &dyn assist_now::Storage,
+        heartbeat: &Heartbeat,
+    ) {
+        self.config = heartbeat.idle(config.get()).await;
         let mut state = State::PowerCycle;
+        let mut health = self.health;
+        sender.send(Event::Health(health)).await;
         loop {
+            heartbeat.beat();
             let next_state = match state {
-                State::PowerCycle => self.do_power_cycle().await,
-                State::Setup => self.do_setup().await,
-                State::Ready => self.do_receive_ubx(&sender).await,
+                State::PowerCycle => self.do_power_cycle(heartbeat).await,
+                State::Setup => self.do_setup(heartbeat).await,
+                State::Ready => {
+                    self.do_receive_ubx(&sender, commands, &mut config, assist_now, heartbeat)
+                        .await
+                }
+                State::FactoryReset => self.do_factory_reset(done, heartbeat).await,
             };
             if next_state != state {
                 defmt::info!("MAX-M10S: {} -> {}", state, next_state);
                 state = next_state;
+                if state == State::Ready {
+                    self.ready_since = Some(Instant::now());
+                }
             }
+            if state == State::Ready {
+                self.health.state = HealthState::Ready;
+            }
+            if self.health != health {
+                health = self.health;
+                defmt::info!("MAX-M10S: {}", health);
+                sender.send(Event::Health(health)).await;
+            }
+        }
+    }
+
+    /// Forgets the failures once the receiver has been ready for `STABLE_AFTER`. Returns whether
+    /// the health changed.
+    fn forget_failures(&mut self) -> bool {
+        if self.backoff.failures() == 0
+            || self.ready_since.is_none_or(|t| t.elapsed() < STABLE_AFTER)
+        {
+            return false;
+        }
+        self.backoff.succeeded();
+        self.health.failures = 0;
+        true
+    }
+
+    /// Records the failure, and backs off before the next attempt.
+    fn fail(&mut self, reason: ReceiverFault) -> State {
+        fault_log::record(Fault::ReceiverPowerCycle(reason));
+        match reason {
+            ReceiverFault::NotReady | ReceiverFault::I2c => self.health.i2c_errors += 1,
+            ReceiverFault::Nak => self.health.ack_naks += 1,
+            // counted one by one
+            ReceiverFault::Timeout | ReceiverFault::Uart => (),
         }
+        self.backoff.failed();
+        self.health.failures = self.backoff.failures();
+        self.health.last_fault = Some(reason);
+        self.health.state = if self.health.failures >= FAILED_AFTER {
+            HealthState::Failed
+        } else {
+            HealthState::Retrying
+        };
+        State::PowerCycle
     }
 
-    async fn do_power_cycle(&mut self) -> State {
+    async fn do_power_cycle(&mut self, heartbeat: &Heartbeat) -> State {
+        if self.health.state != HealthState::Starting {
+            self.health.power_cycles += 1;
+        }
         self.gpio_nreset.set_low();
-        Timer::after_millis(500).await;
+        heartbeat
+            .idle(Timer::after_millis(self.backoff.delay_ms().into()))
+            .await;
         self.gpio_nreset.set_high();
         State::Setup
     }
 
-    async fn do_setup(&mut self) -> State {
-        let ubx_cfg_valset_frame = {
-            let mut frame = [
-                0xb5, 0x62, // header
-                0x06, 0x8a, // id/class (=UBX-CFG-VALSET)
-                0x00, 0x00, // length
-                // payload begin
-                0x00, // version
-                0x01, // layers (=ram)
-                0x00, 0x00, // reserved
-                0x01, 0x00, 0xa2, 0x10, 0x01, // CFG-TXREADY-ENABLED (=true)
-                0x02, 0x00, 0xa2, 0x10, 0x01, // CFG-TXREADY-POLARIT (=true=low-active)
-                0x03, 0x00, 0xa2, 0x20, 0x05, // CFG-TXREADY-PIN (=5=EXTINT)
-                0x04, 0x00, 0xa2, 0x30, 0x01, 0x00, // CFG-TXREADY-THRESHOLD (=8/8)
-                0x05, 0x00, 0xa2, 0x20, 0x00, // CFG-TXREADY-INTERFACE (=0=I2C)
-                0x01, 0x00, 0x21, 0x30, 0xc8, 0x00, // CFG-RATE-MEAS (=200 ms/5 Hz)
-                0x07, 0x00, 0x91, 0x20, 0x01, // CFG-MSGOUT-UBX_NAV_PVT_UART1 (=1)
-                0x7e, 0x01, 0x91, 0x20, 0x01, // CFG-MSGOUT-UBX_TIM_TP_UART1 (=1)
-                0x01, 0x00, 0x71, 0x10, 0x01, // CFG-I2CINPROT-UBX (=1)
-                0x02, 0x00, 0x71, 0x10, 0x00, // CFG-I2CINPROT-NMEA (=0)
-                0x01, 0x00, 0x72, 0x10, 0x01, // CFG-I2COUTPROT-UBX (=1)
-                0x02, 0x00, 0x72, 0x10, 0x00, // CFG-I2COUTPROT-NMEA (=0)
-                0x01, 0x00, 0x73, 0x10, 0x00, // CFG-UART1INPROT-UBX (=0)
-                0x02, 0x00, 0x73, 0x10, 0x00, // CFG-UART1INPROT-NMEA (=0)
-                0x01, 0x00, 0x74, 0x10, 0x01, // CFG-UART1OUTPROT-UBX (=1)
-                0x02, 0x00, 0x74, 0x10, 0x00, // CFG-UART1OUTPROT-NMEA (=0)
-                // CFG-UART1-BAUDRATE (=115200)
-                0x01, 0x00, 0x52, 0x40, 0x00, 0xc2, 0x01, 0x00,
-                // payload end
-                0x00, // ck_a
-                0x00, // ck_b
-            ];
-            let len = frame.len() as u16 - 8;
-            frame[4..6].copy_from_slice(&len.to_le_bytes());
-            (frame[frame.len() - 2], frame[frame.len() - 1]) =
-                ubx::checksum(&frame[2..frame.len() - 2]);
-            frame
-        };
-
+    async fn do_setup(&mut self, heartbeat: &Heartbeat) -> State {
+        // the receiver does not respond on I2C until it has booted
         let t = Instant::now();
+        let mut len = [0; 2];
         while let Err(e) = self
             .i2c
-            .write_async(MAX_M10S_I2C_ADDRESS, ubx_cfg_valset_frame)
+            .write_read_async(MAX_M10S_I2C_ADDRESS, [0xfd_u8], &mut len)
             .await
         {
             defmt::debug!("{}", e);
+            heartbeat.beat();
             if Instant::now() > t + Duration::from_secs(5) {
                 defmt::warn!("I2C bus or device not ready");
-                return State::PowerCycle;
+                return self.fail(ReceiverFault::NotReady);
             }
             Timer::after_millis(100).await;
         }
+        // TX_READY is not set up until the receiver has been configured, unless it was stored
+        self.tx_ready = false;
 
-        let mut buf = UbxStream::<512>::new();
-        'outer: loop {
-            if let Either::Second(..) =
-                select(self.gpio_extint.wait_for_low(), Timer::after_secs(1)).await
-            {
-                defmt::warn!("EXTINT pin (TX_READY) is not being asserted");
-                return State::PowerCycle;
+        // which keys the receiver knows
+        let mut frame = [0; 128];
+        let poll = defmt::unwrap!(MonVer::poll(&mut frame));
+        let version = self
+            .transact(poll, heartbeat, |f| {
+                ((f.class, f.id) == (MonVer::CLASS, MonVer::ID))
+                    .then(|| Version::new(f.payload).ok_or(RequestError::Nak))
+            })
+            .await;
+        match version {
+            Ok(v) => {
+                defmt::info!("MAX-M10S: {}", v);
+                self.protocol_version = v.decode().protocol_version();
+                VERSION.lock(|c| c.set(Some(v)));
+            }
+            Err(e) => {
+                defmt::warn!("failed to poll the version ({})", e);
+                return self.fail(e.into());
+            }
+        }
+
+        if let Err(e) = self.configure_all(heartbeat).await {
+            return self.fail(e.into());
+        }
+        if let Err(e) = self.assist(heartbeat).await {
+            defmt::warn!("assistance failed ({})", e);
+            return self.fail(e.into());
+        }
+        self.assistance = Some(Assistance::default());
+
+        State::Ready
+    }
+
+    /// Tells the receiver the last position and, unless it is the first start, the time kept on the
+    /// local clock since the last fix (UBX-MGA-INI-*), so that it fixes sooner than a cold start.
+    async fn assist(&mut self, heartbeat: &Heartbeat) -> Result<(), RequestError> {
+        let mut frame = [0; 40];
+        if let Some(position) = self.position {
+            let pos = IniPosLlh {
+                lat: position.lat,
+                lon: position.lon,
+                // the height above the ellipsoid differs by less than the accuracy
+                alt: position.h_msl / 10,
+                pos_acc: POSITION_ACCURACY_CM,
+            };
+            let pos = defmt::unwrap!(pos.encode(&mut frame));
+            if !self.request_mga(pos, heartbeat).await? {
+                defmt::warn!("UBX-MGA-INI-POS_LLH not used");
+            }
+        }
+        if let Some((datetime, at)) = self.last_fix {
+            let elapsed = at.elapsed();
+            let now = datetime + TimeDelta::microseconds(elapsed.as_micros() as i64);
+            // the drift, plus a margin for the delay of the message
+            let t_acc_ms = 1000 + elapsed.as_millis() * u64::from(CLOCK_TOLERANCE_PPM) / 1_000_000;
+            let time = IniTimeUtc {
+                year: now.year() as u16,
+                month: now.month() as u8,
+                day: now.day() as u8,
+                hour: now.hour() as u8,
+                min: now.minute() as u8,
+                sec: now.second() as u8,
+                ns: now.nanosecond(),
+                t_acc_s: (t_acc_ms / 1000).min(u16::MAX.into()) as u16,
+                t_acc_ns: (t_acc_ms % 1000 * 1_000_000) as u32,
+            };
+            let time = defmt::unwrap!(time.encode(&mut frame));
+            if !self.request_mga(time, heartbeat).await? {
+                defmt::warn!("UBX-MGA-INI-TIME_UTC not used");
             }
+        }
+        Ok(())
+    }
 
-            let mut len = [0; 2];
-            if let Err(e) = self
-                .i2c
-                .write_read_async(MAX_M10S_I2C_ADDRESS, [0xfd_u8], &mut len)
+    /// One step of sending `assist_now`: writes the next frame (skipping UBX-MGA-ANO of the other
+    /// days), or reads what the receiver has sent over I2C into `i2c_buf` since, until UBX-MGA-ACK
+    /// for it arrives. Only an I2C error is returned, anything else just ends the assistance.
+    async fn assist_offline(
+        &mut self,
+        assist_now: &dyn assist_now::Storage,
+        i2c_buf: &mut UbxStream<512>,
+    ) -> Result<(), RequestError> {
+        let (Some(mut assistance), Some((datetime, at))) = (self.assistance.take(), self.last_fix)
+        else {
+            return Ok(());
+        };
+
+        if let Some(pending) = &assistance.pending {
+            self.read_i2c(i2c_buf).await?;
+            let mut acked = false;
+            while let Some(f) = i2c_buf.pop() {
+                acked |= pending.ack(&f).is_some();
+            }
+            if acked {
+                assistance.pending = None;
+            } else if Instant::now() > pending.deadline {
+                // not worth power-cycling the receiver
+                defmt::warn!("no UBX-MGA-ACK, AssistNow data not sent");
+                return Ok(());
+            }
+            self.assistance = Some(assistance);
+            return Ok(());
+        }
+
+        let today = (datetime + TimeDelta::microseconds(at.elapsed().as_micros() as i64)).date();
+        let mut buf = [0; assist_now::MAX_FRAME_LEN];
+        loop {
+            let frame = match assist_now.read_next(&mut assistance.cursor, &mut buf) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => {
+                    defmt::info!("AssistNow data sent");
+                    return Ok(());
+                }
+                Err(e) => {
+                    defmt::warn!("failed to read the AssistNow data: {}", e);
+                    return Ok(());
+                }
+            };
+            let Some(f) = UbxFrame::parse(frame) else {
+                return Ok(());
+            };
+            if f.id == Ano::ID
+                && let Some(ano) = Ano::parse(f.payload)
+                && (ano.year, ano.month, ano.day)
+                    != (today.year() as u16, today.month() as u8, today.day() as u8)
+            {
+                continue;
+            }
+            self.i2c
+                .write_async(MAX_M10S_I2C_ADDRESS, frame.iter().copied())
                 .await
+                .map_err(RequestError::I2c)?;
+            assistance.pending = Some(PendingMga::new(&f, Instant::now() + RESPONSE_TIMEOUT));
+            break;
+        }
+        self.assistance = Some(assistance);
+        Ok(())
+    }
+
+    async fn configure_all(&mut self, heartbeat: &Heartbeat) -> Result<(), RequestError> {
+        self.configure_ext_int(self.config.ext_int, heartbeat)
+            .await?;
+        for group in &SETUP {
+            let configured = if self.supports(group.protver) {
+                self.configure(group.name, group.items, heartbeat).await?
+            } else {
+                defmt::info!(
+                    "configuration not supported by the receiver: {}",
+                    group.name
+                );
+                false
+            };
+            if group.name == "security" {
+                self.sec_sig = configured;
+            }
+        }
+        let signals = signal_items(&self.config.constellations);
+        self.configure("signals", &signals, heartbeat).await?;
+        let rate = rate_items(&self.config.rate);
+        self.configure("rate", &rate, heartbeat).await?;
+        let power_save = power_save_items(&self.config.power_save);
+        self.configure("power-save", &power_save, heartbeat).await?;
+        let time_pulse = time_pulse_items(&self.config.time_pulse);
+        self.configure("time-pulse", &time_pulse, heartbeat).await?;
+        Ok(())
+    }
+
+    /// Whether the receiver knows the keys which came with `protver`. Not if its protocol version
+    /// is unknown.
+    fn supports(&self, protver: Option<ProtVer>) -> bool {
+        protver.is_none_or(|min| self.protocol_version.is_some_and(|v| v >= min))
+    }
+
+    /// The receiver is polled until EXTINT has been switched, it may not assert TX_READY
+    /// meanwhile.
+    async fn configure_ext_int(
+        &mut self,
+        ext_int: ExtInt,
+        heartbeat: &Heartbeat,
+    ) -> Result<(), RequestError> {
+        self.tx_ready = false;
+        self.configure("ext-int", &ext_int_items(&ext_int), heartbeat)
+            .await?;
+        self.tx_ready = ext_int == ExtInt::TxReady;
+        Ok(())
+    }
+
+    /// Writes `items` to the RAM layer, and to the layers of `receiver_storage` unless they are
+    /// stored there already (the receiver loads them into RAM when it starts). A rejection is only
+    /// logged, the rest of the configuration may still work, and returns `false`.
+    async fn configure(
+        &mut self,
+        name: &'static str,
+        items: &[(u32, u64)],
+        heartbeat: &Heartbeat,
+    ) -> Result<bool, RequestError> {
+        let (layers, stored_in) = match self.config.receiver_storage {
+            ReceiverStorage::Ram => (LAYER_RAM, None),
+            ReceiverStorage::Bbr => (LAYER_RAM | LAYER_BBR, Some(ValGet::LAYER_BBR)),
+            ReceiverStorage::Flash => (
+                LAYER_RAM | LAYER_BBR | LAYER_FLASH,
+                Some(ValGet::LAYER_FLASH),
+            ),
+        };
+        let mut pending =
+            defmt::unwrap!(heapless::Vec::<_, MAX_GROUP_KEYS>::from_slice(items).ok());
+        if let Some(layer) = stored_in {
+            match self.stored(layer, items, heartbeat).await {
+                Ok(stored) => pending.retain(|item| !stored.contains(item)),
+                // none of the keys are stored
+                Err(RequestError::Nak) => (),
+                Err(e) => {
+                    defmt::warn!("configuration failed: {} ({})", name, e);
+                    return Err(e);
+                }
+            }
+            if pending.is_empty() {
+                defmt::debug!("configuration stored already: {}", name);
+                return Ok(true);
+            }
+        }
+
+        let mut frame = [0; 256];
+        let valset = defmt::unwrap!(ValSet::encode(&mut frame, layers, &pending));
+        match self.request(valset, heartbeat).await {
+            Err(RequestError::Nak) => {
+                defmt::error!("configuration rejected: {}", name);
+                self.health.ack_naks += 1;
+                fault_log::record(Fault::ConfigRejected { frame: name });
+                Ok(false)
+            }
+            Err(e) => {
+                defmt::warn!("configuration failed: {} ({})", name, e);
+                Err(e)
+            }
+            Ok(()) => Ok(true),
+        }
+    }
+
+    /// Reads the values of the keys of `items` in `layer`. Those which are not stored are left out.
+    async fn stored(
+        &mut self,
+        layer: u8,
+        items: &[(u32, u64)],
+        heartbeat: &Heartbeat,
+    ) -> Result<heapless::Vec<(u32, u64), MAX_GROUP_KEYS>, RequestError> {
+        let mut keys = heapless::Vec::<u32, MAX_GROUP_KEYS>::new();
+        for (key, _) in items {
+            defmt::unwrap!(keys.push(*key));
+        }
+        let mut frame = [0; 128];
+        let poll = defmt::unwrap!(ValGet::poll(&mut frame, layer, 0, &keys));
+        self.transact(poll, heartbeat, |f| {
+            if (f.class, f.id) == (ValGet::CLASS, ValGet::ID) {
+                let valget = ValGet::parse(f.payload)?;
+                return Some(Ok(valget.items().take(MAX_GROUP_KEYS).collect()));
+            }
+            if f.class == Ack::CLASS
+                && let Some(ack) = Ack::parse(f.id, f.payload)
+                && (ack.class, ack.id) == (ValGet::CLASS, ValGet::ID)
+                && !ack.acknowledged
             {
-                defmt::warn!("I2C operation failed ({})", e);
-                return State::PowerCycle;
+                return Some(Err(RequestError::Nak));
+            }
+            None
+        })
+        .await
+    }
+
+    /// Deletes all the keys written by the clock from BBR and flash.
+    async fn delete_stored(&mut self, heartbeat: &Heartbeat) -> Result<(), RequestError> {
+        let mut keys = heapless::Vec::<u32, { ValDel::MAX_KEYS }>::new();
+        let signals = signal_items(&Constellations::default());
+        let rate = rate_items(&Rate::default());
+        let power_save = power_save_items(&PowerSave::default());
+        let time_pulse = time_pulse_items(&TimePulse::default());
+        let ext_int = ext_int_items(&ExtInt::default());
+        let items = SETUP
+            .iter()
+            .filter(|group| self.supports(group.protver))
+            .flat_map(|group| group.items);
+        for (key, _) in items
+            .chain(&ext_int)
+            .chain(&signals)
+            .chain(&rate)
+            .chain(&power_save)
+            .chain(&time_pulse)
+        {
+            defmt::unwrap!(keys.push(*key));
+        }
+        // the header, layers and checksum, then the keys
+        let mut frame = [0; 8 + 4 + 4 * ValDel::MAX_KEYS];
+        let valdel = defmt::unwrap!(ValDel::encode(&mut frame, LAYER_BBR | LAYER_FLASH, &keys));
+        self.request(valdel, heartbeat).await
+    }
+
+    /// Applies the parts of `config` for the receiver which changed.
+    async fn reconfigure(
+        &mut self,
+        config: Config,
+        heartbeat: &Heartbeat,
+    ) -> Result<(), RequestError> {
+        let old = core::mem::replace(&mut self.config, config);
+        if old.receiver_storage != config.receiver_storage {
+            // the stale values would be loaded by the next start
+            match self.delete_stored(heartbeat).await {
+                Err(RequestError::Nak) => {
+                    defmt::error!("configuration rejected: receiver-storage");
+                    self.health.ack_naks += 1;
+                    fault_log::record(Fault::ConfigRejected {
+                        frame: "receiver-storage",
+                    });
+                }
+                result => result?,
             }
+            return self.configure_all(heartbeat).await;
+        }
+        if old.constellations != config.constellations {
+            let signals = signal_items(&config.constellations);
+            self.configure("signals", &signals, heartbeat).await?;
+        }
+        if old.rate != config.rate {
+            self.configure("rate", &rate_items(&config.rate), heartbeat)
+                .await?;
+        }
+        if old.power_save != config.power_save {
+            let power_save = power_save_items(&config.power_save);
+            self.configure("power-save", &power_save, heartbeat).await?;
+        }
+        if old.time_pulse != config.time_pulse {
+            let time_pulse = time_pulse_items(&config.time_pulse);
+            self.configure("time-pulse", &time_pulse, heartbeat).await?;
+        }
+        if old.ext_int != config.ext_int {
+            self.configure_ext_int(config.ext_int, heartbeat).await?;
+        }
+        Ok(())
+    }
 
-            let len = u16::from_be_bytes(len) as usize;
-            defmt::debug!("len = {}", len);
+    async fn do_factory_reset<M: RawMutex>(
+        &mut self,
+        done: &Signal<M, bool>,
+        heartbeat: &Heartbeat,
+    ) -> State {
+        match self.delete_stored(heartbeat).await {
+            Ok(()) => (),
+            Err(RequestError::Nak) => {
+                defmt::error!("configuration rejected: factory-reset");
+                self.health.ack_naks += 1;
+                fault_log::record(Fault::ConfigRejected {
+                    frame: "factory-reset",
+                });
+                done.signal(false);
+                return State::Ready;
+            }
+            Err(e) => {
+                defmt::warn!("configuration failed: factory-reset ({})", e);
+                done.signal(false);
+                return self.fail(e.into());
+            }
+        }
 
-            if let Err(e) = self
-                .i2c
-                .read_async(MAX_M10S_I2C_ADDRESS, &mut buf.buf_unused_mut()[..len])
-                .await
+        // not acknowledged, the receiver restarts right away
+        let mut frame = [0; 16];
+        let rst = defmt::unwrap!(Rst::encode(
+            &mut frame,
+            NAV_BBR_COLD_START,
+            RESET_MODE_SOFTWARE
+        ));
+        if let Err(e) = self
+            .i2c
+            .write_async(MAX_M10S_I2C_ADDRESS, rst.iter().copied())
+            .await
+        {
+            defmt::warn!("I2C operation failed ({})", e);
+        }
+        done.signal(true);
+        State::PowerCycle
+    }
+
+    /// Writes the UBX `frame` over I2C, then reads the frames until UBX-ACK-ACK or UBX-ACK-NAK for
+    /// it arrives.
+    async fn request(&mut self, frame: &[u8], heartbeat: &Heartbeat) -> Result<(), RequestError> {
+        // sync chars, then class and id
+        let (class, id) = (frame[2], frame[3]);
+        self.transact(frame, heartbeat, |f| {
+            if f.class == Ack::CLASS
+                && let Some(ack) = Ack::parse(f.id, f.payload)
+                && (ack.class, ack.id) == (class, id)
             {
-                defmt::warn!("I2C operation failed ({})", e);
-                return State::PowerCycle;
+                return Some(match ack.acknowledged {
+                    true => Ok(()),
+                    false => Err(RequestError::Nak),
+                });
             }
+            None
+        })
+        .await
+    }
 
-            buf.commit(len);
+    /// Writes the UBX-MGA `frame` over I2C, then reads the frames until UBX-MGA-ACK for it arrives.
+    /// Returns whether the receiver used it.
+    async fn request_mga(
+        &mut self,
+        frame: &[u8],
+        heartbeat: &Heartbeat,
+    ) -> Result<bool, RequestError> {
+        let Some(request) = UbxFrame::parse(frame) else {
+            return Ok(false);
+        };
+        // the deadline is kept by `transact`
+        let request = PendingMga::new(&request, Instant::now() + RESPONSE_TIMEOUT);
+        self.transact(frame, heartbeat, |f| request.ack(f).map(Ok))
+            .await
+    }
 
-            while let Some(frame) = buf.pop() {
-                if let UbxFrame {
-                    class: 0x05, // (=UBX-ACK-ACK)
-                    id: 0x01,
-                    payload: &[0x06, 0x8a], // (=UBX-CFG-VALSET)
-                } = frame
+    /// Writes the UBX `frame` over I2C, then reads the frames until `response` picks one.
+    async fn transact<T>(
+        &mut self,
+        frame: &[u8],
+        heartbeat: &Heartbeat,
+        mut response: impl FnMut(&UbxFrame<'_>) -> Option<Result<T, RequestError>>,
+    ) -> Result<T, RequestError> {
+        self.i2c
+            .write_async(MAX_M10S_I2C_ADDRESS, frame.iter().copied())
+            .await
+            .map_err(RequestError::I2c)?;
+
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        let mut buf = UbxStream::<512>::new();
+        loop {
+            heartbeat.beat();
+            if self.tx_ready {
+                if let Either::Second(..) =
+                    select(self.gpio_extint.wait_for_low(), Timer::at(deadline)).await
                 {
-                    break 'outer;
+                    return Err(RequestError::Timeout);
+                }
+            } else if Instant::now() > deadline {
+                return Err(RequestError::Timeout);
+            }
+
+            if !self.read_i2c(&mut buf).await? {
+                Timer::after_millis(10).await;
+                continue;
+            }
+
+            while let Some(frame) = buf.pop() {
+                if let Some(result) = response(&frame) {
+                    return result;
                 }
             }
         }
+    }
 
-        State::Ready
+    /// Reads what the receiver has to send over I2C into `buf`. Returns whether there was anything.
+    async fn read_i2c(&mut self, buf: &mut UbxStream<512>) -> Result<bool, RequestError> {
+        let mut len = [0; 2];
+        self.i2c
+            .write_read_async(MAX_M10S_I2C_ADDRESS, [0xfd_u8], &mut len)
+            .await
+            .map_err(RequestError::I2c)?;
+
+        if buf.buf_unused_mut().is_empty() {
+            // garbage without any frame, start over
+            let filled = buf.buf_filled().len();
+            buf.consume(filled);
+        }
+        let unused = buf.buf_unused_mut();
+        let len = (u16::from_be_bytes(len) as usize).min(unused.len());
+        defmt::debug!("len = {}", len);
+        if len == 0 {
+            return Ok(false);
+        }
+
+        self.i2c
+            .read_async(MAX_M10S_I2C_ADDRESS, &mut unused[..len])
+            .await
+            .map_err(RequestError::I2c)?;
+        buf.commit(len);
+        Ok(true)
+    }
+
+    /// Sends the solution, and keeps its time and position unless it is spoofed.
+    async fn navigation_solution<M: RawMutex, const N: usize>(
+        &mut self,
+        sender: &Sender<'_, M, Event, N>,
+        pvt: NavPvt,
+    ) {
+        if let (Some(date), Some(time)) = (
+            NaiveDate::from_ymd_opt(pvt.year.into(), pvt.month.into(), pvt.day.into()),
+            NaiveTime::from_hms_milli_opt(
+                pvt.hour.into(),
+                pvt.min.into(),
+                pvt.sec.into(),
+                pvt.itow % 1000,
+            ),
+        ) {
+            let datetime = date.and_time(time);
+            if pvt.valid & VALID_DATE_TIME == VALID_DATE_TIME && !self.health.spoofing.is_spoofed()
+            {
+                self.last_fix = Some((datetime, Instant::now()));
+            }
+            sender
+                .send(Event::NavigationSolution { datetime, pvt })
+                .await;
+        }
+        if let Some(position) = Position::from_pvt(&pvt)
+            && !self.health.spoofing.is_spoofed()
+        {
+            self.position = Some(position);
+        }
+
+        defmt::info!(
+            "UBX-NAV-PVT: {} ms, {:04}-{:02}-{:02} {:02}:{:02}:{:02}, {} mm/s (+/- {} mm/s), fix = {:#04x}, flags = {:#04x}",
+            pvt.itow,
+            pvt.year,
+            pvt.month,
+            pvt.day,
+            pvt.hour,
+            pvt.min,
+            pvt.sec,
+            pvt.g_speed,
+            pvt.s_acc,
+            pvt.fix_type,
+            pvt.flags
+        );
+    }
+
+    async fn update_interference<M: RawMutex, const N: usize>(
+        &mut self,
+        sender: &Sender<'_, M, Event, N>,
+        jamming: JammingState,
+        spoofing: SpoofingState,
+    ) {
+        if (jamming, spoofing) == (self.health.jamming, self.health.spoofing) {
+            return;
+        }
+        if spoofing.is_spoofed() && !self.health.spoofing.is_spoofed() {
+            fault_log::record(Fault::Spoofing);
+        }
+        self.health.jamming = jamming;
+        self.health.spoofing = spoofing;
+        sender.send(Event::Health(self.health)).await;
     }
 
     async fn do_receive_ubx<M: RawMutex, const N: usize>(
         &mut self,
         sender: &Sender<'_, M, Event, N>,
+        commands: &Signal<M, Command>,
+        config: &mut DynReceiver<'_, Config>,
+        assist_now: &dyn assist_now::Storage,
+        heartbeat: &Heartbeat,
     ) -> State {
         let mut buf = UbxStream::<512>::new();
+        // UBX-MGA-ACK of the assistance
+        let mut i2c_buf = UbxStream::<512>::new();
         let mut errors = 0_u32;
+        let mut sec_sig_jamming = false;
+        // UBX-NAV-PVT until UBX-SEC-SIG arrives
+        let mut held_pvt: Option<NavPvt> = None;
         loop {
             if errors > 10 {
                 defmt::warn!("too many UART errors");
-                return State::PowerCycle;
+                return self.fail(ReceiverFault::Uart);
+            }
+            if self.forget_failures() {
+                sender.send(Event::Health(self.health)).await;
             }
 
-            match self.uart.read(buf.buf_unused_mut()).await {
-                Ok(len) => buf.commit(len),
-                Err(e) => {
+            // the assistance is sent a frame at a time between the reads: `Some(None)` to write
+            // the next one, `Some(Some(deadline))` while waiting for UBX-MGA-ACK
+            let assisting = match (&self.assistance, self.last_fix) {
+                (Some(a), Some(..)) => Some(a.pending.as_ref().map(|p| p.deadline)),
+                _ => None,
+            };
+            let tx_ready = self.tx_ready;
+            let extint = &mut self.gpio_extint;
+            // the receiver may stop sending, that is not a stall of this task
+            let read = select4(
+                self.uart.read(buf.buf_unused_mut()),
+                commands.wait(),
+                config.changed(),
+                async move {
+                    match assisting {
+                        None => core::future::pending::<()>().await,
+                        Some(None) => (),
+                        Some(Some(deadline)) if tx_ready => {
+                            select(extint.wait_for_low(), Timer::at(deadline)).await;
+                        }
+                        Some(Some(..)) => Timer::after_millis(10).await,
+                    }
+                },
+            );
+            match heartbeat.idle(read).await {
+                Either4::First(Ok(len)) => buf.commit(len),
+                Either4::Second(Command::FactoryReset) => return State::FactoryReset,
+                Either4::Third(config) => {
+                    if let Err(e) = self.reconfigure(config, heartbeat).await {
+                        return self.fail(e.into());
+                    }
+                    // its UBX-MGA-ACK may have been read meanwhile, the frame has been processed
+                    // by now anyway
+                    if let Some(a) = &mut self.assistance {
+                        a.pending = None;
+                    }
+                    continue;
+                }
+                Either4::Fourth(()) => {
+                    if let Err(e) = self.assist_offline(assist_now, &mut i2c_buf).await {
+                        return self.fail(e.into());
+                    }
+                    continue;
+                }
+                Either4::First(Err(e)) => {
                     defmt::warn!("error while reading UART: {}", e);
                     errors += 1;
+                    self.health.uart_errors += 1;
+                    sender.send(Event::Health(self.health)).await;
                     continue;
                 }
             }
@@ -226,99 +1297,89 @@ where
                 );
 
                 match frame {
-                    // UBX-NAV-PVT
                     UbxFrame {
-                        class: 0x01,
-                        id: 0x07,
+                        class: NavPvt::CLASS,
+                        id: NavPvt::ID,
                         payload,
                     } => {
-                        if payload.len() != 92 {
+                        let Some(pvt) = NavPvt::parse(payload) else {
                             defmt::warn!("got UBX-NAV-PVT but wrong size: {}", payload.len());
                             continue;
-                        }
+                        };
+                        timing::record_pvt(&pvt);
 
-                        let itow =
-                            u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
-                        let year = u16::from_le_bytes([payload[4], payload[5]]);
-                        let month = payload[6];
-                        let day = payload[7];
-                        let hour = payload[8];
-                        let min = payload[9];
-                        let sec = payload[10];
-                        let gps_fix = payload[20];
-                        let flags = payload[21];
-                        let gspeed_cm_s = u32::from_le_bytes([
-                            payload[60],
-                            payload[61],
-                            payload[62],
-                            payload[63],
-                        ]);
-
-                        let ground_speed_meter_hour = gspeed_cm_s * 60 * 60 / 100;
-                        if let (Some(date), Some(time)) = (
-                            NaiveDate::from_ymd_opt(year.into(), month.into(), day.into()),
-                            NaiveTime::from_hms_milli_opt(
-                                hour.into(),
-                                min.into(),
-                                sec.into(),
-                                itow % 1000,
-                            ),
-                        ) {
-                            sender
-                                .send(Event::DateTimeAndVelocity {
-                                    datetime: date.and_time(time),
-                                    ground_speed_meter_hour,
-                                })
-                                .await;
+                        // the solution is not trusted until UBX-SEC-SIG of the same epoch, which
+                        // follows it, has told whether it is spoofed
+                        if !self.sec_sig {
+                            self.navigation_solution(sender, pvt).await;
+                        } else if let Some(pvt) = held_pvt.replace(pvt) {
+                            // no UBX-SEC-SIG since, as good as it gets
+                            self.navigation_solution(sender, pvt).await;
                         }
+                    }
+
+                    UbxFrame {
+                        class: NavTimeUtc::CLASS,
+                        id: NavTimeUtc::ID,
+                        payload,
+                    } => {
+                        let Some(utc) = NavTimeUtc::parse(payload) else {
+                            defmt::warn!("got UBX-NAV-TIMEUTC but wrong size: {}", payload.len());
+                            continue;
+                        };
 
-                        defmt::info!(
-                            "UBX-NAV-PVT: {} ms, {:04}-{:02}-{:02} {:02}:{:02}:{:02}, {} cm/s, {}.{:03} km/h, fix = {:#04x}, flags = {:#04x}",
-                            itow,
-                            year,
-                            month,
-                            day,
-                            hour,
-                            min,
-                            sec,
-                            gspeed_cm_s,
-                            ground_speed_meter_hour / 1000,
-                            ground_speed_meter_hour % 1000,
-                            gps_fix,
-                            flags
+                        defmt::debug!(
+                            "UBX-NAV-TIMEUTC: {} ms, +/- {} ns, valid = {:#04x}",
+                            utc.itow,
+                            utc.t_acc,
+                            utc.valid
                         );
+
+                        timing::record_time_utc(&utc);
                     }
 
-                    // UBX-TIM-TP
                     UbxFrame {
-                        class: 0x0d,
-                        id: 0x01,
+                        class: NavOdo::CLASS,
+                        id: NavOdo::ID,
                         payload,
                     } => {
-                        if payload.len() != 16 {
-                            defmt::warn!("got UBX-TIM-TP but wrong size: {}", payload.len());
+                        let Some(odo) = NavOdo::parse(payload) else {
+                            defmt::warn!("got UBX-NAV-ODO but wrong size: {}", payload.len());
                             continue;
-                        }
+                        };
 
-                        let towms =
-                            u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
-                        let towsubms =
-                            u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
-                        let week = u16::from_le_bytes([payload[12], payload[13]]);
-                        let flags = payload[14];
+                        defmt::debug!(
+                            "UBX-NAV-ODO: {} m, total = {} m, std = {} m",
+                            odo.distance,
+                            odo.total_distance,
+                            odo.distance_std
+                        );
+
+                        sender.send(Event::Odometer(odo)).await;
+                    }
+
+                    UbxFrame {
+                        class: TimTp::CLASS,
+                        id: TimTp::ID,
+                        payload,
+                    } => {
+                        let Some(tp) = TimTp::parse(payload) else {
+                            defmt::warn!("got UBX-TIM-TP but wrong size: {}", payload.len());
+                            continue;
+                        };
 
                         defmt::debug!(
                             "UBX-TIM-TP: {} ms, {} ms, week = {}, flags = {:#04x}",
-                            towms,
-                            towsubms,
-                            week,
-                            flags,
+                            tp.tow_ms,
+                            tp.tow_sub_ms,
+                            tp.week,
+                            tp.flags,
                         );
 
-                        if (flags & 0x03) == 0x03 && towsubms == 0 {
+                        if (tp.flags & 0x03) == 0x03 && tp.tow_sub_ms == 0 {
                             let datetime = GPS_EPOCH
-                                + TimeDelta::weeks(week.into())
-                                + TimeDelta::milliseconds(towms.into());
+                                + TimeDelta::weeks(tp.week.into())
+                                + TimeDelta::milliseconds(tp.tow_ms.into());
                             sender.send(Event::DateTimeNextPulse(datetime)).await;
 
                             defmt::info!(
@@ -333,6 +1394,110 @@ where
                         }
                     }
 
+                    UbxFrame {
+                        class: TimTm2::CLASS,
+                        id: TimTm2::ID,
+                        payload,
+                    } => {
+                        let Some(tm2) = TimTm2::parse(payload) else {
+                            defmt::warn!("got UBX-TIM-TM2 but wrong size: {}", payload.len());
+                            continue;
+                        };
+
+                        defmt::debug!(
+                            "UBX-TIM-TM2: #{}, flags = {:#04x}, +/- {} ns",
+                            tm2.count,
+                            tm2.flags,
+                            tm2.acc_est
+                        );
+
+                        // the time base follows the time grid of the time pulse
+                        if !tm2.time_valid() || tm2.time_base() != Some(TimeBase::Utc) {
+                            defmt::warn!("UBX-TIM-TM2: no valid UTC, ignored");
+                            continue;
+                        }
+                        let edge = |new: bool, rising, wn: u16, tow_ms: u32, tow_sub_ms_ns: u32| {
+                            new.then(|| ExternalEvent {
+                                datetime: GPS_EPOCH
+                                    + TimeDelta::weeks(wn.into())
+                                    + TimeDelta::milliseconds(tow_ms.into())
+                                    + TimeDelta::nanoseconds(tow_sub_ms_ns.into()),
+                                rising,
+                                count: tm2.count,
+                                acc_est_ns: tm2.acc_est,
+                            })
+                        };
+                        let mut events = [
+                            edge(
+                                tm2.new_rising_edge(),
+                                true,
+                                tm2.wn_r,
+                                tm2.tow_ms_r,
+                                tm2.tow_sub_ms_r,
+                            ),
+                            edge(
+                                tm2.new_falling_edge(),
+                                false,
+                                tm2.wn_f,
+                                tm2.tow_ms_f,
+                                tm2.tow_sub_ms_f,
+                            ),
+                        ];
+                        // both edges may be reported at once, in the order they happened
+                        events.sort_unstable_by_key(|e| e.map(|e| e.datetime));
+                        for event in events.into_iter().flatten() {
+                            sender.send(Event::ExternalEvent(event)).await;
+                        }
+                    }
+
+                    UbxFrame {
+                        class: MonRf::CLASS,
+                        id: MonRf::ID,
+                        payload,
+                    } => {
+                        let Some(rf) = MonRf::parse(payload).and_then(|rf| rf.blocks().next())
+                        else {
+                            defmt::warn!("got UBX-MON-RF but wrong size: {}", payload.len());
+                            continue;
+                        };
+
+                        defmt::debug!("UBX-MON-RF: {}", rf);
+
+                        if !sec_sig_jamming {
+                            self.update_interference(
+                                sender,
+                                rf.jamming_state(),
+                                self.health.spoofing,
+                            )
+                            .await;
+                        }
+                        sender.send(Event::Rf(rf)).await;
+                    }
+
+                    UbxFrame {
+                        class: SecSig::CLASS,
+                        id: SecSig::ID,
+                        payload,
+                    } => {
+                        let Some(sig) = SecSig::parse(payload) else {
+                            defmt::warn!("got UBX-SEC-SIG but wrong size: {}", payload.len());
+                            continue;
+                        };
+
+                        defmt::debug!("UBX-SEC-SIG: {}", sig);
+
+                        sec_sig_jamming = sig.jamming_detection_enabled();
+                        let jamming = match sec_sig_jamming {
+                            true => sig.jamming_state(),
+                            false => self.health.jamming,
+                        };
+                        self.update_interference(sender, jamming, sig.spoofing_state())
+                            .await;
+                        if let Some(pvt) = held_pvt.take() {
+                            self.navigation_solution(sender, pvt).await;
+                        }
+                    }
+
                     _ => (),
                 }
             }
@@ -0,0 +1,108 @@
+use config::envelope::Versioned;
+use ubx::nav::{NavOdo, NavPvt};
+
+pub use config::OdometerSource;
+
+/// Mean radius of the earth in meters.
+const EARTH_RADIUS_M: f32 = 6_371_000.0;
+
+/// The record is saved every time the total distance grows by this amount.
+const SAVE_INTERVAL_M: u32 = 1000;
+
+/// Persistent part of the odometer.
+#[derive(Copy, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize, defmt::Format)]
+pub struct OdometerRecord {
+    pub total_m: u32,
+    pub trip_m: u32,
+}
+
+impl Versioned for OdometerRecord {
+    const MAGIC: u32 = u32::from_le_bytes(*b"GODO");
+    const VERSION: u16 = 1;
+}
+
+pub struct Odometer {
+    record: OdometerRecord,
+    saved_total_m: u32,
+    fraction_m: f32,
+    last_position: Option<(i32, i32)>,
+    last_receiver_distance_m: Option<u32>,
+}
+
+impl Odometer {
+    pub fn new(record: OdometerRecord) -> Self {
+        Self {
+            record,
+            saved_total_m: record.total_m,
+            fraction_m: 0.0,
+            last_position: None,
+            last_receiver_distance_m: None,
+        }
+    }
+
+    pub fn record(&self) -> OdometerRecord {
+        self.record
+    }
+
+    pub fn reset_trip(&mut self) {
+        self.record.trip_m = 0;
+    }
+
+    /// Returns the record if it is time to save it, and assumes that it will be saved.
+    pub fn take_record_to_save(&mut self) -> Option<OdometerRecord> {
+        if self.record.total_m.wrapping_sub(self.saved_total_m) < SAVE_INTERVAL_M {
+            return None;
+        }
+        self.saved_total_m = self.record.total_m;
+        Some(self.record)
+    }
+
+    /// Accumulates the distance from the last position. Positions are ignored while stationary so
+    /// that the jitter of the solution does not add up.
+    pub fn update_position(&mut self, pvt: &NavPvt, moving: bool) {
+        if !pvt.gnss_fix_ok() {
+            self.last_position = None;
+            return;
+        }
+        if let (true, Some((lat, lon))) = (moving, self.last_position) {
+            self.add(distance_m(lat, lon, pvt.lat, pvt.lon));
+        }
+        self.last_position = Some((pvt.lat, pvt.lon));
+    }
+
+    /// Accumulates the increase of `distance` in UBX-NAV-ODO, which restarts from 0 every time the
+    /// receiver is reset.
+    pub fn update_receiver_distance(&mut self, odo: &NavOdo) {
+        if let Some(last) = self.last_receiver_distance_m
+            && odo.distance >= last
+        {
+            self.add((odo.distance - last) as f32);
+        }
+        self.last_receiver_distance_m = Some(odo.distance);
+    }
+
+    fn add(&mut self, distance_m: f32) {
+        self.fraction_m += distance_m;
+        let m = self.fraction_m as u32;
+        self.fraction_m -= m as f32;
+        self.record.total_m = self.record.total_m.wrapping_add(m);
+        self.record.trip_m = self.record.trip_m.wrapping_add(m);
+    }
+}
+
+/// Equirectangular approximation, which is accurate enough for the distance between successive
+/// solutions.
+pub(crate) fn distance_m(lat1: i32, lon1: i32, lat2: i32, lon2: i32) -> f32 {
+    const RAD_PER_UNIT: f32 = core::f32::consts::PI / 180.0 / 1e7;
+    const HALF_TURN: i64 = 180 * 10_000_000;
+    let dlat = (i64::from(lat2) - i64::from(lat1)) as f32 * RAD_PER_UNIT;
+    let dlon = match i64::from(lon2) - i64::from(lon1) {
+        d if d > HALF_TURN => d - 2 * HALF_TURN,
+        d if d < -HALF_TURN => d + 2 * HALF_TURN,
+        d => d,
+    } as f32
+        * RAD_PER_UNIT;
+    let mean_lat = (lat1 as f32 + lat2 as f32) / 2.0 * RAD_PER_UNIT;
+    let x = dlon * libm::cosf(mean_lat);
+    EARTH_RADIUS_M * libm::sqrtf(x * x + dlat * dlat)
+}
@@ -0,0 +1,64 @@
+use config::envelope::Versioned;
+use ubx::nav::NavPvt;
+
+use crate::odometer::distance_m;
+
+/// The record is saved every time the position moves by this distance from the saved one. The
+/// receiver is told that it may be off by much more anyway.
+const SAVE_DISTANCE_M: f32 = 10_000.0;
+
+#[derive(Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize, defmt::Format)]
+pub struct Position {
+    /// 1e-7 deg
+    pub lat: i32,
+    /// 1e-7 deg
+    pub lon: i32,
+    /// mm above the mean sea level
+    pub h_msl: i32,
+}
+
+impl Position {
+    pub fn from_pvt(pvt: &NavPvt) -> Option<Self> {
+        pvt.gnss_fix_ok().then_some(Self {
+            lat: pvt.lat,
+            lon: pvt.lon,
+            h_msl: pvt.h_msl,
+        })
+    }
+}
+
+/// Last position fixed, to help the receiver to find the satellites after a power cycle.
+#[derive(Copy, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize, defmt::Format)]
+pub struct PositionRecord {
+    /// `None` until the first fix
+    pub last: Option<Position>,
+}
+
+impl Versioned for PositionRecord {
+    const MAGIC: u32 = u32::from_le_bytes(*b"GPOS");
+    const VERSION: u16 = 1;
+}
+
+pub struct PositionTracker {
+    saved: Option<Position>,
+}
+
+impl PositionTracker {
+    pub fn new(record: PositionRecord) -> Self {
+        Self { saved: record.last }
+    }
+
+    /// Returns the record if `position` is far enough from the saved one, and assumes that it will
+    /// be saved.
+    pub fn take_record_to_save(&mut self, position: Position) -> Option<PositionRecord> {
+        if let Some(saved) = self.saved
+            && distance_m(saved.lat, saved.lon, position.lat, position.lon) < SAVE_DISTANCE_M
+        {
+            return None;
+        }
+        self.saved = Some(position);
+        Some(PositionRecord {
+            last: Some(position),
+        })
+    }
+}
@@ -0,0 +1,998 @@
+use core::fmt::{self, Write as _};
+
+use embassy_futures::{
+    select::{Either, select},
+    yield_now,
+};
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex,
+    channel::{DynamicReceiver, DynamicSender},
+    signal::Signal,
+    watch::{DynAnonReceiver, DynReceiver},
+};
+use embassy_time::{Duration, Instant, with_timeout};
+use embassy_usb::{
+    class::cdc_acm::{self, CdcAcmClass},
+    driver::{Driver, EndpointError},
+};
+
+use misc::{crlf_stream::CrlfStream, log_store::Cursor};
+use ubx::{
+    mon::{AntennaStatus, JammingState},
+    sec::SpoofingState,
+};
+
+use crate::assist_now;
+use crate::config::{
+    AutoRotate, Config, Constellation, Constellations, DisplayMode, ExtInt, MAX_ROTATION_LEN,
+    PowerSave, Rate, ReceiverStorage, Rotation, TimePulse,
+};
+use crate::fault_log::{self, MAX_ENTRY_LEN};
+use crate::flash::MAX_WRITE_DELAY;
+use crate::max_m10s::{self, CLOCK_TOLERANCE_PPM, ExternalEvent, HealthState};
+use crate::odometer::OdometerSource;
+use crate::speed::SpeedUnit;
+use crate::status::{ConfigStore, Status};
+use crate::timing::{self, WINDOW as TIMING_WINDOW};
+use crate::watchdog::Heartbeat;
+
+const MAX_PACKET_SIZE: usize = 64;
+
+/// Most bytes uploaded by a line of `assistnow data`, i.e. `xxd -p -c 128`
+const MAX_DATA_PER_LINE: usize = 128;
+
+/// How long after a time pulse the AssistNow data may be written, the sector erases included
+const FLASH_WINDOW: Duration = Duration::from_millis(500);
+
+const HELP: &[(&str, &str)] = &[
+    ("help", "show this message"),
+    (
+        "config [export | import <json> | status]",
+        "show, load or check the saving of the whole config",
+    ),
+    (
+        "rotation [<mode>...]",
+        "show or set the modes cycled by SW3",
+    ),
+    (
+        "auto-rotate [off | <mode> <duration> <period>]",
+        "show or set the mode shown periodically in the time mode",
+    ),
+    ("speed [reset]", "show or reset the speed statistics"),
+    ("odometer [reset]", "show the odometer or reset the trip"),
+    (
+        "odometer-source [positions | receiver]",
+        "show or set how the distance is measured",
+    ),
+    (
+        "speed-unit [kmh | mph | knots | mps]",
+        "show or set the unit of speed",
+    ),
+    (
+        "constellations [<gnss>...]",
+        "show or set the GNSS tracked by the receiver",
+    ),
+    (
+        "rate [<measurement-hz> [<navigation-hz>]]",
+        "show or set how often the receiver measures and computes the solution",
+    ),
+    (
+        "power-save [off | cyclic-tracking | on-off <period>]",
+        "show or set the power save mode of the receiver",
+    ),
+    (
+        "time-pulse [<cable-delay-ns> [<user-delay-ns>]]",
+        "show or set the delays compensated by the time pulse",
+    ),
+    (
+        "ext-int [tx-ready | timestamp]",
+        "show or set whether EXTINT of the receiver is TX_READY or timestamps its edges",
+    ),
+    (
+        "receiver-storage [ram | bbr | flash]",
+        "show or set where the receiver keeps its configuration over resets",
+    ),
+    (
+        "receiver",
+        "show the health, the antenna and the firmware version of the GNSS receiver",
+    ),
+    (
+        "timing [clear]",
+        "show or start over the statistics of the time accuracy and the time pulse",
+    ),
+    (
+        "assistnow [begin | data <hex> | end | clear]",
+        "show or upload the AssistNow data sent to the receiver after each reset",
+    ),
+    ("faults [clear]", "show or clear the log of the faults"),
+    (
+        "factory-reset",
+        "restore the default config of the clock and the receiver, and reboot",
+    ),
+];
+
+pub enum Request {
+    UpdateConfig(ConfigUpdate),
+    ResetSpeedStats,
+    ResetTrip,
+}
+
+/// A change of the config, applied by the main loop to its current config so that neither the
+/// changes made with the switches nor those queued before it are lost.
+#[derive(Clone, Copy)]
+pub enum ConfigUpdate {
+    Import(Config),
+    Rotation(Rotation),
+    AutoRotate(Option<AutoRotate>),
+    OdometerSource(OdometerSource),
+    SpeedUnit(SpeedUnit),
+    Constellations(Constellations),
+    Rate(Rate),
+    TimePulse(TimePulse),
+    PowerSave(PowerSave),
+    ExtInt(ExtInt),
+    ReceiverStorage(ReceiverStorage),
+}
+
+impl ConfigUpdate {
+    pub fn apply(self, cfg: &mut Config) {
+        match self {
+            Self::Import(c) => *cfg = c,
+            Self::Rotation(r) => cfg.rotation = r,
+            Self::AutoRotate(a) => cfg.auto_rotate = a,
+            Self::OdometerSource(s) => cfg.odometer_source = s,
+            Self::SpeedUnit(u) => cfg.speed_unit = u,
+            Self::Constellations(c) => cfg.constellations = c,
+            Self::Rate(r) => cfg.rate = r,
+            Self::TimePulse(t) => cfg.time_pulse = t,
+            Self::PowerSave(p) => cfg.power_save = p,
+            Self::ExtInt(e) => cfg.ext_int = e,
+            Self::ReceiverStorage(s) => cfg.receiver_storage = s,
+        }
+    }
+}
+
+/// Line-based command interface over USB CDC-ACM. Each command has to be terminated by CRLF.
+/// The edges timestamped on EXTINT are printed as they come.
+pub struct Shell<'d, D>
+where
+    D: Driver<'d>,
+{
+    tx: cdc_acm::Sender<'d, D>,
+    rx: cdc_acm::Receiver<'d, D>,
+    requests: DynamicSender<'d, Request>,
+    replaced: &'d Signal<ThreadModeRawMutex, Config>,
+    factory_reset: DynamicSender<'d, ()>,
+    config: DynAnonReceiver<'d, Config>,
+    status: DynAnonReceiver<'d, Status>,
+    faults: &'d dyn fault_log::Reader,
+    assist_now: &'d dyn assist_now::Storage,
+    external_events: DynamicReceiver<'d, ExternalEvent>,
+    time_pulse: DynReceiver<'d, ()>,
+    heartbeat: &'d Heartbeat,
+    /// When the last time pulse was waited for
+    pulse_at: Option<Instant>,
+}
+
+impl<'d, D> Shell<'d, D>
+where
+    D: Driver<'d>,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        class: CdcAcmClass<'d, D>,
+        requests: DynamicSender<'d, Request>,
+        replaced: &'d Signal<ThreadModeRawMutex, Config>,
+        factory_reset: DynamicSender<'d, ()>,
+        config: DynAnonReceiver<'d, Config>,
+        status: DynAnonReceiver<'d, Status>,
+        faults: &'d dyn fault_log::Reader,
+        assist_now: &'d dyn assist_now::Storage,
+        external_events: DynamicReceiver<'d, ExternalEvent>,
+        time_pulse: DynReceiver<'d, ()>,
+        heartbeat: &'d Heartbeat,
+    ) -> Self {
+        let (tx, rx) = class.split();
+        Self {
+            tx,
+            rx,
+            requests,
+            replaced,
+            factory_reset,
+            config,
+            status,
+            faults,
+            assist_now,
+            external_events,
+            time_pulse,
+            heartbeat,
+            pulse_at: None,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            self.heartbeat.idle(self.rx.wait_connection()).await;
+            defmt::info!("shell: connected");
+            // only those since the connection
+            while self.external_events.try_receive().is_ok() {}
+            if let Err(e) = self.serve().await {
+                defmt::info!("shell: disconnected ({})", e);
+            }
+        }
+    }
+
+    async fn serve(&mut self) -> Result<(), EndpointError> {
+        // long enough for `config import`
+        let mut stream = CrlfStream::<512>::new();
+        loop {
+            let mut packet = [0; MAX_PACKET_SIZE];
+            let len = match self
+                .heartbeat
+                .idle(select(
+                    self.rx.read_packet(&mut packet),
+                    self.external_events.receive(),
+                ))
+                .await
+            {
+                Either::First(len) => len?,
+                Either::Second(event) => {
+                    self.print_external_event(&event).await?;
+                    continue;
+                }
+            };
+            let len = len.min(stream.buf_unused_mut().len());
+            stream.buf_unused_mut()[..len].copy_from_slice(&packet[..len]);
+            stream.commit(len);
+
+            while let Some(line) = stream.pop() {
+                match core::str::from_utf8(line) {
+                    Ok(line) => self.execute(line.trim()).await?,
+                    Err(..) => self.println(format_args!("error: invalid UTF-8")).await?,
+                }
+            }
+
+            if stream.buf_unused_mut().is_empty() {
+                stream.consume(stream.buf_filled().len());
+                self.println(format_args!("error: line too long")).await?;
+            }
+        }
+    }
+
+    async fn execute(&mut self, line: &str) -> Result<(), EndpointError> {
+        let mut args = line.split_ascii_whitespace();
+        let Some(command) = args.next() else {
+            return Ok(());
+        };
+        let Some(mut cfg) = self.config.try_get() else {
+            return self.println(format_args!("error: not ready")).await;
+        };
+
+        match command {
+            "help" => {
+                for (usage, description) in HELP {
+                    self.println(format_args!("{:<48}{}", usage, description))
+                        .await?;
+                }
+            }
+
+            "config" => match args.next() {
+                None | Some("export") => {
+                    let mut buf = [0; 512];
+                    match config::text::export(&cfg, &mut buf) {
+                        Ok(text) => self.println(format_args!("{}", text)).await?,
+                        Err(e) => self.println(format_args!("error: {:?}", e)).await?,
+                    }
+                }
+                Some("import") => {
+                    let (_, text) = line.split_once("import").unwrap_or_default();
+                    let new = match config::text::import(text) {
+                        Ok(new) => new,
+                        Err(e) => return self.println(format_args!("error: {:?}", e)).await,
+                    };
+                    // against the config the import replaced, the switches may have changed it since
+                    self.replaced.reset();
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::Import(new)))
+                        .await;
+                    let old = self.replaced.wait().await;
+                    let mut diff = heapless::String::<512>::new();
+                    let result = config::text::diff(&old, &new, |name, old, new| {
+                        let _ = write!(diff, "{}: {} -> {}\r\n", name, old, new);
+                    });
+                    if let Err(e) = result {
+                        return self.println(format_args!("error: {:?}", e)).await;
+                    }
+                    if diff.is_empty() {
+                        return self.println(format_args!("config: no changes")).await;
+                    }
+                    self.println(format_args!("{}config: imported", diff))
+                        .await?;
+                }
+                Some("status") => {
+                    let status = self.status.try_get().unwrap_or_default();
+                    let state = match status.config_store {
+                        ConfigStore::Saved => "saved",
+                        ConfigStore::Pending => "pending",
+                        ConfigStore::Failed => "failed",
+                    };
+                    self.println(format_args!("config: {}", state)).await?;
+                }
+                Some(..) => {
+                    self.println(format_args!("error: invalid arguments"))
+                        .await?
+                }
+            },
+
+            "rotation" => {
+                let mut modes = [DisplayMode::Time; MAX_ROTATION_LEN];
+                let mut len = 0;
+                for name in args {
+                    let Some(mode) = DisplayMode::from_name(name) else {
+                        return self
+                            .println(format_args!("error: unknown mode: {}", name))
+                            .await;
+                    };
+                    if len == MAX_ROTATION_LEN {
+                        return self.println(format_args!("error: too many modes")).await;
+                    }
+                    modes[len] = mode;
+                    len += 1;
+                }
+                if len > 0 {
+                    let Some(rotation) = Rotation::new(&modes[..len]) else {
+                        return self.println(format_args!("error: invalid rotation")).await;
+                    };
+                    cfg.rotation = rotation;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::Rotation(rotation)))
+                        .await;
+                }
+                self.print_rotation(&cfg.rotation).await?;
+            }
+
+            "auto-rotate" => {
+                match (args.next(), args.next(), args.next()) {
+                    (None, ..) => (),
+                    (Some("off"), None, None) => {
+                        cfg.auto_rotate = None;
+                        self.requests
+                            .send(Request::UpdateConfig(ConfigUpdate::AutoRotate(None)))
+                            .await;
+                    }
+                    (Some(mode), Some(duration), Some(period)) => {
+                        let auto_rotate = DisplayMode::from_name(mode).and_then(|mode| {
+                            AutoRotate::new(mode, duration.parse().ok()?, period.parse().ok()?)
+                        });
+                        let Some(auto_rotate) = auto_rotate else {
+                            return self
+                                .println(format_args!("error: invalid auto-rotate"))
+                                .await;
+                        };
+                        cfg.auto_rotate = Some(auto_rotate);
+                        self.requests
+                            .send(Request::UpdateConfig(ConfigUpdate::AutoRotate(Some(
+                                auto_rotate,
+                            ))))
+                            .await;
+                    }
+                    _ => return self.println(format_args!("error: invalid arguments")).await,
+                }
+                self.print_auto_rotate(&cfg.auto_rotate).await?;
+            }
+
+            "speed" => {
+                match args.next() {
+                    None => (),
+                    Some("reset") => {
+                        self.requests.send(Request::ResetSpeedStats).await;
+                        return self.println(format_args!("speed: reset")).await;
+                    }
+                    Some(..) => {
+                        return self.println(format_args!("error: invalid arguments")).await;
+                    }
+                }
+                let status = self.status.try_get().unwrap_or_default();
+                let unit = cfg.speed_unit;
+                if let Some(speed) = status.ground_speed_mm_s {
+                    self.print_speed("current", speed, unit).await?;
+                }
+                self.print_speed("max", status.speed_stats.max_mm_s(), unit)
+                    .await?;
+                self.print_speed("average", status.speed_stats.average_mm_s(), unit)
+                    .await?;
+            }
+
+            "odometer" => {
+                match args.next() {
+                    None => (),
+                    Some("reset") => {
+                        self.requests.send(Request::ResetTrip).await;
+                        return self.println(format_args!("trip: reset")).await;
+                    }
+                    Some(..) => {
+                        return self.println(format_args!("error: invalid arguments")).await;
+                    }
+                }
+                let status = self.status.try_get().unwrap_or_default();
+                let unit = cfg.speed_unit;
+                self.print_distance("trip", status.odometer.trip_m, unit)
+                    .await?;
+                self.print_distance("total", status.odometer.total_m, unit)
+                    .await?;
+                match status.heading_1e5_deg {
+                    Some(h) => {
+                        let h = h.rem_euclid(360_00000) / 1000;
+                        self.println(format_args!("heading: {}.{:02} deg", h / 100, h % 100))
+                            .await?
+                    }
+                    None => self.println(format_args!("heading: -")).await?,
+                }
+            }
+
+            "odometer-source" => {
+                if let Some(name) = args.next() {
+                    let Some(source) = OdometerSource::from_name(name) else {
+                        return self
+                            .println(format_args!("error: unknown source: {}", name))
+                            .await;
+                    };
+                    cfg.odometer_source = source;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::OdometerSource(source)))
+                        .await;
+                }
+                self.println(format_args!(
+                    "odometer-source: {}",
+                    cfg.odometer_source.name()
+                ))
+                .await?;
+            }
+
+            "speed-unit" => {
+                if let Some(name) = args.next() {
+                    let Some(unit) = SpeedUnit::from_name(name) else {
+                        return self
+                            .println(format_args!("error: unknown unit: {}", name))
+                            .await;
+                    };
+                    cfg.speed_unit = unit;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::SpeedUnit(unit)))
+                        .await;
+                }
+                self.println(format_args!("speed-unit: {}", cfg.speed_unit.name()))
+                    .await?;
+            }
+
+            "constellations" => {
+                let mut constellations = Constellations {
+                    gps: false,
+                    galileo: false,
+                    beidou: false,
+                    glonass: false,
+                    qzss: false,
+                    sbas: false,
+                };
+                let mut any = false;
+                for name in args {
+                    let Some(c) = Constellation::from_name(name) else {
+                        return self
+                            .println(format_args!("error: unknown constellation: {}", name))
+                            .await;
+                    };
+                    constellations.set(c, true);
+                    any = true;
+                }
+                if any {
+                    if !constellations.is_valid() {
+                        return self
+                            .println(format_args!(
+                                "error: at least one of gps, galileo, beidou and glonass is required"
+                            ))
+                            .await;
+                    }
+                    cfg.constellations = constellations;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::Constellations(
+                            constellations,
+                        )))
+                        .await;
+                }
+                let mut line = heapless::String::<64>::new();
+                for c in Constellation::ALL {
+                    if cfg.constellations.contains(c) {
+                        let _ = write!(line, " {}", c.name());
+                    }
+                }
+                self.println(format_args!("constellations:{}", line))
+                    .await?;
+            }
+
+            "rate" => {
+                if let Some(measurement) = args.next() {
+                    let navigation = args.next().unwrap_or(measurement);
+                    let rate = measurement
+                        .parse()
+                        .ok()
+                        .zip(navigation.parse().ok())
+                        .and_then(|(m, n)| Rate::new(m, n));
+                    let Some(rate) = rate else {
+                        return self
+                            .println(format_args!(
+                                "error: invalid rate (1, 2, 4, 5, 8 or 10 Hz, measurements divisible by solutions)"
+                            ))
+                            .await;
+                    };
+                    cfg.rate = rate;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::Rate(rate)))
+                        .await;
+                }
+                self.println(format_args!(
+                    "rate: {} Hz measurements, {} Hz solutions",
+                    cfg.rate.measurement_hz, cfg.rate.navigation_hz
+                ))
+                .await?;
+            }
+
+            "time-pulse" => {
+                if let Some(cable) = args.next() {
+                    let user = args.next().unwrap_or("0");
+                    let time_pulse = cable
+                        .parse()
+                        .ok()
+                        .zip(user.parse().ok())
+                        .map(|(cable_delay_ns, user_delay_ns)| TimePulse {
+                            cable_delay_ns,
+                            user_delay_ns,
+                        })
+                        .filter(TimePulse::is_valid);
+                    let Some(time_pulse) = time_pulse else {
+                        return self
+                            .println(format_args!(
+                                "error: invalid delays (cable 0 to {} ns, user up to +/- {} ns)",
+                                TimePulse::MAX_CABLE_DELAY_NS,
+                                TimePulse::MAX_USER_DELAY_NS
+                            ))
+                            .await;
+                    };
+                    cfg.time_pulse = time_pulse;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::TimePulse(time_pulse)))
+                        .await;
+                }
+                self.println(format_args!(
+                    "time-pulse: rising edge on each second of UTC, {} ns cable delay, {} ns user delay",
+                    cfg.time_pulse.cable_delay_ns, cfg.time_pulse.user_delay_ns
+                ))
+                .await?;
+            }
+
+            "power-save" => {
+                if let Some(name) = args.next() {
+                    let power_save = match (name, args.next()) {
+                        ("off", None) => Some(PowerSave::Off),
+                        ("cyclic-tracking", None) => Some(PowerSave::CyclicTracking),
+                        ("on-off", Some(period)) => period
+                            .parse()
+                            .ok()
+                            .map(|period_secs| PowerSave::OnOff { period_secs })
+                            .filter(PowerSave::is_valid),
+                        _ => None,
+                    };
+                    let Some(power_save) = power_save else {
+                        return self
+                            .println(format_args!(
+                                "error: invalid mode (on-off every {} to {} seconds)",
+                                PowerSave::MIN_PERIOD_SECS,
+                                PowerSave::MAX_PERIOD_SECS
+                            ))
+                            .await;
+                    };
+                    cfg.power_save = power_save;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::PowerSave(power_save)))
+                        .await;
+                }
+                let drift_us = u32::from(cfg.power_save.fix_interval_secs()) * CLOCK_TOLERANCE_PPM;
+                match cfg.power_save {
+                    PowerSave::OnOff { period_secs } => {
+                        self.println(format_args!(
+                            "power-save: on-off, a fix every {} s (the clock may drift by {}.{} ms in between)",
+                            period_secs,
+                            drift_us / 1000,
+                            drift_us % 1000 / 100
+                        ))
+                        .await?
+                    }
+                    p => {
+                        self.println(format_args!("power-save: {}", p.name()))
+                            .await?
+                    }
+                }
+            }
+
+            "ext-int" => {
+                if let Some(name) = args.next() {
+                    let Some(ext_int) = ExtInt::from_name(name) else {
+                        return self
+                            .println(format_args!("error: unknown ext-int: {}", name))
+                            .await;
+                    };
+                    cfg.ext_int = ext_int;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::ExtInt(ext_int)))
+                        .await;
+                }
+                self.println(format_args!("ext-int: {}", cfg.ext_int.name()))
+                    .await?;
+            }
+
+            "receiver-storage" => {
+                if let Some(name) = args.next() {
+                    let Some(storage) = ReceiverStorage::from_name(name) else {
+                        return self
+                            .println(format_args!("error: unknown storage: {}", name))
+                            .await;
+                    };
+                    cfg.receiver_storage = storage;
+                    self.requests
+                        .send(Request::UpdateConfig(ConfigUpdate::ReceiverStorage(
+                            storage,
+                        )))
+                        .await;
+                }
+                self.println(format_args!(
+                    "receiver-storage: {}",
+                    cfg.receiver_storage.name()
+                ))
+                .await?;
+            }
+
+            "receiver" => {
+                let status = self.status.try_get().unwrap_or_default();
+                let health = status.receiver;
+                let state = match health.state {
+                    HealthState::Starting => "starting",
+                    HealthState::Ready => "ready",
+                    HealthState::Retrying => "retrying",
+                    HealthState::Failed => "failed",
+                };
+                self.println(format_args!(
+                    "receiver: {} ({} consecutive failures)",
+                    state, health.failures
+                ))
+                .await?;
+                self.println(format_args!(
+                    "power cycles: {}, I2C errors: {}, UART errors: {}, ACK-NAKs: {}",
+                    health.power_cycles, health.i2c_errors, health.uart_errors, health.ack_naks
+                ))
+                .await?;
+                if let Some(fault) = health.last_fault {
+                    self.println(format_args!("last fault: {}", fault.description()))
+                        .await?;
+                }
+                if let Some(rf) = status.rf {
+                    let antenna = match rf.antenna_status() {
+                        AntennaStatus::Init => "initializing",
+                        AntennaStatus::DontKnow => "unknown",
+                        AntennaStatus::Ok => "OK",
+                        AntennaStatus::Short => "short circuit",
+                        AntennaStatus::Open => "open circuit",
+                    };
+                    self.println(format_args!("antenna: {}", antenna)).await?;
+                    self.println(format_args!(
+                        "jamming indicator: {}/255, noise: {}, AGC: {}/8191",
+                        rf.jam_ind, rf.noise_per_ms, rf.agc_cnt
+                    ))
+                    .await?;
+                }
+                let jamming = match health.jamming {
+                    JammingState::Unknown => "unknown",
+                    JammingState::Ok => "none",
+                    JammingState::Warning => "warning",
+                    JammingState::Critical => "critical",
+                };
+                let spoofing = match health.spoofing {
+                    SpoofingState::Unknown => "unknown",
+                    SpoofingState::Ok => "none",
+                    SpoofingState::Indicated => "indicated (the time is held over)",
+                    SpoofingState::Affirmed => "affirmed (the time is held over)",
+                };
+                self.println(format_args!("jamming: {}, spoofing: {}", jamming, spoofing))
+                    .await?;
+                if let Some(version) = max_m10s::version() {
+                    let version = version.decode();
+                    self.println(format_args!("software: {}", version.sw_version))
+                        .await?;
+                    self.println(format_args!("hardware: {}", version.hw_version))
+                        .await?;
+                    for extension in version.extensions() {
+                        self.println(format_args!("  {}", extension)).await?;
+                    }
+                }
+            }
+
+            "timing" => match args.next() {
+                None => self.print_timing().await?,
+                Some("clear") => {
+                    timing::clear();
+                    self.println(format_args!("timing: cleared")).await?
+                }
+                Some(..) => {
+                    self.println(format_args!("error: invalid arguments"))
+                        .await?
+                }
+            },
+
+            // nothing is printed for `data` unless it fails, the host may not be reading
+            "assistnow" => match (args.next(), args.next(), args.next()) {
+                (None, ..) => match self.assist_now.summary() {
+                    Ok(Some(s)) => {
+                        self.println(format_args!(
+                            "assistnow: {} frames, {} bytes",
+                            s.frames, s.len
+                        ))
+                        .await?
+                    }
+                    Ok(None) => self.println(format_args!("assistnow: none")).await?,
+                    Err(e) => self.println(format_args!("error: {:?}", e)).await?,
+                },
+                (Some("begin"), None, _) => {
+                    self.wait_for_time_pulse().await;
+                    match self.assist_now.begin() {
+                        Ok(()) => self.println(format_args!("assistnow: ready")).await?,
+                        Err(e) => self.println(format_args!("error: {:?}", e)).await?,
+                    }
+                }
+                (Some("data"), Some(hex), None) => {
+                    let mut buf = [0; MAX_DATA_PER_LINE];
+                    let Some(data) = decode_hex(hex, &mut buf) else {
+                        return self.println(format_args!("error: invalid data")).await;
+                    };
+                    self.wait_for_time_pulse().await;
+                    if let Err(e) = self.assist_now.write(data) {
+                        self.println(format_args!("error: {:?}", e)).await?;
+                    }
+                    // the lines may be queued already, let the others run between the erases
+                    yield_now().await;
+                }
+                (Some("end"), None, _) => {
+                    let mut check = assist_now::Check::default();
+                    let result = loop {
+                        self.wait_for_time_pulse().await;
+                        match self.assist_now.finish(&mut check) {
+                            Ok(Some(s)) => break Ok(s),
+                            Ok(None) => yield_now().await,
+                            Err(e) => break Err(e),
+                        }
+                    };
+                    match result {
+                        Ok(s) => {
+                            self.println(format_args!(
+                                "assistnow: {} frames, {} bytes saved",
+                                s.frames, s.len
+                            ))
+                            .await?
+                        }
+                        Err(e) => self.println(format_args!("error: {:?}", e)).await?,
+                    }
+                }
+                (Some("clear"), None, _) => {
+                    self.wait_for_time_pulse().await;
+                    match self.assist_now.clear() {
+                        Ok(()) => self.println(format_args!("assistnow: cleared")).await?,
+                        Err(e) => self.println(format_args!("error: {:?}", e)).await?,
+                    }
+                }
+                _ => {
+                    self.println(format_args!("error: invalid arguments"))
+                        .await?
+                }
+            },
+
+            "faults" => match args.next() {
+                None => {
+                    let mut cursor = Cursor::default();
+                    let mut n = 0;
+                    loop {
+                        let mut buf = [0; MAX_ENTRY_LEN];
+                        match self.faults.read_next(&mut cursor, &mut buf) {
+                            Ok(Some(entry)) => self.println(format_args!("{}", entry)).await?,
+                            Ok(None) => break,
+                            Err(e) => return self.println(format_args!("error: {:?}", e)).await,
+                        }
+                        n += 1;
+                    }
+                    self.println(format_args!("faults: {}", n)).await?;
+                }
+                Some("clear") => match self.faults.clear() {
+                    Ok(()) => self.println(format_args!("faults: cleared")).await?,
+                    Err(e) => self.println(format_args!("error: {:?}", e)).await?,
+                },
+                Some(..) => {
+                    self.println(format_args!("error: invalid arguments"))
+                        .await?
+                }
+            },
+
+            "factory-reset" => {
+                if args.next().is_some() {
+                    return self.println(format_args!("error: invalid arguments")).await;
+                }
+                self.println(format_args!("factory-reset: rebooting"))
+                    .await?;
+                self.factory_reset.send(()).await;
+            }
+
+            _ => {
+                self.println(format_args!(
+                    "error: unknown command: {} (try 'help')",
+                    command
+                ))
+                .await?
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn print_rotation(&mut self, rotation: &Rotation) -> Result<(), EndpointError> {
+        let mut line = heapless::String::<128>::new();
+        for mode in rotation.as_slice() {
+            let _ = write!(line, " {}", mode.name());
+        }
+        self.println(format_args!("rotation:{}", line)).await
+    }
+
+    async fn print_auto_rotate(
+        &mut self,
+        auto_rotate: &Option<AutoRotate>,
+    ) -> Result<(), EndpointError> {
+        match auto_rotate {
+            Some(a) => {
+                self.println(format_args!(
+                    "auto-rotate: {} {} {}",
+                    a.mode.name(),
+                    a.duration_secs,
+                    a.period_secs
+                ))
+                .await
+            }
+            None => self.println(format_args!("auto-rotate: off")).await,
+        }
+    }
+
+    async fn print_speed(
+        &mut self,
+        label: &str,
+        speed_mm_s: u32,
+        unit: SpeedUnit,
+    ) -> Result<(), EndpointError> {
+        let speed = unit.from_mm_s(speed_mm_s);
+        self.println(format_args!(
+            "{}: {}.{:03} {}",
+            label,
+            speed / 1000,
+            speed % 1000,
+            unit.name()
+        ))
+        .await
+    }
+
+    async fn print_distance(
+        &mut self,
+        label: &str,
+        distance_m: u32,
+        unit: SpeedUnit,
+    ) -> Result<(), EndpointError> {
+        let distance = unit.distance_from_m(distance_m);
+        self.println(format_args!(
+            "{}: {}.{:03} {}",
+            label,
+            distance / 1000,
+            distance % 1000,
+            unit.distance_name()
+        ))
+        .await
+    }
+
+    async fn print_external_event(&mut self, event: &ExternalEvent) -> Result<(), EndpointError> {
+        self.println(format_args!(
+            "event: {} UTC {} #{} (+/- {} ns)",
+            event.datetime,
+            if event.rising { "rising" } else { "falling" },
+            event.count,
+            event.acc_est_ns
+        ))
+        .await
+    }
+
+    async fn print_timing(&mut self) -> Result<(), EndpointError> {
+        let report = timing::report();
+        let mut line = heapless::String::<128>::new();
+        let _ = line.push_str("time accuracy:");
+        for (t_acc_ns, source) in [
+            (report.pvt_t_acc_ns, "UBX-NAV-PVT"),
+            (report.utc_t_acc_ns, "UBX-NAV-TIMEUTC"),
+        ] {
+            let _ = match t_acc_ns {
+                Some(ns) => write!(line, " {} ns ({})", ns, source),
+                None => write!(line, " - ({})", source),
+            };
+        }
+        self.println(format_args!("{}", line)).await?;
+        if let Some(s) = report.utc_t_acc {
+            self.println(format_args!(
+                "  last {} of UBX-NAV-TIMEUTC: min {} ns, max {} ns, mean {} ns",
+                s.count, s.min, s.max, s.mean
+            ))
+            .await?;
+        }
+        match report.pulse_error {
+            Some(s) => {
+                self.println(format_args!(
+                    "time pulse interval - 1 s, last {} of {}: min {} ns, max {} ns, mean {} ns, jitter {} ns",
+                    s.count,
+                    TIMING_WINDOW,
+                    s.min,
+                    s.max,
+                    s.mean,
+                    s.max.abs_diff(s.min)
+                ))
+                .await?
+            }
+            None => self.println(format_args!("time pulse interval: -")).await?,
+        }
+        if let Some(adev) = report.pulse_adev {
+            self.println(format_args!(
+                "time pulse Allan deviation (1 s): {}e-9",
+                adev
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// The flash stalls the execution while erasing, so it is written right after the display has
+    /// been refreshed by a time pulse as [`crate::flash::BackgroundWriter`] does. The lines
+    /// following within [`FLASH_WINDOW`] are written without waiting for the next one.
+    async fn wait_for_time_pulse(&mut self) {
+        if self.pulse_at.is_some_and(|at| at.elapsed() < FLASH_WINDOW) {
+            return;
+        }
+        // the pulse seen before the command does not count
+        let _ = self.time_pulse.try_changed();
+        let _ = with_timeout(MAX_WRITE_DELAY, self.time_pulse.changed()).await;
+        self.pulse_at = Some(Instant::now());
+    }
+
+    async fn println(&mut self, args: fmt::Arguments<'_>) -> Result<(), EndpointError> {
+        let mut line = heapless::String::<1024>::new();
+        // truncated output is still better than nothing
+        let _ = line.write_fmt(args);
+        let _ = line.push_str("\r\n");
+        for packet in line.as_bytes().chunks(MAX_PACKET_SIZE) {
+            // the host may not be reading
+            self.heartbeat.idle(self.tx.write_packet(packet)).await?;
+        }
+        if line.len().is_multiple_of(MAX_PACKET_SIZE) {
+            self.heartbeat.idle(self.tx.write_packet(&[])).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes pairs of hex digits into `buf`, returns `None` if they do not fit.
+fn decode_hex<'b>(hex: &str, buf: &'b mut [u8]) -> Option<&'b [u8]> {
+    let hex = hex.as_bytes();
+    if !hex.len().is_multiple_of(2) || hex.len() / 2 > buf.len() {
+        return None;
+    }
+    for (dst, pair) in buf.iter_mut().zip(hex.chunks(2)) {
+        let pair = core::str::from_utf8(pair).ok()?;
+        *dst = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(&buf[..hex.len() / 2])
+}
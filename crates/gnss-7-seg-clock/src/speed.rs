@@ -0,0 +1,81 @@
+pub use config::SpeedUnit;
+
+/// Returns 0 if the speed is not significantly larger than its estimated accuracy (`sAcc` of
+/// UBX-NAV-PVT), which is the case when stationary.
+pub fn suppress_noise(ground_speed_mm_s: u32, speed_accuracy_mm_s: u32) -> u32 {
+    if ground_speed_mm_s < speed_accuracy_mm_s.saturating_mul(2) {
+        0
+    } else {
+        ground_speed_mm_s
+    }
+}
+
+/// Maximum and average of the ground speed. The average only takes the samples while moving into
+/// account, and assumes that the samples are taken at a fixed rate.
+#[derive(Copy, Clone, Default, PartialEq, defmt::Format)]
+pub struct SpeedStats {
+    max_mm_s: u32,
+    sum_mm_s: u64,
+    samples: u32,
+}
+
+impl SpeedStats {
+    pub fn update(&mut self, ground_speed_mm_s: u32) {
+        self.max_mm_s = self.max_mm_s.max(ground_speed_mm_s);
+        if ground_speed_mm_s > 0 {
+            self.sum_mm_s += u64::from(ground_speed_mm_s);
+            self.samples += 1;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn max_mm_s(&self) -> u32 {
+        self.max_mm_s
+    }
+
+    pub fn average_mm_s(&self) -> u32 {
+        self.sum_mm_s
+            .checked_div(u64::from(self.samples))
+            .unwrap_or(0) as u32
+    }
+}
+
+/// Moving average of the ground speed over the last second, so that the velocity shown does not
+/// flicker at higher navigation rates. At 1 Hz, the samples are shown as they are.
+#[derive(Copy, Clone, Default)]
+pub struct Smoothing {
+    samples_mm_s: [u32; Self::MAX_SAMPLES],
+    len: usize,
+    next: usize,
+    samples_per_sec: usize,
+}
+
+impl Smoothing {
+    const MAX_SAMPLES: usize = 10;
+
+    /// Returns the average including `ground_speed_mm_s`, or 0 if it is 0 (stationary). The
+    /// samples are discarded when `samples_per_sec` changes.
+    pub fn update(&mut self, ground_speed_mm_s: u32, samples_per_sec: u8) -> u32 {
+        let window = usize::from(samples_per_sec).clamp(1, Self::MAX_SAMPLES);
+        if window != self.samples_per_sec {
+            *self = Self {
+                samples_per_sec: window,
+                ..Default::default()
+            };
+        }
+        self.samples_mm_s[self.next] = ground_speed_mm_s;
+        self.next = (self.next + 1) % window;
+        self.len = (self.len + 1).min(window);
+        if ground_speed_mm_s == 0 {
+            return 0;
+        }
+        let sum: u64 = self.samples_mm_s[..self.len]
+            .iter()
+            .map(|s| u64::from(*s))
+            .sum();
+        (sum / self.len as u64) as u32
+    }
+}
@@ -0,0 +1,37 @@
+use ubx::mon::{AntennaStatus, RfBlock};
+
+use crate::max_m10s::Health;
+use crate::odometer::OdometerRecord;
+use crate::speed::SpeedStats;
+
+/// Runtime information published for the USB shell.
+#[derive(Copy, Clone, Default, PartialEq, defmt::Format)]
+pub struct Status {
+    pub ground_speed_mm_s: Option<u32>,
+    pub speed_stats: SpeedStats,
+    /// `None` while stationary
+    pub heading_1e5_deg: Option<i32>,
+    pub odometer: OdometerRecord,
+    pub config_store: ConfigStore,
+    pub receiver: Health,
+    /// From UBX-MON-RF, `None` until received
+    pub rf: Option<RfBlock>,
+}
+
+impl Status {
+    /// Short or open circuit reported by the antenna supervisor.
+    pub fn antenna_fault(&self) -> Option<AntennaStatus> {
+        self.rf
+            .map(|rf| rf.antenna_status())
+            .filter(|s| matches!(s, AntennaStatus::Short | AntennaStatus::Open))
+    }
+}
+
+/// State of the last write of the config to the flash.
+#[derive(Copy, Clone, Default, PartialEq, defmt::Format)]
+pub enum ConfigStore {
+    #[default]
+    Saved,
+    Pending,
+    Failed,
+}
@@ -0,0 +1,90 @@
+//! Quality of the time: the accuracy estimated by the receiver (tAcc), and the jitter of the time
+//! pulse measured on the clock of the board. The latter includes the latency of the interrupt and
+//! of the executor, and the drift of the crystal.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::Instant;
+
+use misc::timing::{PulseIntervals, RollingStats, Summary};
+use ubx::nav::{NavPvt, NavTimeUtc};
+
+/// Samples kept for the statistics, i.e. a minute of the pulses
+pub const WINDOW: usize = 60;
+
+/// The time pulse is configured to be every second.
+const PULSE_PERIOD_US: u64 = 1_000_000;
+
+struct Timing {
+    pvt_t_acc_ns: Option<u32>,
+    utc_t_acc_ns: Option<u32>,
+    utc_t_acc_stats: RollingStats<WINDOW>,
+    pulses: PulseIntervals<WINDOW>,
+}
+
+static TIMING: Mutex<CriticalSectionRawMutex, RefCell<Timing>> = Mutex::new(RefCell::new(Timing {
+    pvt_t_acc_ns: None,
+    utc_t_acc_ns: None,
+    utc_t_acc_stats: RollingStats::new(),
+    pulses: PulseIntervals::new(PULSE_PERIOD_US),
+}));
+
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub struct Report {
+    /// tAcc of the last UBX-NAV-PVT, in ns
+    pub pvt_t_acc_ns: Option<u32>,
+    /// tAcc of the last UBX-NAV-TIMEUTC, in ns. `None` while UTC is not valid.
+    pub utc_t_acc_ns: Option<u32>,
+    /// Of the last [`WINDOW`] tAcc of UBX-NAV-TIMEUTC, in ns
+    pub utc_t_acc: Option<Summary>,
+    /// Of the last [`WINDOW`] intervals between the pulses minus a second, in ns
+    pub pulse_error: Option<Summary>,
+    /// Allan deviation at 1 s of the pulses against the clock of the board, in 1e-9 (ns per
+    /// second)
+    pub pulse_adev: Option<u32>,
+}
+
+pub fn record_pvt(pvt: &NavPvt) {
+    TIMING.lock(|t| t.borrow_mut().pvt_t_acc_ns = Some(pvt.t_acc));
+}
+
+pub fn record_time_utc(utc: &NavTimeUtc) {
+    TIMING.lock(|t| {
+        let mut t = t.borrow_mut();
+        if utc.valid_utc() {
+            t.utc_t_acc_ns = Some(utc.t_acc);
+            t.utc_t_acc_stats
+                .push(utc.t_acc.min(i32::MAX as u32) as i32);
+        } else {
+            t.utc_t_acc_ns = None;
+        }
+    });
+}
+
+/// Called on the rising edge of the time pulse, as soon as it is seen.
+pub fn record_pulse(at: Instant) {
+    TIMING.lock(|t| t.borrow_mut().pulses.record(at.as_micros()));
+}
+
+/// Starts the statistics over.
+pub fn clear() {
+    TIMING.lock(|t| {
+        let mut t = t.borrow_mut();
+        t.utc_t_acc_stats.clear();
+        t.pulses.clear();
+    });
+}
+
+pub fn report() -> Report {
+    TIMING.lock(|t| {
+        let t = t.borrow();
+        Report {
+            pvt_t_acc_ns: t.pvt_t_acc_ns,
+            utc_t_acc_ns: t.utc_t_acc_ns,
+            utc_t_acc: t.utc_t_acc_stats.summary(),
+            pulse_error: t.pulses.errors_ns().summary(),
+            pulse_adev: t.pulses.errors_ns().allan_deviation(),
+        }
+    })
+}
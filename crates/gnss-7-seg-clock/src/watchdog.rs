@@ -0,0 +1,94 @@
+use core::cell::Cell;
+use core::pin::pin;
+
+use embassy_futures::select::*;
+use embassy_rp::watchdog::Watchdog;
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+
+/// The hardware watchdog reboots if [`Supervisor::run`] itself stops, e.g. the executor is blocked.
+pub const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(5);
+const FEED_PERIOD: Duration = Duration::from_secs(1);
+
+/// Each task has to call [`Heartbeat::beat`] at least this often.
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shows that a task is making progress.
+pub struct Heartbeat {
+    name: &'static str,
+    last: Mutex<CriticalSectionRawMutex, Cell<Instant>>,
+}
+
+impl Heartbeat {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            last: Mutex::new(Cell::new(Instant::from_ticks(0))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn beat(&self) {
+        self.last.lock(|l| l.set(Instant::now()));
+    }
+
+    /// Awaits `fut`, which may legitimately take forever (e.g. waiting for a request), beating
+    /// meanwhile. Only the rest of the task is supervised.
+    pub async fn idle<F: Future>(&self, fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        loop {
+            self.beat();
+            if let Either::First(output) =
+                select(fut.as_mut(), Timer::after(HEARTBEAT_TIMEOUT / 2)).await
+            {
+                self.beat();
+                return output;
+            }
+        }
+    }
+
+    fn is_stalled(&self) -> bool {
+        self.last.lock(|l| l.get()) + HEARTBEAT_TIMEOUT < Instant::now()
+    }
+}
+
+/// Feeds the hardware watchdog as long as all the `heartbeats` are beating.
+pub struct Supervisor<'d> {
+    watchdog: Watchdog,
+    heartbeats: &'d [&'d Heartbeat],
+}
+
+impl<'d> Supervisor<'d> {
+    /// The watchdog is expected to be started with [`WATCHDOG_TIMEOUT`] already, so that the boot
+    /// is also supervised.
+    pub fn new(watchdog: Watchdog, heartbeats: &'d [&'d Heartbeat]) -> Self {
+        Self {
+            watchdog,
+            heartbeats,
+        }
+    }
+
+    /// Returns the first task which stalled. The watchdog is kept fed for [`WATCHDOG_TIMEOUT`]
+    /// more, which leaves time to record it before [`Supervisor::reboot`].
+    pub async fn run(&mut self) -> &'d Heartbeat {
+        let mut ticker = Ticker::every(FEED_PERIOD);
+        loop {
+            self.watchdog.feed(WATCHDOG_TIMEOUT);
+            if let Some(h) = self.heartbeats.iter().find(|h| h.is_stalled()) {
+                defmt::error!("task stalled: {}", h.name);
+                return h;
+            }
+            ticker.next().await;
+        }
+    }
+
+    pub fn reboot(&mut self) -> ! {
+        self.watchdog.trigger_reset();
+        loop {
+            cortex_m::asm::nop();
+        }
+    }
+}
@@ -0,0 +1,76 @@
+/// Exponential backoff of the retries: the delay doubles on each consecutive failure, from
+/// `initial_ms` up to `max_ms`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Backoff {
+    initial_ms: u32,
+    max_ms: u32,
+    failures: u32,
+}
+
+impl Backoff {
+    pub const fn new(initial_ms: u32, max_ms: u32) -> Self {
+        Self {
+            initial_ms,
+            max_ms,
+            failures: 0,
+        }
+    }
+
+    /// Consecutive failures since the last success.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn failed(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    pub fn succeeded(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Delay before the next attempt.
+    pub fn delay_ms(&self) -> u32 {
+        let shift = self.failures.saturating_sub(1).min(31);
+        self.initial_ms
+            .saturating_mul(1 << shift)
+            .min(self.max_ms)
+            .max(self.initial_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::backoff::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn delays() {
+        let mut backoff = Backoff::new(500, 5000);
+        assert_eq!(backoff.delay_ms(), 500);
+        let delays: Vec<_> = (0..8)
+            .map(|_| {
+                backoff.failed();
+                backoff.delay_ms()
+            })
+            .collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000, 5000, 5000, 5000, 5000]);
+        assert_eq!(backoff.failures(), 8);
+
+        backoff.succeeded();
+        assert_eq!(backoff.failures(), 0);
+        assert_eq!(backoff.delay_ms(), 500);
+    }
+
+    #[test]
+    fn saturates() {
+        let mut backoff = Backoff::new(1, u32::MAX);
+        for _ in 0..100 {
+            backoff.failed();
+        }
+        assert_eq!(backoff.delay_ms(), 1 << 31);
+        assert_eq!(Backoff::new(500, 100).delay_ms(), 500);
+    }
+}
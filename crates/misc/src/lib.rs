@@ -1,3 +1,7 @@
 #![no_std]
 
+pub mod backoff;
+pub mod crc;
 pub mod crlf_stream;
+pub mod log_store;
+pub mod timing;
@@ -0,0 +1,532 @@
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::crc::Crc32;
+
+// seq: u32, len: u16, !len: u16, crc: u32
+const HEADER_LEN: usize = 12;
+const CHUNK_LEN: usize = 64;
+const ERASED: u8 = 0xff;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// The flash has less than 2 sectors, or its read/write granularity is not supported.
+    UnsupportedFlash,
+    DataTooLarge(usize),
+    BufferTooSmall(usize),
+    Flash(E),
+}
+
+#[derive(Clone, Copy)]
+struct Record {
+    addr: u32,
+    len: u16,
+    seq: u32,
+}
+
+/// Position in a [`LogStore`], to read all the records with [`LogStore::read_next`].
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Cursor {
+    /// Counted from the sector of the oldest records
+    sector: u32,
+    offset: u32,
+}
+
+/// Append-only store of variable-length records, of which only the newest one is of interest.
+///
+/// Records are appended to a sector until it is full, then the next sector is erased and used,
+/// so the erases are spread over all the sectors of `flash`. Each record has a sequence number
+/// and a CRC; a record torn by a power loss fails the CRC check and the previous one is used
+/// instead. Sectors partially written or erased are never appended to.
+pub struct LogStore<F> {
+    flash: F,
+    latest: Option<Record>,
+    /// Sector appended to, and the offset of its free space (`None` if it must not be appended to)
+    sector: u32,
+    free: Option<u32>,
+}
+
+impl<F: NorFlash> LogStore<F> {
+    /// Scans `flash` for the newest record.
+    pub fn new(flash: F) -> Result<Self, Error<F::Error>> {
+        if flash.capacity() / F::ERASE_SIZE < 2
+            || !CHUNK_LEN.is_multiple_of(F::WRITE_SIZE)
+            || !F::WRITE_SIZE.is_multiple_of(F::READ_SIZE)
+            || !F::ERASE_SIZE.is_multiple_of(F::WRITE_SIZE)
+        {
+            return Err(Error::UnsupportedFlash);
+        }
+        let mut store = Self {
+            flash,
+            latest: None,
+            sector: 0,
+            free: None,
+        };
+        // the first record goes to the sector 0
+        store.sector = store.sectors() - 1;
+        for sector in 0..store.sectors() {
+            store.scan(sector)?;
+        }
+        Ok(store)
+    }
+
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    /// Copies the newest record into `buf`.
+    pub fn read_latest<'b>(
+        &mut self,
+        buf: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, Error<F::Error>> {
+        let Some(record) = self.latest else {
+            return Ok(None);
+        };
+        let len = usize::from(record.len);
+        let Some(buf) = buf.get_mut(..len) else {
+            return Err(Error::BufferTooSmall(len));
+        };
+        let mut pos = 0;
+        self.for_each_chunk(record.addr + HEADER_LEN as u32, len, |chunk| {
+            buf[pos..pos + chunk.len()].copy_from_slice(chunk);
+            pos += chunk.len();
+        })?;
+        Ok(Some(buf))
+    }
+
+    /// Copies the record at `cursor` into `buf` and moves `cursor` to the next one. Starting from
+    /// `Cursor::default()`, the records still on the flash are read from the oldest one.
+    pub fn read_next<'b>(
+        &mut self,
+        cursor: &mut Cursor,
+        buf: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, Error<F::Error>> {
+        while cursor.sector < self.sectors() {
+            let sector = (self.sector + 1 + cursor.sector) % self.sectors();
+            let offset = cursor.offset as usize;
+            let addr = sector * F::ERASE_SIZE as u32 + cursor.offset;
+            let mut header = [0; HEADER_LEN];
+            if offset + HEADER_LEN <= F::ERASE_SIZE {
+                let mut pos = 0;
+                self.for_each_chunk(addr, HEADER_LEN, |chunk| {
+                    header[pos..pos + chunk.len()].copy_from_slice(chunk);
+                    pos += chunk.len();
+                })?;
+            }
+            // the rest of the sector is blank or torn
+            let Some(record) = self.check(addr, &header)? else {
+                *cursor = Cursor {
+                    sector: cursor.sector + 1,
+                    offset: 0,
+                };
+                continue;
+            };
+
+            let len = usize::from(record.len);
+            let Some(buf) = buf.get_mut(..len) else {
+                return Err(Error::BufferTooSmall(len));
+            };
+            let mut pos = 0;
+            self.for_each_chunk(addr + HEADER_LEN as u32, len, |chunk| {
+                buf[pos..pos + chunk.len()].copy_from_slice(chunk);
+                pos += chunk.len();
+            })?;
+            cursor.offset += record_size::<F>(len) as u32;
+            return Ok(Some(buf));
+        }
+        Ok(None)
+    }
+
+    /// Erases all the records.
+    pub fn clear(&mut self) -> Result<(), Error<F::Error>> {
+        self.latest = None;
+        self.sector = self.sectors() - 1;
+        self.free = None;
+        self.flash
+            .erase(0, self.sectors() * F::ERASE_SIZE as u32)
+            .map_err(Error::Flash)
+    }
+
+    pub fn append(&mut self, data: &[u8]) -> Result<(), Error<F::Error>> {
+        let size = record_size::<F>(data.len());
+        if data.len() > usize::from(u16::MAX) || size > F::ERASE_SIZE {
+            return Err(Error::DataTooLarge(data.len()));
+        }
+
+        let free = match self.free {
+            Some(offset) if offset as usize + size <= F::ERASE_SIZE => {
+                let addr = self.sector * F::ERASE_SIZE as u32 + offset;
+                self.is_blank(addr, size)?.then_some(offset)
+            }
+            _ => None,
+        };
+        let offset = match free {
+            Some(offset) => offset,
+            None => {
+                let next = (self.sector + 1) % self.sectors();
+                let from = next * F::ERASE_SIZE as u32;
+                self.free = None;
+                self.flash
+                    .erase(from, from + F::ERASE_SIZE as u32)
+                    .map_err(Error::Flash)?;
+                self.sector = next;
+                0
+            }
+        };
+
+        let seq = self.latest.map_or(0, |r| r.seq.wrapping_add(1));
+        let addr = self.sector * F::ERASE_SIZE as u32 + offset;
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(&seq.to_le_bytes());
+        header[4..6].copy_from_slice(&(data.len() as u16).to_le_bytes());
+        header[6..8].copy_from_slice(&(!(data.len() as u16)).to_le_bytes());
+        let mut crc = Crc32::new();
+        crc.update(&header[..8]);
+        crc.update(data);
+        header[8..12].copy_from_slice(&crc.finish().to_le_bytes());
+
+        // until the write completes, the sector is in an unknown state
+        let mut chunk = [ERASED; CHUNK_LEN];
+        let mut n = 0;
+        let mut pos = addr;
+        for b in header.iter().chain(data) {
+            chunk[n] = *b;
+            n += 1;
+            if n == CHUNK_LEN {
+                self.flash.write(pos, &chunk).map_err(Error::Flash)?;
+                pos += CHUNK_LEN as u32;
+                n = 0;
+            }
+        }
+        if n > 0 {
+            let m = n.next_multiple_of(F::WRITE_SIZE);
+            chunk[n..m].fill(ERASED);
+            self.flash.write(pos, &chunk[..m]).map_err(Error::Flash)?;
+        }
+
+        self.latest = Some(Record {
+            addr,
+            len: data.len() as u16,
+            seq,
+        });
+        self.free = Some(offset + size as u32);
+        Ok(())
+    }
+
+    fn sectors(&self) -> u32 {
+        (self.flash.capacity() / F::ERASE_SIZE) as u32
+    }
+
+    fn scan(&mut self, sector: u32) -> Result<(), Error<F::Error>> {
+        let base = sector * F::ERASE_SIZE as u32;
+        let mut newest_here = false;
+        let mut offset = 0;
+        let free = loop {
+            if offset + HEADER_LEN > F::ERASE_SIZE {
+                break None;
+            }
+            let mut header = [0; HEADER_LEN];
+            let mut pos = 0;
+            self.for_each_chunk(base + offset as u32, HEADER_LEN, |chunk| {
+                header[pos..pos + chunk.len()].copy_from_slice(chunk);
+                pos += chunk.len();
+            })?;
+            if header.iter().all(|b| *b == ERASED) {
+                break Some(offset as u32);
+            }
+            let Some(record) = self.check(base + offset as u32, &header)? else {
+                break None;
+            };
+            if self.latest.is_none_or(|l| record.seq > l.seq) {
+                self.latest = Some(record);
+                newest_here = true;
+            }
+            offset += record_size::<F>(usize::from(record.len));
+        };
+        if newest_here {
+            self.sector = sector;
+            self.free = free;
+        }
+        Ok(())
+    }
+
+    fn check(
+        &mut self,
+        addr: u32,
+        header: &[u8; HEADER_LEN],
+    ) -> Result<Option<Record>, Error<F::Error>> {
+        let seq = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let len = u16::from_le_bytes([header[4], header[5]]);
+        let len_inv = u16::from_le_bytes([header[6], header[7]]);
+        let crc = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        let end = addr as usize % F::ERASE_SIZE + record_size::<F>(usize::from(len));
+        if len != !len_inv || end > F::ERASE_SIZE {
+            return Ok(None);
+        }
+        let mut actual = Crc32::new();
+        actual.update(&header[..8]);
+        self.for_each_chunk(addr + HEADER_LEN as u32, usize::from(len), |chunk| {
+            actual.update(chunk)
+        })?;
+        Ok((actual.finish() == crc).then_some(Record { addr, len, seq }))
+    }
+
+    fn is_blank(&mut self, addr: u32, len: usize) -> Result<bool, Error<F::Error>> {
+        let mut blank = true;
+        self.for_each_chunk(addr, len, |chunk| {
+            blank &= chunk.iter().all(|b| *b == ERASED)
+        })?;
+        Ok(blank)
+    }
+
+    /// Reads `addr..addr + len` in chunks, taking care of the alignment required by `F`.
+    fn for_each_chunk(
+        &mut self,
+        addr: u32,
+        len: usize,
+        mut f: impl FnMut(&[u8]),
+    ) -> Result<(), Error<F::Error>> {
+        let mut chunk = [0; CHUNK_LEN];
+        let addr = addr as usize;
+        let end = addr + len;
+        let mut pos = addr - addr % F::READ_SIZE;
+        while pos < end {
+            let n = CHUNK_LEN.min((end - pos).next_multiple_of(F::READ_SIZE));
+            self.flash
+                .read(pos as u32, &mut chunk[..n])
+                .map_err(Error::Flash)?;
+            f(&chunk[addr.saturating_sub(pos)..(end - pos).min(n)]);
+            pos += n;
+        }
+        Ok(())
+    }
+}
+
+fn record_size<F: NorFlash>(len: usize) -> usize {
+    (HEADER_LEN + len).next_multiple_of(F::WRITE_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::log_store::*;
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+    use std::vec::Vec;
+
+    const SECTOR: usize = 128;
+
+    /// RAM-backed flash. Programming can only clear bits, as on the real NOR flash. Once
+    /// `power_budget` bytes have been programmed or erased, the rest of the operation is lost.
+    #[derive(Clone)]
+    struct RamFlash {
+        mem: [u8; 3 * SECTOR],
+        erase_counts: [u32; 3],
+        power_budget: Option<usize>,
+    }
+
+    impl RamFlash {
+        fn new() -> Self {
+            Self {
+                mem: [ERASED; 3 * SECTOR],
+                erase_counts: [0; 3],
+                power_budget: None,
+            }
+        }
+
+        fn consume_power(&mut self) -> Result<(), NorFlashErrorKind> {
+            match &mut self.power_budget {
+                Some(0) => Err(NorFlashErrorKind::Other),
+                Some(n) => {
+                    *n -= 1;
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl ErrorType for RamFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for RamFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.mem[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.mem.len()
+        }
+    }
+
+    impl NorFlash for RamFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = SECTOR;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            assert!((from as usize).is_multiple_of(SECTOR) && (to as usize).is_multiple_of(SECTOR));
+            for sector in from as usize / SECTOR..to as usize / SECTOR {
+                self.erase_counts[sector] += 1;
+            }
+            for i in from as usize..to as usize {
+                self.consume_power()?;
+                self.mem[i] = ERASED;
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            assert!((offset as usize).is_multiple_of(Self::WRITE_SIZE));
+            assert!(bytes.len().is_multiple_of(Self::WRITE_SIZE));
+            for (i, b) in bytes.iter().enumerate() {
+                self.consume_power()?;
+                self.mem[offset as usize + i] &= *b;
+            }
+            Ok(())
+        }
+    }
+
+    fn latest(flash: RamFlash) -> Option<Vec<u8>> {
+        let mut store = LogStore::new(flash).unwrap();
+        let mut buf = [0; SECTOR];
+        store.read_latest(&mut buf).unwrap().map(|d| d.to_vec())
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(latest(RamFlash::new()), None);
+    }
+
+    #[test]
+    fn append_and_reopen() {
+        let mut store = LogStore::new(RamFlash::new()).unwrap();
+        let mut buf = [0; 16];
+        store.append(b"hello").unwrap();
+        assert_eq!(store.read_latest(&mut buf), Ok(Some(b"hello".as_slice())));
+        store.append(b"").unwrap();
+        assert_eq!(store.read_latest(&mut buf), Ok(Some(b"".as_slice())));
+        store.append(b"world!").unwrap();
+        assert_eq!(store.read_latest(&mut buf), Ok(Some(b"world!".as_slice())));
+
+        assert_eq!(latest(store.into_inner()), Some(b"world!".to_vec()));
+    }
+
+    #[test]
+    fn errors() {
+        let mut store = LogStore::new(RamFlash::new()).unwrap();
+        assert_eq!(
+            store.append(&[0; SECTOR - HEADER_LEN + 1]),
+            Err(Error::DataTooLarge(SECTOR - HEADER_LEN + 1))
+        );
+        store.append(&[1; SECTOR - HEADER_LEN]).unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(
+            store.read_latest(&mut buf),
+            Err(Error::BufferTooSmall(SECTOR - HEADER_LEN))
+        );
+    }
+
+    #[test]
+    fn wear_levelling() {
+        let mut store = LogStore::new(RamFlash::new()).unwrap();
+        for i in 0..300_u32 {
+            store.append(&i.to_le_bytes()).unwrap();
+            if i % 7 == 0 {
+                store = LogStore::new(store.into_inner()).unwrap();
+            }
+        }
+        let flash = store.into_inner();
+        // 8 records of 16 bytes per sector
+        assert_eq!(flash.erase_counts.iter().sum::<u32>(), 300 / 8 + 1);
+        let min = flash.erase_counts.iter().min().unwrap();
+        let max = flash.erase_counts.iter().max().unwrap();
+        assert!(max - min <= 1);
+        assert_eq!(latest(flash), Some(299_u32.to_le_bytes().to_vec()));
+    }
+
+    fn all(store: &mut LogStore<RamFlash>) -> Vec<Vec<u8>> {
+        let mut cursor = Cursor::default();
+        let mut buf = [0; SECTOR];
+        let mut records = Vec::new();
+        while let Some(data) = store.read_next(&mut cursor, &mut buf).unwrap() {
+            records.push(data.to_vec());
+        }
+        records
+    }
+
+    #[test]
+    fn read_all() {
+        let mut store = LogStore::new(RamFlash::new()).unwrap();
+        assert!(all(&mut store).is_empty());
+
+        for i in 0..10_u32 {
+            store.append(&i.to_le_bytes()).unwrap();
+        }
+        let expected: Vec<_> = (0..10_u32).map(|i| i.to_le_bytes().to_vec()).collect();
+        assert_eq!(all(&mut store), expected);
+
+        // 8 records per sector, the oldest ones are erased with the sector 0
+        for i in 10..30_u32 {
+            store.append(&i.to_le_bytes()).unwrap();
+        }
+        let mut store = LogStore::new(store.into_inner()).unwrap();
+        let expected: Vec<_> = (8..30_u32).map(|i| i.to_le_bytes().to_vec()).collect();
+        assert_eq!(all(&mut store), expected);
+
+        let mut cursor = Cursor::default();
+        assert_eq!(
+            store.read_next(&mut cursor, &mut [0; 2]),
+            Err(Error::BufferTooSmall(4))
+        );
+
+        store.clear().unwrap();
+        assert!(all(&mut store).is_empty());
+        assert_eq!(latest(store.into_inner()), None);
+    }
+
+    #[test]
+    fn power_loss() {
+        // the records of various lengths cross the sector boundaries at various offsets
+        let mut flash = RamFlash::new();
+        for i in 0..40_u8 {
+            let previous = latest(flash.clone());
+            let data = [i; 32];
+            let data = &data[..usize::from(i % 5) * 7];
+
+            let mut cut = 0;
+            loop {
+                let mut f = flash.clone();
+                f.power_budget = Some(cut);
+                let mut store = LogStore::new(f).unwrap();
+                let completed = store.append(data).is_ok();
+                let mut f = store.into_inner();
+                f.power_budget = None;
+
+                let after_cut = latest(f.clone());
+                if completed {
+                    assert_eq!(after_cut, Some(data.to_vec()));
+                    flash = f;
+                    break;
+                }
+                assert!(
+                    after_cut == previous || after_cut.as_deref() == Some(data),
+                    "i = {i}, cut = {cut}"
+                );
+
+                // the torn record is skipped
+                let mut store = LogStore::new(f).unwrap();
+                let records = all(&mut store);
+                assert_eq!(records.last().cloned(), after_cut, "i = {i}, cut = {cut}");
+
+                // still usable
+                store.append(b"next").unwrap();
+                assert_eq!(latest(store.into_inner()), Some(b"next".to_vec()));
+                cut += 1;
+            }
+        }
+    }
+}
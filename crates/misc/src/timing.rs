@@ -0,0 +1,251 @@
+/// Rolling statistics of the last `N` samples, e.g. in ns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollingStats<const N: usize> {
+    samples: [i32; N],
+    len: usize,
+    /// Index of the oldest sample once full
+    next: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Summary {
+    pub count: u32,
+    pub min: i32,
+    pub max: i32,
+    /// Rounded towards zero
+    pub mean: i32,
+}
+
+impl<const N: usize> Default for RollingStats<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RollingStats<N> {
+    pub const fn new() -> Self {
+        Self {
+            samples: [0; N],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Adds `sample`, dropping the oldest one once there are `N`.
+    pub fn push(&mut self, sample: i32) {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Oldest first
+    fn iter(&self) -> impl Iterator<Item = i32> + '_ {
+        let start = (self.next + N - self.len) % N;
+        (0..self.len).map(move |i| self.samples[(start + i) % N])
+    }
+
+    /// `None` if there are no samples.
+    pub fn summary(&self) -> Option<Summary> {
+        if self.is_empty() {
+            return None;
+        }
+        let (min, max, sum) = self
+            .iter()
+            .fold((i32::MAX, i32::MIN, 0_i64), |(a, b, s), x| {
+                (a.min(x), b.max(x), s + i64::from(x))
+            });
+        Some(Summary {
+            count: self.len as u32,
+            min,
+            max,
+            mean: (sum / self.len as i64) as i32,
+        })
+    }
+
+    /// Allan deviation at the interval τ of the samples, estimated from the differences of the
+    /// consecutive ones, in the unit of the samples. Each sample is the error of an interval of τ
+    /// (i.e. τ times the fractional frequency), so that the result divided by τ is the Allan
+    /// deviation σ_y(τ). `None` if there are fewer than 2 samples.
+    pub fn allan_deviation(&self) -> Option<u32> {
+        if self.len < 2 {
+            return None;
+        }
+        let sum: u128 = self
+            .iter()
+            .zip(self.iter().skip(1))
+            .map(|(a, b)| (i64::from(b) - i64::from(a)).unsigned_abs().pow(2) as u128)
+            .sum();
+        let variance = sum / (2 * (self.len as u128 - 1));
+        Some(variance.isqrt().min(u32::MAX.into()) as u32)
+    }
+}
+
+/// Errors of the intervals between the pulses of a nominal `period_us`, timestamped by the local
+/// clock in µs.
+///
+/// The pulses which come after more than one and a half periods start over, e.g. when the receiver
+/// has lost the lock and has not generated some of them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PulseIntervals<const N: usize> {
+    period_us: u64,
+    last_us: Option<u64>,
+    /// Interval minus `period_us`, in ns
+    errors_ns: RollingStats<N>,
+}
+
+impl<const N: usize> PulseIntervals<N> {
+    pub const fn new(period_us: u64) -> Self {
+        Self {
+            period_us,
+            last_us: None,
+            errors_ns: RollingStats::new(),
+        }
+    }
+
+    pub fn record(&mut self, at_us: u64) {
+        if let Some(last_us) = self.last_us.replace(at_us) {
+            let interval_us = at_us.saturating_sub(last_us);
+            if interval_us <= self.period_us + self.period_us / 2 {
+                let error_us = interval_us as i64 - self.period_us as i64;
+                self.errors_ns.push((error_us * 1000) as i32);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.last_us = None;
+        self.errors_ns.clear();
+    }
+
+    pub fn errors_ns(&self) -> &RollingStats<N> {
+        &self.errors_ns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::timing::*;
+
+    #[test]
+    fn rolling() {
+        let mut stats = RollingStats::<4>::new();
+        assert_eq!(stats.summary(), None);
+        assert_eq!(stats.allan_deviation(), None);
+
+        stats.push(10);
+        assert_eq!(
+            stats.summary(),
+            Some(Summary {
+                count: 1,
+                min: 10,
+                max: 10,
+                mean: 10,
+            })
+        );
+        assert_eq!(stats.allan_deviation(), None);
+
+        for x in [-20, 30, 5] {
+            stats.push(x);
+        }
+        assert_eq!(
+            stats.summary(),
+            Some(Summary {
+                count: 4,
+                min: -20,
+                max: 30,
+                mean: 6,
+            })
+        );
+
+        // the oldest (10) is dropped
+        stats.push(-7);
+        assert_eq!(
+            stats.summary(),
+            Some(Summary {
+                count: 4,
+                min: -20,
+                max: 30,
+                mean: 2,
+            })
+        );
+
+        stats.clear();
+        assert!(stats.is_empty());
+        assert_eq!(stats.summary(), None);
+    }
+
+    #[test]
+    fn allan_deviation() {
+        let mut stats = RollingStats::<8>::new();
+        // a constant frequency offset does not count
+        for _ in 0..8 {
+            stats.push(12_000);
+        }
+        assert_eq!(stats.allan_deviation(), Some(0));
+
+        // differences of ±200: sqrt(7 * 200^2 / (2 * 7))
+        stats.clear();
+        for x in [100, -100, 100, -100, 100, -100, 100, -100] {
+            stats.push(x);
+        }
+        assert_eq!(stats.allan_deviation(), Some(141));
+
+        // the extremes do not overflow
+        stats.clear();
+        stats.push(i32::MIN);
+        stats.push(i32::MAX);
+        assert_eq!(stats.allan_deviation(), Some(3_037_000_499));
+    }
+
+    #[test]
+    fn pulse_intervals() {
+        let mut pulses = PulseIntervals::<8>::new(1_000_000);
+        pulses.record(5_000_000);
+        assert!(pulses.errors_ns().is_empty());
+
+        pulses.record(6_000_003);
+        pulses.record(6_999_998);
+        assert_eq!(
+            pulses.errors_ns().summary(),
+            Some(Summary {
+                count: 2,
+                min: -5_000,
+                max: 3_000,
+                mean: -1_000,
+            })
+        );
+
+        // missed pulses start over
+        pulses.record(9_000_000);
+        pulses.record(10_000_001);
+        assert_eq!(
+            pulses.errors_ns().summary(),
+            Some(Summary {
+                count: 3,
+                min: -5_000,
+                max: 3_000,
+                mean: -333,
+            })
+        );
+
+        pulses.clear();
+        assert!(pulses.errors_ns().is_empty());
+        pulses.record(11_000_001);
+        assert!(pulses.errors_ns().is_empty());
+    }
+}
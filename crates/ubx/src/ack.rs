@@ -0,0 +1,60 @@
+/// UBX-ACK-ACK (0x05 0x01) and UBX-ACK-NAK (0x05 0x00)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ack {
+    /// Class and ID of the acknowledged message
+    pub class: u8,
+    pub id: u8,
+    /// `false` if rejected (NAK)
+    pub acknowledged: bool,
+}
+
+impl Ack {
+    pub const CLASS: u8 = 0x05;
+    pub const ID_ACK: u8 = 0x01;
+    pub const ID_NAK: u8 = 0x00;
+    pub const LEN: usize = 2;
+
+    pub fn parse(id: u8, payload: &[u8]) -> Option<Self> {
+        let acknowledged = match id {
+            Self::ID_ACK => true,
+            Self::ID_NAK => false,
+            _ => return None,
+        };
+        match *payload {
+            [class, id] => Some(Self {
+                class,
+                id,
+                acknowledged,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_ack() {
+        assert_eq!(
+            Ack::parse(0x01, &[0x06, 0x8a]),
+            Some(Ack {
+                class: 0x06,
+                id: 0x8a,
+                acknowledged: true,
+            })
+        );
+        assert_eq!(
+            Ack::parse(0x00, &[0x06, 0x8c]),
+            Some(Ack {
+                class: 0x06,
+                id: 0x8c,
+                acknowledged: false,
+            })
+        );
+        assert_eq!(Ack::parse(0x02, &[0x06, 0x8a]), None);
+        assert_eq!(Ack::parse(0x01, &[0x06]), None);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{FrameBuilder, u32_le};
+
+/// Configuration layers, used as the bit mask in UBX-CFG-VALSET/VALDEL.
+pub const LAYER_RAM: u8 = 0x01;
+pub const LAYER_BBR: u8 = 0x02;
+pub const LAYER_FLASH: u8 = 0x04;
+
+/// `navBbrMask` of UBX-CFG-RST: clears all the navigation data in BBR.
+pub const NAV_BBR_COLD_START: u16 = 0xffff;
+/// `resetMode` of UBX-CFG-RST
+pub const RESET_MODE_HARDWARE: u8 = 0x00;
+pub const RESET_MODE_SOFTWARE: u8 = 0x01;
+
+/// Size in bytes of the value of `key`, or `None` if the size field of the key is invalid.
+pub fn value_size(key: u32) -> Option<usize> {
+    match (key >> 28) & 0x07 {
+        // 1 bit, stored as a byte
+        0x01 | 0x02 => Some(1),
+        0x03 => Some(2),
+        0x04 => Some(4),
+        0x05 => Some(8),
+        _ => None,
+    }
+}
+
+/// UBX-CFG-VALSET (0x06 0x8a)
+pub struct ValSet;
+
+impl ValSet {
+    pub const CLASS: u8 = 0x06;
+    pub const ID: u8 = 0x8a;
+    pub const MAX_KEYS: usize = 64;
+
+    /// Sets `items`, pairs of a key and its value, in `layers`. The values are truncated to the
+    /// size encoded in the key.
+    pub fn encode<'a>(buf: &'a mut [u8], layers: u8, items: &[(u32, u64)]) -> Option<&'a [u8]> {
+        if items.len() > Self::MAX_KEYS {
+            return None;
+        }
+        let mut frame = FrameBuilder::new(buf, Self::CLASS, Self::ID)?;
+        // version 0 (no transaction), layers, reserved
+        frame.push(&[0x00, layers, 0x00, 0x00])?;
+        for (key, value) in items {
+            let size = value_size(*key)?;
+            frame.push(&key.to_le_bytes())?;
+            frame.push(&value.to_le_bytes()[..size])?;
+        }
+        Some(frame.finish())
+    }
+}
+
+/// UBX-CFG-VALGET (0x06 0x8b), the response to the poll request
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ValGet<'a> {
+    pub layer: u8,
+    pub position: u16,
+    items: &'a [u8],
+}
+
+impl<'a> ValGet<'a> {
+    pub const CLASS: u8 = 0x06;
+    pub const ID: u8 = 0x8b;
+    pub const MAX_KEYS: usize = 64;
+
+    /// Layers as the index (not the bit mask) in UBX-CFG-VALGET
+    pub const LAYER_RAM: u8 = 0;
+    pub const LAYER_BBR: u8 = 1;
+    pub const LAYER_FLASH: u8 = 2;
+    pub const LAYER_DEFAULT: u8 = 7;
+
+    /// Polls the values of `keys` in `layer`, skipping the first `position` of them. The keys
+    /// which are not set in the layer are left out of the response.
+    pub fn poll<'b>(buf: &'b mut [u8], layer: u8, position: u16, keys: &[u32]) -> Option<&'b [u8]> {
+        if keys.len() > Self::MAX_KEYS {
+            return None;
+        }
+        let mut frame = FrameBuilder::new(buf, Self::CLASS, Self::ID)?;
+        // version 0 (request)
+        frame.push(&[0x00, layer])?;
+        frame.push(&position.to_le_bytes())?;
+        for key in keys {
+            frame.push(&key.to_le_bytes())?;
+        }
+        Some(frame.finish())
+    }
+
+    pub fn parse(payload: &'a [u8]) -> Option<Self> {
+        let (header, items) = payload.split_at_checked(4)?;
+        if header[0] != 0x01 {
+            return None;
+        }
+        let valget = Self {
+            layer: header[1],
+            position: u16::from_le_bytes([header[2], header[3]]),
+            items,
+        };
+        // every key has to be followed by its value
+        let mut rest = items;
+        while !rest.is_empty() {
+            let size = value_size(u32_le(rest.get(..4)?, 0))?;
+            rest = rest.get(4 + size..)?;
+        }
+        Some(valget)
+    }
+
+    /// Pairs of a key and its value.
+    pub fn items(&self) -> impl Iterator<Item = (u32, u64)> + use<'a> {
+        let mut rest = self.items;
+        core::iter::from_fn(move || {
+            let key = u32_le(rest.get(..4)?, 0);
+            let size = value_size(key)?;
+            let mut value = [0; 8];
+            value[..size].copy_from_slice(rest.get(4..4 + size)?);
+            rest = &rest[4 + size..];
+            Some((key, u64::from_le_bytes(value)))
+        })
+    }
+}
+
+/// UBX-CFG-VALDEL (0x06 0x8c)
+pub struct ValDel;
+
+impl ValDel {
+    pub const CLASS: u8 = 0x06;
+    pub const ID: u8 = 0x8c;
+    pub const MAX_KEYS: usize = 64;
+
+    /// Deletes `keys` from `layers` (BBR and/or flash).
+    pub fn encode<'a>(buf: &'a mut [u8], layers: u8, keys: &[u32]) -> Option<&'a [u8]> {
+        if keys.len() > Self::MAX_KEYS {
+            return None;
+        }
+        let mut frame = FrameBuilder::new(buf, Self::CLASS, Self::ID)?;
+        // version 0 (no transaction), layers, reserved
+        frame.push(&[0x00, layers, 0x00, 0x00])?;
+        for key in keys {
+            frame.push(&key.to_le_bytes())?;
+        }
+        Some(frame.finish())
+    }
+}
+
+/// UBX-CFG-RST (0x06 0x04). Not acknowledged.
+pub struct Rst;
+
+impl Rst {
+    pub const CLASS: u8 = 0x06;
+    pub const ID: u8 = 0x04;
+
+    pub fn encode(buf: &mut [u8], nav_bbr_mask: u16, reset_mode: u8) -> Option<&[u8]> {
+        let mut frame = FrameBuilder::new(buf, Self::CLASS, Self::ID)?;
+        frame.push(&nav_bbr_mask.to_le_bytes())?;
+        frame.push(&[reset_mode, 0x00])?;
+        Some(frame.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::{UbxFrame, UbxStream};
+
+    #[test]
+    fn test_valset() {
+        let mut buf = [0; 40];
+        let frame = ValSet::encode(
+            &mut buf,
+            LAYER_RAM,
+            &[
+                (0x10a20001, 1),      // CFG-TXREADY-ENABLED
+                (0x30210001, 200),    // CFG-RATE-MEAS
+                (0x40520001, 115200), // CFG-UART1-BAUDRATE
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            frame,
+            [
+                0xb5, 0x62, 0x06, 0x8a, 0x17, 0x00, // header
+                0x00, 0x01, 0x00, 0x00, // version, layers, reserved
+                0x01, 0x00, 0xa2, 0x10, 0x01, // CFG-TXREADY-ENABLED
+                0x01, 0x00, 0x21, 0x30, 0xc8, 0x00, // CFG-RATE-MEAS
+                0x01, 0x00, 0x52, 0x40, 0x00, 0xc2, 0x01, 0x00, // CFG-UART1-BAUDRATE
+                0xcc, 0xd5, // checksum
+            ]
+        );
+
+        assert_eq!(value_size(0x50000000), Some(8));
+        assert_eq!(
+            ValSet::encode(&mut buf, LAYER_RAM, &[(0x00000001, 0)]),
+            None
+        );
+        assert_eq!(
+            ValSet::encode(&mut buf[..20], LAYER_RAM, &[(0x40520001, 0); 2]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_valget() {
+        let mut buf = [0; 24];
+        let frame = ValGet::poll(&mut buf, ValGet::LAYER_FLASH, 0, &[0x10a20001, 0x40520001]);
+        assert_eq!(
+            frame,
+            Some(
+                [
+                    0xb5, 0x62, 0x06, 0x8b, 0x0c, 0x00, // header
+                    0x00, 0x02, 0x00, 0x00, // version, layer, position
+                    0x01, 0x00, 0xa2, 0x10, // CFG-TXREADY-ENABLED
+                    0x01, 0x00, 0x52, 0x40, // CFG-UART1-BAUDRATE
+                    0xe5, 0x4f, // checksum
+                ]
+                .as_slice()
+            )
+        );
+        assert_eq!(ValGet::poll(&mut buf, ValGet::LAYER_RAM, 0, &[0; 5]), None);
+
+        let payload = [
+            0x01, 0x02, 0x00, 0x00, // version, layer, position
+            0x01, 0x00, 0x21, 0x30, 0xc8, 0x00, // CFG-RATE-MEAS
+            0x01, 0x00, 0x52, 0x40, 0x00, 0xc2, 0x01, 0x00, // CFG-UART1-BAUDRATE
+        ];
+        let valget = ValGet::parse(&payload).unwrap();
+        assert_eq!(valget.layer, ValGet::LAYER_FLASH);
+        assert_eq!(valget.position, 0);
+        assert_eq!(
+            valget.items().collect::<std::vec::Vec<_>>(),
+            [(0x30210001, 200), (0x40520001, 115200)]
+        );
+        assert_eq!(
+            ValGet::parse(&payload[..4]).map(|v| v.items().count()),
+            Some(0)
+        );
+        assert_eq!(ValGet::parse(&payload[..17]), None);
+        assert_eq!(ValGet::parse(&payload[..7]), None);
+        let mut request = payload;
+        request[0] = 0x00;
+        assert_eq!(ValGet::parse(&request), None);
+    }
+
+    #[test]
+    fn test_valdel() {
+        let mut buf = [0; 32];
+        let frame =
+            ValDel::encode(&mut buf, LAYER_BBR | LAYER_FLASH, &[0x10a20001, 0x40520001]).unwrap();
+        assert_eq!(
+            frame,
+            [
+                0xb5, 0x62, 0x06, 0x8c, 0x0c, 0x00, // header
+                0x00, 0x06, 0x00, 0x00, // version, layers, reserved
+                0x01, 0x00, 0xa2, 0x10, // CFG-TXREADY-ENABLED
+                0x01, 0x00, 0x52, 0x40, // CFG-UART1-BAUDRATE
+                0xea, 0x8a, // checksum
+            ]
+        );
+
+        let mut stream = UbxStream::<32>::new();
+        stream.buf_unused_mut()[..frame.len()].copy_from_slice(frame);
+        stream.commit(frame.len());
+        assert_eq!(
+            stream.pop(),
+            Some(UbxFrame {
+                class: ValDel::CLASS,
+                id: ValDel::ID,
+                payload: &frame[6..18],
+            })
+        );
+
+        assert_eq!(ValDel::encode(&mut buf, LAYER_BBR, &[0; 6]), None);
+        assert_eq!(ValDel::encode(&mut [0; 256], LAYER_BBR, &[0; 65]), None);
+    }
+
+    #[test]
+    fn test_rst() {
+        let mut buf = [0; 16];
+        assert_eq!(
+            Rst::encode(&mut buf, NAV_BBR_COLD_START, RESET_MODE_SOFTWARE),
+            Some(
+                [
+                    0xb5, 0x62, 0x06, 0x04, 0x04, 0x00, // header
+                    0xff, 0xff, 0x01, 0x00, // payload
+                    0x0d, 0x5f, // checksum
+                ]
+                .as_slice()
+            )
+        );
+        assert_eq!(Rst::encode(&mut buf[..11], 0, 0), None);
+    }
+}
@@ -1,5 +1,13 @@
 #![no_std]
 
+pub mod ack;
+pub mod cfg;
+pub mod mga;
+pub mod mon;
+pub mod nav;
+pub mod sec;
+pub mod tim;
+
 pub fn checksum(buf: &[u8]) -> (u8, u8) {
     let mut ck_a = 0_u8;
     let mut ck_b = 0_u8;
@@ -10,6 +18,66 @@ pub fn checksum(buf: &[u8]) -> (u8, u8) {
     (ck_a, ck_b)
 }
 
+/// Builds a UBX frame in a buffer.
+pub struct FrameBuilder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> FrameBuilder<'a> {
+    /// Returns `None` if `buf` can not hold an empty frame.
+    pub fn new(buf: &'a mut [u8], class: u8, id: u8) -> Option<Self> {
+        if buf.len() < UBX_FRAME_PAYLOAD_OFFSET + UBX_FRAME_CHECKSUM_SIZE {
+            return None;
+        }
+        let header = buf.get_mut(..UBX_FRAME_PAYLOAD_OFFSET)?;
+        header.copy_from_slice(&[UBX_PREAMBLE1, UBX_PREAMBLE2, class, id, 0, 0]);
+        Some(Self {
+            buf,
+            len: UBX_FRAME_PAYLOAD_OFFSET,
+        })
+    }
+
+    /// Appends `bytes` to the payload. Returns `None` if the buffer is too small.
+    pub fn push(&mut self, bytes: &[u8]) -> Option<&mut Self> {
+        let end = self.len + bytes.len();
+        if end + UBX_FRAME_CHECKSUM_SIZE > self.buf.len() {
+            return None;
+        }
+        self.buf[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Some(self)
+    }
+
+    /// Fills the length and the checksum.
+    pub fn finish(self) -> &'a [u8] {
+        let payload_len = (self.len - UBX_FRAME_PAYLOAD_OFFSET) as u16;
+        self.buf[UBX_FRAME_LENGTH_OFFSET..UBX_FRAME_PAYLOAD_OFFSET]
+            .copy_from_slice(&payload_len.to_le_bytes());
+        let (ck_a, ck_b) = checksum(&self.buf[UBX_FRAME_CLASS_OFFSET..self.len]);
+        self.buf[self.len] = ck_a;
+        self.buf[self.len + 1] = ck_b;
+        &self.buf[..self.len + UBX_FRAME_CHECKSUM_SIZE]
+    }
+}
+
+fn u16_le(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn u32_le(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+fn i32_le(buf: &[u8], offset: usize) -> i32 {
+    u32_le(buf, offset) as i32
+}
+
 #[derive(Debug, PartialEq)]
 pub struct UbxFrame<'a> {
     pub class: u8,
@@ -17,6 +85,36 @@ pub struct UbxFrame<'a> {
     pub payload: &'a [u8],
 }
 
+impl<'a> UbxFrame<'a> {
+    /// Length of the whole frame from its first 6 bytes (sync chars, class, id and length), or
+    /// `None` unless they start with the sync chars.
+    pub fn len_from_header(header: &[u8]) -> Option<usize> {
+        match *header {
+            [UBX_PREAMBLE1, UBX_PREAMBLE2, _, _, len_l, len_h, ..] => {
+                Some(UBX_FRAME_METATATA_SIZE + u16::from_le_bytes([len_l, len_h]) as usize)
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses `frame`, which has to be exactly one frame with the correct checksum.
+    pub fn parse(frame: &'a [u8]) -> Option<Self> {
+        if Self::len_from_header(frame)? != frame.len() {
+            return None;
+        }
+        let (ck, checksum_bytes) = frame.split_at(frame.len() - UBX_FRAME_CHECKSUM_SIZE);
+        let (ck_a, ck_b) = checksum(&ck[UBX_FRAME_CLASS_OFFSET..]);
+        if checksum_bytes != [ck_a, ck_b] {
+            return None;
+        }
+        Some(Self {
+            class: frame[UBX_FRAME_CLASS_OFFSET],
+            id: frame[UBX_FRAME_ID_OFFSET],
+            payload: &ck[UBX_FRAME_PAYLOAD_OFFSET..],
+        })
+    }
+}
+
 pub struct UbxStream<const N: usize> {
     buf: [u8; N],
     begin: usize,
@@ -173,6 +271,27 @@ mod tests {
         0xf5, // ck_b
     ];
 
+    #[test]
+    fn test_parse() {
+        assert_eq!(UbxFrame::len_from_header(&UBX_FRAME2[..6]), Some(12));
+        assert_eq!(UbxFrame::len_from_header(&UBX_FRAME2[..5]), None);
+        assert_eq!(UbxFrame::len_from_header(b"abcdef"), None);
+
+        assert_eq!(
+            UbxFrame::parse(&UBX_FRAME2),
+            Some(UbxFrame {
+                class: 0xab,
+                id: 0xcd,
+                payload: &[0xde, 0xad, 0xbe, 0xef],
+            })
+        );
+        assert_eq!(UbxFrame::parse(&UBX_FRAME2[..11]), None);
+
+        let mut corrupted = UBX_FRAME2;
+        corrupted[7] = 0x00;
+        assert_eq!(UbxFrame::parse(&corrupted), None);
+    }
+
     #[test]
     fn test_commit_and_pop() {
         let mut buf = UbxStream::<32>::new();
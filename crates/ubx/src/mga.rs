@@ -0,0 +1,256 @@
+use crate::FrameBuilder;
+
+/// UBX-MGA-INI-POS_LLH (0x13 0x40), the approximate position to speed up the first fix.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IniPosLlh {
+    /// 1e-7 deg
+    pub lat: i32,
+    /// 1e-7 deg
+    pub lon: i32,
+    /// cm above the ellipsoid
+    pub alt: i32,
+    /// cm
+    pub pos_acc: u32,
+}
+
+impl IniPosLlh {
+    pub const CLASS: u8 = 0x13;
+    pub const ID: u8 = 0x40;
+    const TYPE: u8 = 0x01;
+
+    pub fn encode<'a>(&self, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let mut frame = FrameBuilder::new(buf, Self::CLASS, Self::ID)?;
+        // type, version 0, reserved
+        frame.push(&[Self::TYPE, 0x00, 0x00, 0x00])?;
+        frame.push(&self.lat.to_le_bytes())?;
+        frame.push(&self.lon.to_le_bytes())?;
+        frame.push(&self.alt.to_le_bytes())?;
+        frame.push(&self.pos_acc.to_le_bytes())?;
+        Some(frame.finish())
+    }
+}
+
+/// UBX-MGA-INI-TIME_UTC (0x13 0x40), the approximate UTC to speed up the first fix. Valid on
+/// receipt of the message, with the leap seconds unknown.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IniTimeUtc {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub min: u8,
+    pub sec: u8,
+    pub ns: u32,
+    /// Seconds part of the accuracy
+    pub t_acc_s: u16,
+    /// Nanoseconds part of the accuracy
+    pub t_acc_ns: u32,
+}
+
+impl IniTimeUtc {
+    pub const CLASS: u8 = 0x13;
+    pub const ID: u8 = 0x40;
+    const TYPE: u8 = 0x10;
+    /// `ref`: none, i.e. on receipt of the message
+    const REF_NONE: u8 = 0x00;
+    /// `leapSecs`: unknown
+    const LEAP_SECS_UNKNOWN: i8 = -128;
+
+    pub fn encode<'a>(&self, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let mut frame = FrameBuilder::new(buf, Self::CLASS, Self::ID)?;
+        // type, version 0, ref
+        frame.push(&[Self::TYPE, 0x00, Self::REF_NONE])?;
+        frame.push(&Self::LEAP_SECS_UNKNOWN.to_le_bytes())?;
+        frame.push(&self.year.to_le_bytes())?;
+        frame.push(&[self.month, self.day, self.hour, self.min, self.sec, 0x00])?;
+        frame.push(&self.ns.to_le_bytes())?;
+        frame.push(&self.t_acc_s.to_le_bytes())?;
+        frame.push(&[0x00, 0x00])?;
+        frame.push(&self.t_acc_ns.to_le_bytes())?;
+        Some(frame.finish())
+    }
+}
+
+/// UBX-MGA-ANO (0x13 0x20), AssistNow Offline data of a satellite for a day. Only the date is
+/// decoded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ano {
+    pub sv_id: u8,
+    pub gnss_id: u8,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Ano {
+    pub const CLASS: u8 = 0x13;
+    pub const ID: u8 = 0x20;
+    pub const LEN: usize = 76;
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() != Self::LEN {
+            return None;
+        }
+        Some(Self {
+            sv_id: payload[2],
+            gnss_id: payload[3],
+            // years since 2000
+            year: 2000 + u16::from(payload[4]),
+            month: payload[5],
+            day: payload[6],
+        })
+    }
+}
+
+/// UBX-MGA-ACK-DATA0 (0x13 0x60), sent for each UBX-MGA message once CFG-NAVSPG-ACKAIDING is
+/// enabled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ack {
+    /// `false` if the receiver did not use the message
+    pub accepted: bool,
+    /// Why it was not used, 0 if accepted
+    pub info_code: u8,
+    /// ID of the acknowledged message
+    pub msg_id: u8,
+    /// First 4 bytes of the payload of the acknowledged message
+    pub msg_payload_start: [u8; 4],
+}
+
+impl Ack {
+    pub const CLASS: u8 = 0x13;
+    pub const ID: u8 = 0x60;
+    pub const LEN: usize = 8;
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        match *payload {
+            [kind, 0x00, info_code, msg_id, a, b, c, d] => Some(Self {
+                accepted: kind == 0x01,
+                info_code,
+                msg_id,
+                msg_payload_start: [a, b, c, d],
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether this acknowledges the UBX-MGA message with `id` and `payload`.
+    pub fn is_for(&self, id: u8, payload: &[u8]) -> bool {
+        self.msg_id == id && payload.get(..4) == Some(self.msg_payload_start.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_ini_pos_llh() {
+        let pos = IniPosLlh {
+            lat: 356_812_362,
+            lon: 1_397_671_248,
+            alt: 4_000,
+            pos_acc: 10_000_000,
+        };
+        let mut buf = [0; 28];
+        assert_eq!(
+            pos.encode(&mut buf),
+            Some(
+                [
+                    0xb5, 0x62, 0x13, 0x40, 0x14, 0x00, // header
+                    0x01, 0x00, 0x00, 0x00, // type, version, reserved
+                    0x4a, 0x86, 0x44, 0x15, // lat
+                    0x50, 0xc5, 0x4e, 0x53, // lon
+                    0xa0, 0x0f, 0x00, 0x00, // alt
+                    0x80, 0x96, 0x98, 0x00, // posAcc
+                    0xa4, 0x20, // checksum
+                ]
+                .as_slice()
+            )
+        );
+        assert_eq!(pos.encode(&mut buf[..27]), None);
+    }
+
+    #[test]
+    fn test_ini_time_utc() {
+        let time = IniTimeUtc {
+            year: 2026,
+            month: 10,
+            day: 18,
+            hour: 12,
+            min: 34,
+            sec: 56,
+            ns: 500_000_000,
+            t_acc_s: 2,
+            t_acc_ns: 0,
+        };
+        let mut buf = [0; 32];
+        assert_eq!(
+            time.encode(&mut buf),
+            Some(
+                [
+                    0xb5, 0x62, 0x13, 0x40, 0x18, 0x00, // header
+                    0x10, 0x00, 0x00, 0x80, // type, version, ref, leapSecs
+                    0xea, 0x07, 0x0a, 0x12, // year, month, day
+                    0x0c, 0x22, 0x38, 0x00, // hour, minute, second, reserved
+                    0x00, 0x65, 0xcd, 0x1d, // ns
+                    0x02, 0x00, 0x00, 0x00, // tAccS, reserved
+                    0x00, 0x00, 0x00, 0x00, // tAccNs
+                    0xbf, 0x33, // checksum
+                ]
+                .as_slice()
+            )
+        );
+        assert_eq!(time.encode(&mut buf[..31]), None);
+    }
+
+    #[test]
+    fn test_ano() {
+        let mut payload = [0; Ano::LEN];
+        payload[..8].copy_from_slice(&[
+            0x00, 0x00, // type, version
+            0x05, 0x02, // svId, gnssId (=Galileo)
+            0x1a, 0x0a, 0x12, // year (=2026), month, day
+            0x00, // reserved
+        ]);
+        assert_eq!(
+            Ano::parse(&payload),
+            Some(Ano {
+                sv_id: 5,
+                gnss_id: 2,
+                year: 2026,
+                month: 10,
+                day: 18,
+            })
+        );
+        assert_eq!(Ano::parse(&payload[..75]), None);
+    }
+
+    #[test]
+    fn test_ack() {
+        let payload = [
+            0x01, 0x00, // type (=accepted), version
+            0x00, 0x20, // infoCode, msgId (=ANO)
+            0x00, 0x00, 0x05, 0x02, // msgPayloadStart
+        ];
+        let ack = Ack::parse(&payload).unwrap();
+        assert_eq!(
+            ack,
+            Ack {
+                accepted: true,
+                info_code: 0,
+                msg_id: 0x20,
+                msg_payload_start: [0x00, 0x00, 0x05, 0x02],
+            }
+        );
+        assert!(ack.is_for(0x20, &[0x00, 0x00, 0x05, 0x02, 0x1a]));
+        assert!(!ack.is_for(0x20, &[0x00, 0x00, 0x06, 0x02, 0x1a]));
+        assert!(!ack.is_for(0x40, &[0x00, 0x00, 0x05, 0x02, 0x1a]));
+
+        let payload = [0x00, 0x00, 0x02, 0x40, 0x01, 0x00, 0x00, 0x00];
+        let ack = Ack::parse(&payload).unwrap();
+        assert!(!ack.accepted);
+        assert_eq!(ack.info_code, 2);
+        assert_eq!(Ack::parse(&payload[..7]), None);
+    }
+}
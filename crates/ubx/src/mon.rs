@@ -0,0 +1,260 @@
+use crate::{FrameBuilder, u16_le, u32_le};
+
+/// UBX-MON-VER (0x0a 0x04)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MonVer<'a> {
+    /// e.g. "ROM SPG 5.10 (7b202e)"
+    pub sw_version: &'a str,
+    /// e.g. "000A0000"
+    pub hw_version: &'a str,
+    extensions: &'a [u8],
+}
+
+impl<'a> MonVer<'a> {
+    pub const CLASS: u8 = 0x0a;
+    pub const ID: u8 = 0x04;
+    pub const SW_VERSION_LEN: usize = 30;
+    pub const HW_VERSION_LEN: usize = 10;
+    pub const EXTENSION_LEN: usize = 30;
+
+    /// Polls the message, which is answered by UBX-MON-VER instead of UBX-ACK-ACK.
+    pub fn poll(buf: &mut [u8]) -> Option<&[u8]> {
+        Some(FrameBuilder::new(buf, Self::CLASS, Self::ID)?.finish())
+    }
+
+    /// Followed by any number of the extensions.
+    pub fn parse(payload: &'a [u8]) -> Option<Self> {
+        let (sw_version, rest) = payload.split_at_checked(Self::SW_VERSION_LEN)?;
+        let (hw_version, extensions) = rest.split_at_checked(Self::HW_VERSION_LEN)?;
+        if extensions.len() % Self::EXTENSION_LEN != 0 {
+            return None;
+        }
+        let ver = Self {
+            sw_version: c_str(sw_version)?,
+            hw_version: c_str(hw_version)?,
+            extensions,
+        };
+        ver.extensions
+            .chunks_exact(Self::EXTENSION_LEN)
+            .all(|e| c_str(e).is_some())
+            .then_some(ver)
+    }
+
+    /// e.g. "FWVER=SPG 5.10", "PROTVER=34.10", "MOD=MAX-M10S", "GPS;GLO;GAL;BDS"
+    pub fn extensions(&self) -> impl Iterator<Item = &'a str> + use<'a> {
+        self.extensions
+            .chunks_exact(Self::EXTENSION_LEN)
+            .filter_map(c_str)
+    }
+
+    /// Version of the protocol from the `PROTVER=` extension, e.g. `(34, 10)`.
+    pub fn protocol_version(&self) -> Option<(u8, u8)> {
+        let v = self.extensions().find_map(|e| e.strip_prefix("PROTVER="))?;
+        let (major, minor) = v.trim().split_once('.')?;
+        Some((major.parse().ok()?, minor.parse().ok()?))
+    }
+}
+
+/// UBX-MON-RF (0x0a 0x38)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MonRf<'a> {
+    blocks: &'a [u8],
+}
+
+impl<'a> MonRf<'a> {
+    pub const CLASS: u8 = 0x0a;
+    pub const ID: u8 = 0x38;
+    const HEADER_LEN: usize = 4;
+
+    pub fn parse(payload: &'a [u8]) -> Option<Self> {
+        let (header, blocks) = payload.split_at_checked(Self::HEADER_LEN)?;
+        let n_blocks = header[1] as usize;
+        (header[0] == 0x00 && blocks.len() == n_blocks * RfBlock::LEN).then_some(Self { blocks })
+    }
+
+    /// One per RF band.
+    pub fn blocks(&self) -> impl Iterator<Item = RfBlock> + use<'a> {
+        self.blocks.chunks_exact(RfBlock::LEN).map(RfBlock::parse)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RfBlock {
+    pub block_id: u8,
+    pub flags: u8,
+    pub ant_status: u8,
+    pub ant_power: u8,
+    pub post_status: u32,
+    pub noise_per_ms: u16,
+    /// 0 to 8191
+    pub agc_cnt: u16,
+    /// CW jamming, 0 (none) to 255 (strong)
+    pub jam_ind: u8,
+    pub ofs_i: i8,
+    pub mag_i: u8,
+    pub ofs_q: i8,
+    pub mag_q: u8,
+}
+
+impl RfBlock {
+    const LEN: usize = 24;
+
+    fn parse(block: &[u8]) -> Self {
+        Self {
+            block_id: block[0],
+            flags: block[1],
+            ant_status: block[2],
+            ant_power: block[3],
+            post_status: u32_le(block, 4),
+            noise_per_ms: u16_le(block, 12),
+            agc_cnt: u16_le(block, 14),
+            jam_ind: block[16],
+            ofs_i: block[17] as i8,
+            mag_i: block[18],
+            ofs_q: block[19] as i8,
+            mag_q: block[20],
+        }
+    }
+
+    pub fn antenna_status(&self) -> AntennaStatus {
+        match self.ant_status {
+            0 => AntennaStatus::Init,
+            2 => AntennaStatus::Ok,
+            3 => AntennaStatus::Short,
+            4 => AntennaStatus::Open,
+            _ => AntennaStatus::DontKnow,
+        }
+    }
+
+    pub fn jamming_state(&self) -> JammingState {
+        match self.flags & 0x03 {
+            1 => JammingState::Ok,
+            2 => JammingState::Warning,
+            3 => JammingState::Critical,
+            _ => JammingState::Unknown,
+        }
+    }
+}
+
+/// Reported by the antenna supervisor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AntennaStatus {
+    Init,
+    /// The supervisor is not configured.
+    DontKnow,
+    Ok,
+    Short,
+    Open,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JammingState {
+    #[default]
+    Unknown,
+    Ok,
+    /// Interference visible, but the fix is OK.
+    Warning,
+    /// Interference visible, and no fix.
+    Critical,
+}
+
+/// Up to the first NUL, if any.
+fn c_str(bytes: &[u8]) -> Option<&str> {
+    let len = bytes.iter().position(|c| *c == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..len]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    fn field<const N: usize>(s: &str) -> [u8; N] {
+        let mut buf = [0; N];
+        buf[..s.len()].copy_from_slice(s.as_bytes());
+        buf
+    }
+
+    #[test]
+    fn test_mon_ver() {
+        let mut buf = [0; 8];
+        assert_eq!(
+            MonVer::poll(&mut buf),
+            Some([0xb5, 0x62, 0x0a, 0x04, 0x00, 0x00, 0x0e, 0x34].as_slice())
+        );
+        assert_eq!(MonVer::poll(&mut buf[..7]), None);
+
+        let mut payload = std::vec::Vec::new();
+        payload.extend(field::<30>("ROM SPG 5.10 (7b202e)"));
+        payload.extend(field::<10>("000A0000"));
+        payload.extend(field::<30>("FWVER=SPG 5.10"));
+        payload.extend(field::<30>("PROTVER=34.10"));
+        payload.extend(field::<30>("MOD=MAX-M10S"));
+        let ver = MonVer::parse(&payload).unwrap();
+        assert_eq!(ver.sw_version, "ROM SPG 5.10 (7b202e)");
+        assert_eq!(ver.hw_version, "000A0000");
+        assert_eq!(
+            ver.extensions().collect::<std::vec::Vec<_>>(),
+            ["FWVER=SPG 5.10", "PROTVER=34.10", "MOD=MAX-M10S"]
+        );
+        assert_eq!(ver.protocol_version(), Some((34, 10)));
+
+        let ver = MonVer::parse(&payload[..40]).unwrap();
+        assert_eq!(ver.extensions().count(), 0);
+        assert_eq!(ver.protocol_version(), None);
+
+        assert_eq!(MonVer::parse(&payload[..39]), None);
+        assert_eq!(MonVer::parse(&payload[..41]), None);
+        payload[0] = 0xff;
+        assert_eq!(MonVer::parse(&payload), None);
+    }
+
+    #[test]
+    fn test_mon_rf() {
+        let payload = [
+            0x00, 0x01, 0x00, 0x00, // version, nBlocks, reserved0
+            0x00, // blockId
+            0x02, // flags (jammingState=warning)
+            0x03, // antStatus (=SHORT)
+            0x01, // antPower (=ON)
+            0x00, 0x00, 0x00, 0x00, // postStatus
+            0x00, 0x00, 0x00, 0x00, // reserved1
+            0x5e, 0x00, // noisePerMS (=94)
+            0xd0, 0x07, // agcCnt (=2000)
+            0x0f, // jamInd (=15)
+            0xfe, 0x80, 0x01, 0x7f, // ofsI (=-2), magI, ofsQ (=1), magQ
+            0x00, 0x00, 0x00, // reserved2
+        ];
+        let rf = MonRf::parse(&payload).unwrap();
+        let blocks = rf.blocks().collect::<std::vec::Vec<_>>();
+        assert_eq!(
+            blocks,
+            [RfBlock {
+                block_id: 0,
+                flags: 0x02,
+                ant_status: 3,
+                ant_power: 1,
+                post_status: 0,
+                noise_per_ms: 94,
+                agc_cnt: 2000,
+                jam_ind: 15,
+                ofs_i: -2,
+                mag_i: 0x80,
+                ofs_q: 1,
+                mag_q: 0x7f,
+            }]
+        );
+        assert_eq!(blocks[0].antenna_status(), AntennaStatus::Short);
+        assert_eq!(blocks[0].jamming_state(), JammingState::Warning);
+
+        assert_eq!(MonRf::parse(&payload[..4]), None);
+        assert_eq!(MonRf::parse(&payload[..27]), None);
+        assert_eq!(
+            MonRf::parse(&[0x00, 0x00, 0x00, 0x00]).map(|rf| rf.blocks().count()),
+            Some(0)
+        );
+    }
+}
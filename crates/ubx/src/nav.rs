@@ -0,0 +1,260 @@
+use crate::{i32_le, u16_le, u32_le};
+
+/// UBX-NAV-PVT (0x01 0x07)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NavPvt {
+    pub itow: u32,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub min: u8,
+    pub sec: u8,
+    pub valid: u8,
+    /// ns
+    pub t_acc: u32,
+    /// ns
+    pub nano: i32,
+    pub fix_type: u8,
+    pub flags: u8,
+    pub num_sv: u8,
+    /// 1e-7 deg
+    pub lon: i32,
+    /// 1e-7 deg
+    pub lat: i32,
+    /// mm
+    pub h_msl: i32,
+    /// mm
+    pub h_acc: u32,
+    /// mm/s
+    pub g_speed: i32,
+    /// 1e-5 deg
+    pub head_mot: i32,
+    /// mm/s
+    pub s_acc: u32,
+    /// 1e-5 deg
+    pub head_acc: u32,
+}
+
+impl NavPvt {
+    pub const CLASS: u8 = 0x01;
+    pub const ID: u8 = 0x07;
+    pub const LEN: usize = 92;
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() != Self::LEN {
+            return None;
+        }
+        Some(Self {
+            itow: u32_le(payload, 0),
+            year: u16_le(payload, 4),
+            month: payload[6],
+            day: payload[7],
+            hour: payload[8],
+            min: payload[9],
+            sec: payload[10],
+            valid: payload[11],
+            t_acc: u32_le(payload, 12),
+            nano: i32_le(payload, 16),
+            fix_type: payload[20],
+            flags: payload[21],
+            num_sv: payload[23],
+            lon: i32_le(payload, 24),
+            lat: i32_le(payload, 28),
+            h_msl: i32_le(payload, 36),
+            h_acc: u32_le(payload, 40),
+            g_speed: i32_le(payload, 60),
+            head_mot: i32_le(payload, 64),
+            s_acc: u32_le(payload, 68),
+            head_acc: u32_le(payload, 72),
+        })
+    }
+
+    pub fn gnss_fix_ok(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+}
+
+/// UBX-NAV-ODO (0x01 0x09)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NavOdo {
+    pub itow: u32,
+    /// m, since the last reset
+    pub distance: u32,
+    /// m
+    pub total_distance: u32,
+    /// m
+    pub distance_std: u32,
+}
+
+impl NavOdo {
+    pub const CLASS: u8 = 0x01;
+    pub const ID: u8 = 0x09;
+    pub const LEN: usize = 20;
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() != Self::LEN || payload[0] != 0x00 {
+            return None;
+        }
+        Some(Self {
+            itow: u32_le(payload, 4),
+            distance: u32_le(payload, 8),
+            total_distance: u32_le(payload, 12),
+            distance_std: u32_le(payload, 16),
+        })
+    }
+}
+
+/// UBX-NAV-TIMEUTC (0x01 0x21)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NavTimeUtc {
+    pub itow: u32,
+    /// ns
+    pub t_acc: u32,
+    /// ns
+    pub nano: i32,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub min: u8,
+    pub sec: u8,
+    pub valid: u8,
+}
+
+impl NavTimeUtc {
+    pub const CLASS: u8 = 0x01;
+    pub const ID: u8 = 0x21;
+    pub const LEN: usize = 20;
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() != Self::LEN {
+            return None;
+        }
+        Some(Self {
+            itow: u32_le(payload, 0),
+            t_acc: u32_le(payload, 4),
+            nano: i32_le(payload, 8),
+            year: u16_le(payload, 12),
+            month: payload[14],
+            day: payload[15],
+            hour: payload[16],
+            min: payload[17],
+            sec: payload[18],
+            valid: payload[19],
+        })
+    }
+
+    /// `validUTC`, i.e. the leap seconds are known
+    pub fn valid_utc(&self) -> bool {
+        self.valid & 0x04 != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_nav_pvt() {
+        let mut payload = [0_u8; 92];
+        payload[0..4].copy_from_slice(&123_456_u32.to_le_bytes());
+        payload[4..6].copy_from_slice(&2025_u16.to_le_bytes());
+        payload[6..11].copy_from_slice(&[3, 16, 12, 34, 56]);
+        payload[11] = 0x37;
+        payload[12..16].copy_from_slice(&25_u32.to_le_bytes());
+        payload[16..20].copy_from_slice(&(-1234_i32).to_le_bytes());
+        payload[20] = 0x03;
+        payload[21] = 0x01;
+        payload[23] = 12;
+        payload[24..28].copy_from_slice(&1_397_000_000_i32.to_le_bytes());
+        payload[28..32].copy_from_slice(&356_000_000_i32.to_le_bytes());
+        payload[36..40].copy_from_slice(&40_000_i32.to_le_bytes());
+        payload[40..44].copy_from_slice(&1500_u32.to_le_bytes());
+        payload[60..64].copy_from_slice(&8333_i32.to_le_bytes());
+        payload[64..68].copy_from_slice(&9_000_000_i32.to_le_bytes());
+        payload[68..72].copy_from_slice(&300_u32.to_le_bytes());
+        payload[72..76].copy_from_slice(&500_000_u32.to_le_bytes());
+
+        assert_eq!(
+            NavPvt::parse(&payload),
+            Some(NavPvt {
+                itow: 123_456,
+                year: 2025,
+                month: 3,
+                day: 16,
+                hour: 12,
+                min: 34,
+                sec: 56,
+                valid: 0x37,
+                t_acc: 25,
+                nano: -1234,
+                fix_type: 0x03,
+                flags: 0x01,
+                num_sv: 12,
+                lon: 1_397_000_000,
+                lat: 356_000_000,
+                h_msl: 40_000,
+                h_acc: 1500,
+                g_speed: 8333,
+                head_mot: 9_000_000,
+                s_acc: 300,
+                head_acc: 500_000,
+            })
+        );
+        assert!(NavPvt::parse(&payload).unwrap().gnss_fix_ok());
+        assert_eq!(NavPvt::parse(&payload[..91]), None);
+    }
+
+    #[test]
+    fn test_nav_odo() {
+        let payload = [
+            0x00, 0x00, 0x00, 0x00, // version, reserved0
+            0x40, 0xe2, 0x01, 0x00, // iTOW (=123456)
+            0xe8, 0x03, 0x00, 0x00, // distance (=1000)
+            0x10, 0x27, 0x00, 0x00, // totalDistance (=10000)
+            0x05, 0x00, 0x00, 0x00, // distanceStd (=5)
+        ];
+        assert_eq!(
+            NavOdo::parse(&payload),
+            Some(NavOdo {
+                itow: 123_456,
+                distance: 1000,
+                total_distance: 10_000,
+                distance_std: 5,
+            })
+        );
+        assert_eq!(NavOdo::parse(&payload[..19]), None);
+    }
+
+    #[test]
+    fn test_nav_time_utc() {
+        let payload = [
+            0x40, 0xe2, 0x01, 0x00, // iTOW (=123456)
+            0x15, 0x00, 0x00, 0x00, // tAcc (=21)
+            0x2e, 0xfb, 0xff, 0xff, // nano (=-1234)
+            0xea, 0x07, 0x0a, 0x12, // year (=2026), month, day
+            0x0c, 0x22, 0x38, // hour, min, sec
+            0x37, // valid
+        ];
+        let utc = NavTimeUtc::parse(&payload).unwrap();
+        assert_eq!(
+            utc,
+            NavTimeUtc {
+                itow: 123_456,
+                t_acc: 21,
+                nano: -1234,
+                year: 2026,
+                month: 10,
+                day: 18,
+                hour: 12,
+                min: 34,
+                sec: 56,
+                valid: 0x37,
+            }
+        );
+        assert!(utc.valid_utc());
+        assert_eq!(NavTimeUtc::parse(&payload[..19]), None);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::mon::JammingState;
+
+/// UBX-SEC-SIG (0x27 0x09), version 1 as output by the M10 firmware (SPG 5.10).
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SecSig {
+    pub version: u8,
+    pub jam_flags: u8,
+    pub spf_flags: u8,
+}
+
+impl SecSig {
+    pub const CLASS: u8 = 0x27;
+    pub const ID: u8 = 0x09;
+    pub const LEN: usize = 12;
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() != Self::LEN || payload[0] != 0x01 {
+            return None;
+        }
+        Some(Self {
+            version: payload[0],
+            jam_flags: payload[4],
+            spf_flags: payload[8],
+        })
+    }
+
+    pub fn jamming_detection_enabled(&self) -> bool {
+        self.jam_flags & 0x01 != 0
+    }
+
+    pub fn jamming_state(&self) -> JammingState {
+        match (self.jam_flags >> 1) & 0x03 {
+            1 => JammingState::Ok,
+            2 => JammingState::Warning,
+            3 => JammingState::Critical,
+            _ => JammingState::Unknown,
+        }
+    }
+
+    pub fn spoofing_detection_enabled(&self) -> bool {
+        self.spf_flags & 0x01 != 0
+    }
+
+    pub fn spoofing_state(&self) -> SpoofingState {
+        match (self.spf_flags >> 1) & 0x07 {
+            1 => SpoofingState::Ok,
+            2 => SpoofingState::Indicated,
+            3 => SpoofingState::Affirmed,
+            _ => SpoofingState::Unknown,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpoofingState {
+    #[default]
+    Unknown,
+    Ok,
+    /// Possibly spoofed, e.g. the signals are inconsistent.
+    Indicated,
+    /// Spoofed for sure, e.g. the time or position jumped.
+    Affirmed,
+}
+
+impl SpoofingState {
+    pub fn is_spoofed(&self) -> bool {
+        matches!(self, Self::Indicated | Self::Affirmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_sec_sig() {
+        let payload = [
+            0x01, 0x00, 0x00, 0x00, // version, reserved0
+            0x03, 0x00, 0x00, 0x00, // jamFlags (enabled, no jamming), reserved1
+            0x05, 0x00, 0x00, 0x00, // spfFlags (enabled, indicated), reserved2
+        ];
+        let sig = SecSig::parse(&payload).unwrap();
+        assert!(sig.jamming_detection_enabled());
+        assert_eq!(sig.jamming_state(), JammingState::Ok);
+        assert!(sig.spoofing_detection_enabled());
+        assert_eq!(sig.spoofing_state(), SpoofingState::Indicated);
+        assert!(sig.spoofing_state().is_spoofed());
+
+        let sig = SecSig::parse(&[0x01, 0, 0, 0, 0x06, 0, 0, 0, 0x06, 0, 0, 0]).unwrap();
+        assert!(!sig.jamming_detection_enabled());
+        assert_eq!(sig.jamming_state(), JammingState::Critical);
+        assert_eq!(sig.spoofing_state(), SpoofingState::Affirmed);
+
+        assert_eq!(SecSig::parse(&payload[..11]), None);
+        assert_eq!(SecSig::parse(&[0x02; 12]), None);
+    }
+}
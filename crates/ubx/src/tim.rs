@@ -0,0 +1,183 @@
+use crate::{i32_le, u16_le, u32_le};
+
+/// UBX-TIM-TP (0x0d 0x01)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimTp {
+    /// ms
+    pub tow_ms: u32,
+    /// 2^-32 ms
+    pub tow_sub_ms: u32,
+    /// ps
+    pub q_err: i32,
+    pub week: u16,
+    pub flags: u8,
+    pub ref_info: u8,
+}
+
+impl TimTp {
+    pub const CLASS: u8 = 0x0d;
+    pub const ID: u8 = 0x01;
+    pub const LEN: usize = 16;
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() != Self::LEN {
+            return None;
+        }
+        Some(Self {
+            tow_ms: u32_le(payload, 0),
+            tow_sub_ms: u32_le(payload, 4),
+            q_err: i32_le(payload, 8),
+            week: u16_le(payload, 12),
+            flags: payload[14],
+            ref_info: payload[15],
+        })
+    }
+}
+
+/// UBX-TIM-TM2 (0x0d 0x03), the time of the last rising and falling edges on EXTINT.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimTm2 {
+    /// EXTINT channel
+    pub ch: u8,
+    pub flags: u8,
+    /// Rising edges counted
+    pub count: u16,
+    pub wn_r: u16,
+    pub wn_f: u16,
+    pub tow_ms_r: u32,
+    /// ns
+    pub tow_sub_ms_r: u32,
+    pub tow_ms_f: u32,
+    /// ns
+    pub tow_sub_ms_f: u32,
+    /// ns
+    pub acc_est: u32,
+}
+
+/// `timeBase` of [`TimTm2::flags`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TimeBase {
+    Receiver,
+    Gnss,
+    Utc,
+}
+
+impl TimTm2 {
+    pub const CLASS: u8 = 0x0d;
+    pub const ID: u8 = 0x03;
+    pub const LEN: usize = 28;
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() != Self::LEN {
+            return None;
+        }
+        Some(Self {
+            ch: payload[0],
+            flags: payload[1],
+            count: u16_le(payload, 2),
+            wn_r: u16_le(payload, 4),
+            wn_f: u16_le(payload, 6),
+            tow_ms_r: u32_le(payload, 8),
+            tow_sub_ms_r: u32_le(payload, 12),
+            tow_ms_f: u32_le(payload, 16),
+            tow_sub_ms_f: u32_le(payload, 20),
+            acc_est: u32_le(payload, 24),
+        })
+    }
+
+    /// `newRisingEdge`, i.e. the rising edge is new since the last message
+    pub fn new_rising_edge(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    /// `newFallingEdge`
+    pub fn new_falling_edge(&self) -> bool {
+        self.flags & 0x04 != 0
+    }
+
+    /// `time`: the time base is valid
+    pub fn time_valid(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+
+    /// `None` if reserved
+    pub fn time_base(&self) -> Option<TimeBase> {
+        match (self.flags >> 3) & 0x03 {
+            0 => Some(TimeBase::Receiver),
+            1 => Some(TimeBase::Gnss),
+            2 => Some(TimeBase::Utc),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_tim_tp() {
+        let payload = [
+            0x40, 0xe2, 0x01, 0x00, // towMS (=123456)
+            0x00, 0x00, 0x00, 0x00, // towSubMS
+            0x9c, 0xff, 0xff, 0xff, // qErr (=-100)
+            0x0b, 0x09, // week (=2315)
+            0x03, // flags
+            0x00, // refInfo
+        ];
+        assert_eq!(
+            TimTp::parse(&payload),
+            Some(TimTp {
+                tow_ms: 123_456,
+                tow_sub_ms: 0,
+                q_err: -100,
+                week: 2315,
+                flags: 0x03,
+                ref_info: 0x00,
+            })
+        );
+        assert_eq!(TimTp::parse(&payload[..15]), None);
+    }
+
+    #[test]
+    fn test_tim_tm2() {
+        let payload = [
+            0x00, 0xd4, // ch, flags (=new rising edge, time valid, UTC, new falling edge)
+            0x0c, 0x00, // count (=12)
+            0x0b, 0x09, 0x0b, 0x09, // wnR, wnF (=2315)
+            0x40, 0xe2, 0x01, 0x00, // towMsR (=123456)
+            0xf4, 0x01, 0x00, 0x00, // towSubMsR (=500)
+            0x9e, 0xe2, 0x01, 0x00, // towMsF (=123550)
+            0x00, 0x00, 0x00, 0x00, // towSubMsF
+            0x14, 0x00, 0x00, 0x00, // accEst (=20)
+        ];
+        let tm2 = TimTm2::parse(&payload).unwrap();
+        assert_eq!(
+            tm2,
+            TimTm2 {
+                ch: 0,
+                flags: 0xd4,
+                count: 12,
+                wn_r: 2315,
+                wn_f: 2315,
+                tow_ms_r: 123_456,
+                tow_sub_ms_r: 500,
+                tow_ms_f: 123_550,
+                tow_sub_ms_f: 0,
+                acc_est: 20,
+            }
+        );
+        assert!(tm2.new_rising_edge());
+        assert!(tm2.new_falling_edge());
+        assert!(tm2.time_valid());
+        assert_eq!(tm2.time_base(), Some(TimeBase::Utc));
+        assert_eq!(TimTm2::parse(&payload[..27]), None);
+
+        let tm2 = TimTm2 { flags: 0x08, ..tm2 };
+        assert!(!tm2.new_rising_edge());
+        assert!(!tm2.new_falling_edge());
+        assert!(!tm2.time_valid());
+        assert_eq!(tm2.time_base(), Some(TimeBase::Gnss));
+    }
+}